@@ -1,56 +1,117 @@
 use std::fmt;
+use std::ops::{Deref, DerefMut};
 use serde::{Serialize, Deserialize};
+use num_bigint::BigInt;
 
 /// Top-level program
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Program {
-    Statements(Vec<Statement>),
+    Statements(Vec<Located>),
 }
 
 /// Statements
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Statement {
-    Let { name: String, value: Expression },
+    Let {
+        name: String,
+        ty: Option<Type>, // `let name: ty = value;`
+        value: Expression,
+        is_public: bool,
+    },
     Assign { name: String, value: Expression },
     FunctionDef { 
         file_prefix: String,
         is_main: bool,
+        is_public: bool,
         name: String,
         params: Vec<String>,
-        body: Vec<Statement>,
+        param_types: Vec<Option<Type>>, // One per parameter
+        return_type: Option<Type>,
+        body: Vec<Located>,
+    },
+    Struct {
+        name: String,
+        fields: Vec<String>,
+    },
+    Impl {
+        name: String,
+        methods: Vec<Located>, // FunctionDef statements
+    },
+    Enum {
+        name: String,
+        variants: Vec<Variant>,
+    },
+    Match {
+        subject: Expression,
+        arms: Vec<MatchArm>,
     },
     Return(Expression),
     If {
         condition: Expression,
-        then_branch: Vec<Statement>,
-        else_branch: Option<Vec<Statement>>,
+        then_branch: Vec<Located>,
+        else_branch: Option<Vec<Located>>,
     },
-    While { condition: Expression, body: Vec<Statement> },
+    While { condition: Expression, body: Vec<Located> },
     For {
         init: Option<Box<Statement>>,
         condition: Option<Expression>,
         update: Option<Box<Statement>>,
-        body: Vec<Statement>,
+        body: Vec<Located>,
+    },
+    ForIn { // `for x in iterable` or `for k, v in iterable`
+        names: Vec<String>, // One name, or two to unpack pairs
+        iterable: Expression,
+        body: Vec<Located>,
     },
     ExprStmt(Expression),
-    Block(Vec<Statement>),
+    Block(Vec<Located>),
     Print(Expression),
     Expression(Expression),
-    Import(String),
+    Import {
+        module: String,
+        alias: Option<String>,
+    },
+    ImportFrom {
+        module: String,
+        names: Vec<String>,
+    },
     Try {
-        body: Vec<Statement>,
-        catch: Vec<Statement>,
+        body: Vec<Located>,
+        catch: Vec<Located>,
     },
 }
 
+/// A statement of a block or of the top level of a program, with the
+/// 1-based line where it starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Located {
+    pub line: usize,
+    pub statement: Statement,
+}
+
+impl Deref for Located {
+    type Target = Statement;
+
+    fn deref(&self) -> &Statement {
+        &self.statement
+    }
+}
+
+impl DerefMut for Located {
+    fn deref_mut(&mut self) -> &mut Statement {
+        &mut self.statement
+    }
+}
+
 /// Expressions
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Expression {
     Number(i64),
+    BigInt(BigInt), // Integer literal too large for 64 bits
     Float(f64),
     Bool(bool),
     String(String),
-    Identifier(String),
+    Identifier(String, Option<usize>), // Name and the index of the scope defining it, set by the resolver
     Binary {
         left: Box<Expression>,
         op: BinaryOp,
@@ -60,6 +121,20 @@ pub enum Expression {
         op: UnaryOp,
         expr: Box<Expression>,
     },
+    CompoundAssign { // `target op= value`; `x++` is `x += 1`
+        target: Box<Expression>, // Variable, array element or field
+        op: BinaryOp,
+        value: Box<Expression>,
+    },
+    Range { // `start..end`, excluding `end`
+        start: Box<Expression>,
+        end: Box<Expression>,
+    },
+    Conditional { // `condition ? then_branch : else_branch`
+        condition: Box<Expression>,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>,
+    },
     Call {
         callee: Box<Expression>,
         arguments: Vec<Expression>,
@@ -67,13 +142,92 @@ pub enum Expression {
     Array {
         elements: Vec<Expression>,
     },
+    Map {
+        entries: Vec<(Expression, Expression)>,
+    },
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, Expression)>,
+    },
+    Match {
+        subject: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+    Interpolation {
+        parts: Vec<InterpolationPart>,
+    },
     ArrayAccess {
         array: Box<Expression>,
         index: Box<Expression>,
     },
+    FieldAccess {
+        object: Box<Expression>,
+        field: String,
+    },
     Closure {
         params: Vec<String>,
-        body: Vec<Statement>,
+        body: Vec<Located>,
+    },
+}
+
+/// Position of a piece of source code: the 1-based line and column where it
+/// starts and its byte range in the file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A piece of an interpolated string such as `"hello ${name}"`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InterpolationPart {
+    Text(String),
+    Expression { expr: Expression, span: Span },
+}
+
+/// A variant of an `enum` declaration, with the names of its positional fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+/// One `pattern if guard => body` arm of a `match`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: ArmBody,
+}
+
+/// Arms of a `match` expression are expressions; a `match` statement may
+/// also use blocks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ArmBody {
+    Expression(Expression),
+    Block(Vec<Located>),
+}
+
+/// Patterns of `match` arms
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    Wildcard,             // _
+    Literal(Expression),  // 1, -2.5, "text", true
+    Binding(String),      // name
+    Array {
+        elements: Vec<Pattern>,
+        rest: Option<Option<String>>, // `..` or `..name` after the elements
+    },
+    Struct {
+        name: String,
+        fields: Vec<(String, Pattern)>, // Fields not listed are ignored
+    },
+    Enum {
+        name: String,
+        variant: String,
+        fields: Vec<Pattern>,
     },
 }
 
@@ -93,6 +247,11 @@ pub enum BinaryOp {
     GreaterThanOrEqual,
     And,
     Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 impl fmt::Display for BinaryOp {
@@ -111,6 +270,11 @@ impl fmt::Display for BinaryOp {
             BinaryOp::GreaterThanOrEqual => ">=",
             BinaryOp::And => "&&",
             BinaryOp::Or => "||",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
         };
         write!(f, "{}", s)
     }
@@ -121,6 +285,7 @@ impl fmt::Display for BinaryOp {
 pub enum UnaryOp {
     Negate, // -
     Not,    // !
+    BitNot, // ~
 }
 
 impl fmt::Display for UnaryOp {
@@ -128,6 +293,7 @@ impl fmt::Display for UnaryOp {
         let s = match self {
             UnaryOp::Negate => "-",
             UnaryOp::Not => "!",
+            UnaryOp::BitNot => "~",
         };
         write!(f, "{}", s)
     }
@@ -141,9 +307,40 @@ pub enum Type {
     Bool,
     String,
     Array(Box<Type>),
+    Map(Box<Type>, Box<Type>), // Key and value types
+    Range,
     Void,
     Function {
         params: Vec<Type>,
         return_type: Box<Type>,
     },
+    Named(String), // A struct or enum, possibly `module.Name`
+    Any,           // Not known until run time
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Map(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Type::Range => write!(f, "range"),
+            Type::Void => write!(f, "void"),
+            Type::Function { params, return_type } => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") -> {}", return_type)
+            }
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Any => write!(f, "any"),
+        }
+    }
 }
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use crate::ast::{ArmBody, Expression, InterpolationPart, Located, MatchArm, Pattern, Statement};
+use crate::names::Diagnostic;
+
+/// Check a parsed program for likely mistakes that are not errors. The
+/// returned warnings never stop the program from running.
+pub fn check(program: &[Located]) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        enums: HashMap::new(),
+        function: None,
+        line: 1,
+        warnings: Vec::new(),
+    };
+
+    for stmt in program {
+        if let Statement::Enum { name, variants } = &stmt.statement {
+            let variants = variants.iter().map(|v| v.name.clone()).collect();
+            checker.enums.insert(name.clone(), variants);
+        }
+    }
+    checker.statements(program);
+
+    checker.warnings
+}
+
+struct Checker {
+    enums: HashMap<String, Vec<String>>, // Enums declared in the program -> variant names
+    function: Option<String>,            // Function being checked, for messages
+    line: usize,                         // Line of the statement being checked
+    warnings: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn statements(&mut self, statements: &[Located]) {
+        let outer = self.line;
+        for stmt in statements {
+            self.line = stmt.line;
+            self.statement(stmt);
+        }
+        self.line = outer;
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let { value, .. } | Statement::Assign { value, .. } => {
+                self.expression(value)
+            }
+            Statement::FunctionDef {
+                name,
+                body,
+                is_main,
+                ..
+            } => {
+                let name = if *is_main {
+                    "main".to_string()
+                } else {
+                    name.clone()
+                };
+                let outer = self.function.replace(name);
+                self.statements(body);
+                self.function = outer;
+            }
+            Statement::Impl { name, methods } => {
+                for method in methods {
+                    if let Statement::FunctionDef {
+                        name: method, body, ..
+                    } = &method.statement
+                    {
+                        let outer = self.function.replace(format!("{}.{}", name, method));
+                        self.statements(body);
+                        self.function = outer;
+                    }
+                }
+            }
+            Statement::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+            }
+            Statement::Return(expr)
+            | Statement::ExprStmt(expr)
+            | Statement::Print(expr)
+            | Statement::Expression(expr) => self.expression(expr),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Statement::While { condition, body } => {
+                self.expression(condition);
+                self.statements(body);
+            }
+            Statement::For {
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                if let Some(update) = update {
+                    self.statement(update);
+                }
+                self.statements(body);
+            }
+            Statement::ForIn { iterable, body, .. } => {
+                self.expression(iterable);
+                self.statements(body);
+            }
+            Statement::Block(statements) => self.statements(statements),
+            Statement::Try { body, catch } => {
+                self.statements(body);
+                self.statements(catch);
+            }
+            Statement::Struct { .. }
+            | Statement::Enum { .. }
+            | Statement::Import { .. }
+            | Statement::ImportFrom { .. } => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary { expr, .. } => self.expression(expr),
+            Expression::CompoundAssign { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            Expression::Range { start, end } => {
+                self.expression(start);
+                self.expression(end);
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expression::Call { callee, arguments } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Array { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+            }
+            Expression::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+            }
+            Expression::Interpolation { parts } => {
+                for part in parts {
+                    if let InterpolationPart::Expression { expr, .. } = part {
+                        self.expression(expr);
+                    }
+                }
+            }
+            Expression::ArrayAccess { array, index } => {
+                self.expression(array);
+                self.expression(index);
+            }
+            Expression::FieldAccess { object, .. } => self.expression(object),
+            Expression::Closure { body, .. } => self.statements(body),
+            Expression::Number(_)
+            | Expression::BigInt(_)
+            | Expression::Float(_)
+            | Expression::Bool(_)
+            | Expression::String(_)
+            | Expression::Identifier(..) => {}
+        }
+    }
+
+    fn arms(&mut self, arms: &[MatchArm]) {
+        for arm in arms {
+            if let Some(guard) = &arm.guard {
+                self.expression(guard);
+            }
+            match &arm.body {
+                ArmBody::Expression(expr) => self.expression(expr),
+                ArmBody::Block(statements) => self.statements(statements),
+            }
+        }
+
+        if let Some(missing) = self.missing_cases(arms) {
+            let location = match &self.function {
+                Some(function) => format!(" in function '{}'", function),
+                None => String::new(),
+            };
+            let message = format!(
+                "Non-exhaustive match{}: {} not covered; add a '_' arm",
+                location, missing
+            );
+            self.warnings.push(Diagnostic {
+                line: self.line,
+                message,
+            });
+        }
+    }
+
+    // Describe the values no arm is sure to match, when that can be told from
+    // the patterns alone. Guarded arms may fail, so they cover nothing.
+    fn missing_cases(&self, arms: &[MatchArm]) -> Option<String> {
+        let patterns: Vec<&Pattern> = arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| &arm.pattern)
+            .collect();
+
+        if patterns.iter().any(|p| is_irrefutable(p)) {
+            return None;
+        }
+
+        // Arms over the variants of an enum declared in this program
+        let enum_name = arms.iter().find_map(|arm| match &arm.pattern {
+            Pattern::Enum { name, .. } => Some(name),
+            _ => None,
+        });
+        if let Some((enum_name, variants)) =
+            enum_name.and_then(|name| self.enums.get_key_value(name))
+        {
+            let missing: Vec<String> = variants
+                .iter()
+                .filter(|variant| !patterns.iter().any(|p| matches!(p,
+                    Pattern::Enum { variant: v, fields, .. } if v == *variant && fields.iter().all(is_irrefutable)
+                )))
+                .map(|variant| format!("{}.{}", enum_name, variant))
+                .collect();
+            return if missing.is_empty() {
+                None
+            } else {
+                Some(missing.join(", "))
+            };
+        }
+
+        // Only two booleans exist
+        let covers = |b: bool| {
+            patterns
+                .iter()
+                .any(|p| **p == Pattern::Literal(Expression::Bool(b)))
+        };
+        if arms
+            .iter()
+            .any(|arm| matches!(arm.pattern, Pattern::Literal(Expression::Bool(_))))
+        {
+            return match (covers(true), covers(false)) {
+                (true, true) => None,
+                (true, false) => Some("false".to_string()),
+                (false, true) => Some("true".to_string()),
+                (false, false) => Some("true, false".to_string()),
+            };
+        }
+
+        // Numbers and strings always have values left over
+        if !arms.is_empty()
+            && arms
+                .iter()
+                .all(|arm| matches!(arm.pattern, Pattern::Literal(_)))
+        {
+            return Some("values other than the listed literals".to_string());
+        }
+
+        None
+    }
+}
+
+fn is_irrefutable(pattern: &Pattern) -> bool {
+    matches!(pattern, Pattern::Wildcard | Pattern::Binding(_))
+}
@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+
+/// Command line interface of the `kode` binary
+#[derive(Debug, Parser)]
+#[clap(name = "kode", version, about = "Kode Programming Language")]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a Kode program (.kode source or .kdc bytecode)
+    Run {
+        /// Path to the program, or `-` to read source from stdin. Defaults to
+        /// the entry of the package in the current directory.
+        #[clap(value_parser)]
+        file: Option<PathBuf>,
+
+        /// Run the given source code instead of a file
+        #[clap(long, short, value_parser, conflicts_with = "file")]
+        eval: Option<String>,
+
+        /// Enable verbose output
+        #[clap(long, short)]
+        verbose: bool,
+
+        /// Show execution time
+        #[clap(long)]
+        time: bool,
+
+        /// Extra directory to search for imported modules (repeatable)
+        #[clap(long, value_parser, value_name = "DIR")]
+        lib_path: Vec<PathBuf>,
+
+        /// Arguments passed to the program, available through `args()`
+        #[clap(last = true, value_parser)]
+        args: Vec<String>,
+    },
+
+    /// Compile a Kode program to a .kdc file
+    Build {
+        /// Path to the .kode source file. Defaults to the entry of the
+        /// package in the current directory.
+        #[clap(value_parser)]
+        file: Option<PathBuf>,
+
+        /// Enable verbose output
+        #[clap(long, short)]
+        verbose: bool,
+
+        /// Enable optimization
+        #[clap(long)]
+        optimize: bool,
+
+        /// Compile to a native executable instead of a .kdc file
+        #[clap(long)]
+        native: bool,
+
+        /// Build only, don't run
+        #[clap(long)]
+        no_run: bool,
+
+        /// Extra directory to search for imported modules when running (repeatable)
+        #[clap(long, value_parser, value_name = "DIR")]
+        lib_path: Vec<PathBuf>,
+    },
+
+    /// Check a Kode program for name and type errors without running it
+    Check {
+        /// Path to the .kode source file. Defaults to the entry of the
+        /// package in the current directory.
+        #[clap(value_parser)]
+        file: Option<PathBuf>,
+    },
+
+    /// Report likely mistakes in a Kode program without running it
+    Lint {
+        /// Path to the .kode source file. Defaults to the entry of the
+        /// package in the current directory.
+        #[clap(value_parser)]
+        file: Option<PathBuf>,
+    },
+
+    /// Format Kode source files in place
+    Fmt {
+        /// Files to format, or `-` to format stdin to stdout. Defaults to
+        /// every .kode file of the package in the current directory.
+        #[clap(value_parser)]
+        files: Vec<PathBuf>,
+
+        /// Don't write the files; fail if any of them isn't formatted
+        #[clap(long)]
+        check: bool,
+    },
+
+    /// Start a language server for editors, over stdin and stdout
+    Lsp,
+
+    /// Debug a Kode program with an editor, over stdin and stdout
+    Debug {
+        /// Path to the .kode source file
+        #[clap(value_parser)]
+        file: PathBuf,
+
+        /// Extra directory to search for imported modules (repeatable)
+        #[clap(long, value_parser, value_name = "DIR")]
+        lib_path: Vec<PathBuf>,
+
+        /// Arguments passed to the program, available through `args()`
+        #[clap(last = true, value_parser)]
+        args: Vec<String>,
+    },
+
+    /// Create a new package with a kode.toml manifest
+    Init {
+        /// Directory of the package
+        #[clap(value_parser, default_value = ".")]
+        path: PathBuf,
+
+        /// Package name, defaults to the directory name
+        #[clap(long, value_parser)]
+        name: Option<String>,
+    },
+
+    /// Add a dependency to the package in the current directory
+    Add {
+        /// Name of the dependency, which is also its import name
+        #[clap(value_parser)]
+        name: String,
+
+        /// Use the package in this directory
+        #[clap(long, value_parser, conflicts_with_all = &["git", "version"])]
+        path: Option<String>,
+
+        /// Use the package in this git repository
+        #[clap(long, value_parser, conflicts_with = "version")]
+        git: Option<String>,
+
+        /// Git revision (branch, tag or commit) to check out
+        #[clap(long, value_parser, requires = "git")]
+        rev: Option<String>,
+
+        /// Version requirement to look up in the local registry
+        #[clap(long, value_parser)]
+        version: Option<String>,
+    },
+
+    /// Resolve the dependencies of the current package, fetch git
+    /// dependencies and update kode.lock
+    Install,
+
+    /// Start an interactive REPL session
+    Repl,
+
+    /// Show compiler version
+    Version,
+
+    /// Generate a shell completion script
+    Completions {
+        /// Shell to generate the script for
+        #[clap(value_parser)]
+        shell: Shell,
+    },
+}
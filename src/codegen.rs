@@ -1,20 +1,114 @@
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::{Builder, BuilderError};
 use inkwell::context::Context;
+use inkwell::intrinsics::Intrinsic;
 use inkwell::module::Module;
-use inkwell::builder::Builder;
-use inkwell::values::{FunctionValue, GlobalValue};
-use inkwell::OptimizationLevel;
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, PointerType, StructType};
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValueEnum, FloatValue, FunctionValue, IntValue, PointerValue, StructValue,
+};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel};
 use inkwell::targets::{CodeModel, RelocMode, InitializationConfig, Target, TargetMachine};
-use inkwell::AddressSpace;
 use inkwell::passes::PassManager;
+use std::collections::HashMap;
+use std::fs;
 use std::path::Path;
-use crate::ast::{Statement, Expression};
+use std::process;
+use crate::ast::{BinaryOp, Expression, InterpolationPart, Located, Statement, Type, UnaryOp};
+use crate::types;
+
+// Tags of runtime-tagged values, which stand in for values of type `any`
+const TAG_VOID: u64 = 0;
+const TAG_INT: u64 = 1;
+const TAG_FLOAT: u64 = 2;
+const TAG_BOOL: u64 = 3;
+const TAG_STRING: u64 = 4;
+
+const OVERFLOW: &str = "Integer overflow; native code has no bigints";
+
+/// Compile a type-checked program to a native executable at `output`,
+/// linking it with the system C compiler
+pub fn compile_native(ast: &[Located], optimize: bool, output: &Path) -> Result<(), String> {
+    let context = Context::create();
+    let mut generator = CodeGenerator::new(&context);
+    generator.generate(ast, optimize)?;
+
+    let object = output.with_extension("o");
+    generator.write_object_file(&object)?;
+    let status = process::Command::new("cc")
+        .arg(&object)
+        .arg("-o")
+        .arg(output)
+        .status()
+        .map_err(|e| format!("Failed to run the linker 'cc': {}", e))?;
+    let _ = fs::remove_file(&object);
+
+    if !status.success() {
+        return Err(format!("Failed to link {}", output.display()));
+    }
+    Ok(())
+}
+
+// A value computed by generated code. Values whose type the checker knows are
+// unboxed; values of type `any` carry a tag that is checked when they are used.
+#[derive(Clone, Copy)]
+enum Typed<'ctx> {
+    Int(IntValue<'ctx>),      // i64
+    Float(FloatValue<'ctx>),  // f64
+    Bool(IntValue<'ctx>),     // i1
+    Str(PointerValue<'ctx>),  // NUL-terminated i8*
+    Any(StructValue<'ctx>),   // { i8 tag, i64 payload }
+    Void,
+}
+
+impl<'ctx> Typed<'ctx> {
+    fn ty(&self) -> Type {
+        match self {
+            Typed::Int(_) => Type::Int,
+            Typed::Float(_) => Type::Float,
+            Typed::Bool(_) => Type::Bool,
+            Typed::Str(_) => Type::String,
+            Typed::Any(_) => Type::Any,
+            Typed::Void => Type::Void,
+        }
+    }
+
+    fn basic(self) -> Option<BasicValueEnum<'ctx>> {
+        match self {
+            Typed::Int(value) | Typed::Bool(value) => Some(value.into()),
+            Typed::Float(value) => Some(value.into()),
+            Typed::Str(value) => Some(value.into()),
+            Typed::Any(value) => Some(value.into()),
+            Typed::Void => None,
+        }
+    }
+}
+
+// A local variable: a stack slot holding a value of type `ty`
+#[derive(Clone)]
+struct Local<'ctx> {
+    ptr: PointerValue<'ctx>,
+    ty: Type,
+    annotated: bool,
+}
+
+#[derive(Clone)]
+struct NativeFunction<'ctx> {
+    value: FunctionValue<'ctx>,
+    params: Vec<Type>,
+    return_type: Type,
+}
 
 pub struct CodeGenerator<'ctx> {
     context: &'ctx Context,
     module: Module<'ctx>,
     builder: Builder<'ctx>,
     main_fn: Option<FunctionValue<'ctx>>,
-    pass_manager: PassManager<FunctionValue<'ctx>>
+    pass_manager: PassManager<FunctionValue<'ctx>>,
+    functions: HashMap<String, NativeFunction<'ctx>>, // Functions of the program
+    function: Option<FunctionValue<'ctx>>,             // Function being generated
+    return_type: Type,                                // Its return type
+    scopes: Vec<HashMap<String, Local<'ctx>>>,
 }
 
 impl<'ctx> CodeGenerator<'ctx> {
@@ -23,38 +117,88 @@ impl<'ctx> CodeGenerator<'ctx> {
         let builder = context.create_builder();
         let pass_manager = PassManager::create(&module);
 
-        // Create printf declaration
+        // Declare the C library functions the generated code calls
         let i32_type = context.i32_type();
+        let i64_type = context.i64_type();
         let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
         let printf_type = i32_type.fn_type(&[i8_ptr_type.into()], true);
         module.add_function("printf", printf_type, None);
+        let dprintf_type = i32_type.fn_type(&[i32_type.into(), i8_ptr_type.into()], true);
+        module.add_function("dprintf", dprintf_type, None);
+        let snprintf_type = i32_type.fn_type(&[i8_ptr_type.into(), i64_type.into(), i8_ptr_type.into()], true);
+        module.add_function("snprintf", snprintf_type, None);
+        module.add_function("malloc", i8_ptr_type.fn_type(&[i64_type.into()], false), None);
+        module.add_function("strcmp", i32_type.fn_type(&[i8_ptr_type.into(), i8_ptr_type.into()], false), None);
+        module.add_function("exit", context.void_type().fn_type(&[i32_type.into()], false), None);
 
         CodeGenerator {
             context,
             module,
             builder,
             main_fn: None,
-            pass_manager
+            pass_manager,
+            functions: HashMap::new(),
+            function: None,
+            return_type: Type::Void,
+            scopes: vec![HashMap::new()],
         }
     }
 
-    pub fn generate(&mut self, ast: &[Statement], optimize: bool) -> Result<(), String> {
-        // Create main function
+    /// Generate the program. It must have passed the type checker, whose
+    /// types decide how values are represented: `int`, `float` and `bool`
+    /// become i64, f64 and i1, and only values of type `any` are tagged.
+    pub fn generate(&mut self, ast: &[Located], optimize: bool) -> Result<(), String> {
+        // Declare every function first so calls can come before definitions
+        for stmt in ast {
+            if let Statement::FunctionDef { is_main, name, param_types, return_type, .. } = &stmt.statement {
+                let name = if *is_main { "main" } else { name };
+                self.declare_function(name, param_types, return_type.as_ref())?;
+            }
+        }
+        for stmt in ast {
+            if let Statement::FunctionDef { is_main, name, params, body, .. } = &stmt.statement {
+                let name = if *is_main { "main" } else { name };
+                self.generate_function(name, params, body)?;
+            }
+        }
+
+        // The top-level statements run first, then `main` or `app`
         let i32_type = self.context.i32_type();
         let main_type = i32_type.fn_type(&[], false);
         let main_fn = self.module.add_function("main", main_type, None);
         let entry = self.context.append_basic_block(main_fn, "entry");
-        
+
         self.builder.position_at_end(entry);
         self.main_fn = Some(main_fn);
+        self.function = Some(main_fn);
+        self.scopes = vec![HashMap::new()];
 
-        // Generate code for each statement
         for stmt in ast {
-            self.generate_statement(stmt)?;
+            if !matches!(stmt.statement, Statement::FunctionDef { .. }) {
+                self.generate_statement(stmt)?;
+            }
         }
 
-        // Add return 0
-        let _ = self.builder.build_return(Some(&i32_type.const_int(0, false)));
+        if !self.is_terminated() {
+            let entry_point = ["main", "app"].iter().find_map(|name| self.functions.get(*name)).cloned();
+            let code = match entry_point {
+                Some(function) if !function.params.is_empty() => {
+                    return Err("Program arguments are not supported in native code yet".to_string());
+                }
+                Some(function) => {
+                    let result = self.builder.build_call(function.value, &[], "entry").map_err(build_error)?;
+                    match result.try_as_basic_value().left() {
+                        Some(value) => {
+                            let value = typed(&function.return_type, value);
+                            self.exit_code(value)?
+                        }
+                        None => i32_type.const_zero(),
+                    }
+                }
+                None => i32_type.const_zero(),
+            };
+            self.builder.build_return(Some(&code)).map_err(build_error)?;
+        }
 
         // Verify the module
         if self.module.verify().is_err() {
@@ -63,7 +207,9 @@ impl<'ctx> CodeGenerator<'ctx> {
 
         // Optimize if requested
         if optimize {
-            let pass_manager = PassManager::create(&self.module);
+            let pass_manager = &self.pass_manager;
+            // Turns the stack slots of locals into registers first
+            pass_manager.add_promote_memory_to_register_pass();
             pass_manager.add_instruction_combining_pass();
             pass_manager.add_reassociate_pass();
             pass_manager.add_gvn_pass();
@@ -78,51 +224,971 @@ impl<'ctx> CodeGenerator<'ctx> {
         Ok(())
     }
 
-    fn generate_statement(&self, stmt: &Statement) -> Result<(), String> {
+    fn declare_function(
+        &mut self,
+        name: &str,
+        param_types: &[Option<Type>],
+        return_type: Option<&Type>,
+    ) -> Result<(), String> {
+        let Type::Function { params, return_type } = types::signature(param_types, return_type) else {
+            unreachable!("signatures are function types");
+        };
+
+        let in_function = |e: String| format!("{} in function '{}'", e, name);
+        let mut llvm_params: Vec<BasicMetadataTypeEnum> = Vec::new();
+        for param in &params {
+            llvm_params.push(self.llvm_type(param).map_err(in_function)?.into());
+        }
+        let fn_type = match *return_type {
+            Type::Void => self.context.void_type().fn_type(&llvm_params, false),
+            ref ty => self.llvm_type(ty).map_err(in_function)?.fn_type(&llvm_params, false),
+        };
+
+        // Prefixed so that functions such as `exit` don't clash with the C library
+        let value = self.module.add_function(&format!("kode.{}", name), fn_type, None);
+        self.functions.insert(name.to_string(), NativeFunction { value, params, return_type: *return_type });
+        Ok(())
+    }
+
+    fn generate_function(&mut self, name: &str, params: &[String], body: &[Located]) -> Result<(), String> {
+        let function = self.functions[name].clone();
+        let entry = self.context.append_basic_block(function.value, "entry");
+        self.builder.position_at_end(entry);
+        self.function = Some(function.value);
+        self.return_type = function.return_type.clone();
+        self.scopes = vec![HashMap::new()];
+
+        let in_function = |e: String| format!("{} in function '{}'", e, name);
+        for (i, (param, ty)) in params.iter().zip(&function.params).enumerate() {
+            let ptr = self.entry_alloca(ty, param)?;
+            if let Some(value) = function.value.get_nth_param(i as u32) {
+                self.builder.build_store(ptr, value).map_err(build_error)?;
+            }
+            self.declare(param, Local { ptr, ty: ty.clone(), annotated: true });
+        }
+        self.statements(body).map_err(in_function)?;
+
+        if !self.is_terminated() {
+            match &function.return_type {
+                Type::Void => {
+                    self.builder.build_return(None).map_err(build_error)?;
+                }
+                Type::Any => {
+                    let void = self.box_value(Typed::Void)?;
+                    self.builder.build_return(Some(&void)).map_err(build_error)?;
+                }
+                // The type checker makes sure the other functions return on every path
+                _ => {
+                    self.builder.build_unreachable().map_err(build_error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn statements(&mut self, statements: &[Located]) -> Result<(), String> {
+        for stmt in statements {
+            self.generate_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    // Generate the statements in a new scope
+    fn block(&mut self, statements: &[Located]) -> Result<(), String> {
+        self.scopes.push(HashMap::new());
+        let result = self.statements(statements);
+        self.scopes.pop();
+        result
+    }
+
+    fn generate_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        // Code after a `return` never runs, but still needs a block to go in
+        if self.is_terminated() {
+            let block = self.append_block("dead");
+            self.builder.position_at_end(block);
+        }
+
         match stmt {
-            Statement::Print(expr) => self.generate_print(expr),
-            Statement::FunctionDef { name, params: _, body, .. } => {
-                if name == "app" || name == "main" {
-                    // Generate code for function body
-                    for stmt in body {
-                        self.generate_statement(stmt)?;
+            Statement::Let { name, ty, value, .. } => {
+                let value = self.expression(value)?;
+                let (ty, annotated) = match ty {
+                    Some(ty) => (ty.clone(), true),
+                    None => (value.ty(), false),
+                };
+                let value = self.convert(value, &ty)?;
+                let ptr = self.entry_alloca(&ty, name)?;
+                self.store(ptr, value)?;
+                self.declare(name, Local { ptr, ty, annotated });
+                Ok(())
+            }
+            Statement::Assign { name, value } => {
+                let value = self.expression(value)?;
+                self.assign(name, value)?;
+                Ok(())
+            }
+            Statement::Print(expr) => {
+                let value = self.expression(expr)?;
+                self.generate_print(value)
+            }
+            Statement::Return(expr) => {
+                let value = self.expression(expr)?;
+                if self.function == self.main_fn {
+                    // A top-level `return` ends the program
+                    let code = self.exit_code(value)?;
+                    self.builder.build_return(Some(&code)).map_err(build_error)?;
+                    return Ok(());
+                }
+                match self.return_type.clone() {
+                    // `return;` is parsed as `return false;`
+                    Type::Void => self.builder.build_return(None).map_err(build_error)?,
+                    ty => {
+                        let value = self.convert(value, &ty)?.basic();
+                        let value = value.ok_or_else(|| format!("Expected a return value of type {}", ty))?;
+                        self.builder.build_return(Some(&value)).map_err(build_error)?
                     }
+                };
+                Ok(())
+            }
+            Statement::If { condition, then_branch, else_branch } => {
+                let condition = self.condition(condition)?;
+                let then_block = self.append_block("then");
+                let else_block = self.append_block("else");
+                let end_block = self.append_block("endif");
+                self.builder.build_conditional_branch(condition, then_block, else_block).map_err(build_error)?;
+
+                self.builder.position_at_end(then_block);
+                self.block(then_branch)?;
+                self.branch(end_block)?;
+
+                self.builder.position_at_end(else_block);
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch)?;
                 }
+                self.branch(end_block)?;
+
+                self.builder.position_at_end(end_block);
                 Ok(())
             }
-            _ => Ok(()) // Ignore other statements for now
+            Statement::While { condition, body } => {
+                let condition_block = self.append_block("while");
+                let body_block = self.append_block("body");
+                let end_block = self.append_block("endwhile");
+                self.branch(condition_block)?;
+
+                self.builder.position_at_end(condition_block);
+                let condition = self.condition(condition)?;
+                self.builder.build_conditional_branch(condition, body_block, end_block).map_err(build_error)?;
+
+                self.builder.position_at_end(body_block);
+                self.block(body)?;
+                self.branch(condition_block)?;
+
+                self.builder.position_at_end(end_block);
+                Ok(())
+            }
+            Statement::For { init, condition, update, body } => {
+                // Variables declared by `init` are local to the loop
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.generate_statement(init)?;
+                }
+
+                let condition_block = self.append_block("for");
+                let body_block = self.append_block("body");
+                let update_block = self.append_block("update");
+                let end_block = self.append_block("endfor");
+                self.branch(condition_block)?;
+
+                self.builder.position_at_end(condition_block);
+                match condition {
+                    Some(condition) => {
+                        let condition = self.condition(condition)?;
+                        self.builder.build_conditional_branch(condition, body_block, end_block).map_err(build_error)?;
+                    }
+                    None => self.branch(body_block)?,
+                }
+
+                self.builder.position_at_end(body_block);
+                self.block(body)?;
+                self.branch(update_block)?;
+
+                self.builder.position_at_end(update_block);
+                if let Some(update) = update {
+                    self.generate_statement(update)?;
+                }
+                self.branch(condition_block)?;
+
+                self.builder.position_at_end(end_block);
+                self.scopes.pop();
+                Ok(())
+            }
+            Statement::ForIn { names, iterable: Expression::Range { start, end }, body } if names.len() == 1 => {
+                self.range_loop(&names[0], start, end, body)
+            }
+            Statement::ForIn { .. } => {
+                Err("Only 'for x in start..end' loops are supported in native code yet".to_string())
+            }
+            Statement::ExprStmt(expr) | Statement::Expression(expr) => {
+                self.expression(expr)?;
+                Ok(())
+            }
+            Statement::Block(statements) => self.block(statements),
+            Statement::FunctionDef { .. } => Err(unsupported("Nested functions")),
+            Statement::Struct { .. } | Statement::Impl { .. } => Err(unsupported("Structs")),
+            Statement::Enum { .. } => Err(unsupported("Enums")),
+            Statement::Match { .. } => Err(unsupported("'match' statements")),
+            Statement::Import { .. } | Statement::ImportFrom { .. } => Err(unsupported("Imports")),
+            Statement::Try { .. } => Err(unsupported("'try' statements")),
+        }
+    }
+
+    // `for name in start..end` counts in a register of its own, so assigning
+    // to `name` in the body doesn't change the number of iterations
+    fn range_loop(
+        &mut self,
+        name: &str,
+        start: &Expression,
+        end: &Expression,
+        body: &[Located],
+    ) -> Result<(), String> {
+        let start = self.expression(start)?;
+        let start = self.int_operand(start, "Range bounds")?;
+        let end = self.expression(end)?;
+        let end = self.int_operand(end, "Range bounds")?;
+
+        let index = self.entry_alloca(&Type::Int, "index")?;
+        self.builder.build_store(index, start).map_err(build_error)?;
+
+        let condition_block = self.append_block("for");
+        let body_block = self.append_block("body");
+        let end_block = self.append_block("endfor");
+        self.branch(condition_block)?;
+
+        self.builder.position_at_end(condition_block);
+        let i64_type = self.context.i64_type();
+        let current = self.builder.build_load(i64_type, index, "i").map_err(build_error)?.into_int_value();
+        let in_range = self.builder.build_int_compare(IntPredicate::SLT, current, end, "in_range").map_err(build_error)?;
+        self.builder.build_conditional_branch(in_range, body_block, end_block).map_err(build_error)?;
+
+        self.builder.position_at_end(body_block);
+        self.scopes.push(HashMap::new());
+        let ptr = self.entry_alloca(&Type::Int, name)?;
+        self.builder.build_store(ptr, current).map_err(build_error)?;
+        self.declare(name, Local { ptr, ty: Type::Int, annotated: false });
+        let result = self.statements(body);
+        self.scopes.pop();
+        result?;
+
+        // `current < end`, so this can't overflow
+        if !self.is_terminated() {
+            let one = i64_type.const_int(1, false);
+            let next = self.builder.build_int_add(current, one, "next").map_err(build_error)?;
+            self.builder.build_store(index, next).map_err(build_error)?;
         }
+        self.branch(condition_block)?;
+
+        self.builder.position_at_end(end_block);
+        Ok(())
     }
 
-    fn generate_print(&self, expr: &Expression) -> Result<(), String> {
+    fn generate_print(&mut self, value: Typed<'ctx>) -> Result<(), String> {
         let printf = self.module.get_function("printf").unwrap();
 
+        let mut format = String::new();
+        let mut args = Vec::new();
+        self.format_value(value, &mut format, &mut args)?;
+        format.push('\n');
+
+        let format_str = self.global_string(&format)?;
+        let mut call_args: Vec<BasicMetadataValueEnum> = vec![format_str.into()];
+        call_args.extend(args);
+        self.builder.build_call(printf, &call_args, "printf_call").map_err(build_error)?;
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Result<Typed<'ctx>, String> {
         match expr {
-            Expression::String(s) => {
-                let format_str = self.builder.build_global_string_ptr(&format!("{}\n", s), "str")
-                    .map_err(|e| format!("Failed to build string: {:?}", e))?;
-                let _ = self.builder.build_call(
-                    printf,
-                    &[format_str.as_pointer_value().into()],
-                    "printf_call"
-                );
-                Ok(())
+            Expression::Number(n) => Ok(Typed::Int(self.context.i64_type().const_int(*n as u64, true))),
+            Expression::BigInt(n) => Err(format!("Integer {} doesn't fit in 64 bits; native code has no bigints", n)),
+            Expression::Float(f) => Ok(Typed::Float(self.context.f64_type().const_float(*f))),
+            Expression::Bool(b) => Ok(Typed::Bool(self.context.bool_type().const_int(*b as u64, false))),
+            Expression::String(s) => Ok(Typed::Str(self.global_string(s)?)),
+            Expression::Identifier(name, _) => {
+                let local = self.lookup(name)?;
+                self.load(&local, name)
             }
-            Expression::Number(n) => {
-                let format_str = self.builder.build_global_string_ptr("%d\n", "int_fmt")
-                    .map_err(|e| format!("Failed to build format string: {:?}", e))?;
-                let value = self.context.i32_type().const_int(*n as u64, false);
-                let _ = self.builder.build_call(
-                    printf,
-                    &[format_str.as_pointer_value().into(), value.into()],
-                    "printf_call"
-                );
-                Ok(())
+            Expression::Binary { left, op, right } => {
+                let left = self.expression(left)?;
+                let right = self.expression(right)?;
+                self.binary(left, op, right)
+            }
+            Expression::Unary { op, expr } => {
+                let value = self.expression(expr)?;
+                self.unary(op, value)
+            }
+            Expression::CompoundAssign { target, op, value } => match target.as_ref() {
+                Expression::Identifier(name, _) => {
+                    let local = self.lookup(name)?;
+                    let current = self.load(&local, name)?;
+                    let value = self.expression(value)?;
+                    let result = self.binary(current, op, value)?;
+                    self.assign(name, result)
+                }
+                _ => Err(unsupported("Array elements and fields")),
+            },
+            Expression::Conditional { condition, then_branch, else_branch } => {
+                self.conditional(condition, then_branch, else_branch)
+            }
+            Expression::Call { callee, arguments } => self.call(callee, arguments),
+            Expression::Interpolation { parts } => {
+                let mut format = String::new();
+                let mut args = Vec::new();
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(text) => format.push_str(&text.replace('%', "%%")),
+                        InterpolationPart::Expression { expr, .. } => {
+                            let value = self.expression(expr)?;
+                            self.format_value(value, &mut format, &mut args)?;
+                        }
+                    }
+                }
+                Ok(Typed::Str(self.format_string(&format, &args)?))
+            }
+            Expression::Range { .. } => {
+                Err("Ranges can only be iterated over by 'for' loops in native code".to_string())
+            }
+            Expression::Array { .. } | Expression::ArrayAccess { .. } => Err(unsupported("Arrays")),
+            Expression::Map { .. } => Err(unsupported("Maps")),
+            Expression::StructLiteral { .. } | Expression::FieldAccess { .. } => Err(unsupported("Structs")),
+            Expression::Match { .. } => Err(unsupported("'match' expressions")),
+            Expression::Closure { .. } => Err(unsupported("Closures")),
+        }
+    }
+
+    fn binary(&mut self, left: Typed<'ctx>, op: &BinaryOp, right: Typed<'ctx>) -> Result<Typed<'ctx>, String> {
+        // An operand of type `any` must have the type of the other one, which
+        // is checked at run time
+        let (left, right) = match (left, right) {
+            (Typed::Any(_), Typed::Any(_)) => {
+                return Err(format!(
+                    "Operator '{}' needs an operand of known type in native code; add type annotations",
+                    op
+                ));
+            }
+            (Typed::Any(left), right) => (self.unbox(left, &right.ty())?, right),
+            (left, Typed::Any(right)) => {
+                let ty = left.ty();
+                (left, self.unbox(right, &ty)?)
+            }
+            operands => operands,
+        };
+        if types::binary_type(&left.ty(), op, &right.ty()).is_none() {
+            return Err(format!("Operator '{}' cannot be applied to {} and {}", op, left.ty(), right.ty()));
+        }
+
+        match (left, right) {
+            (Typed::Int(l), Typed::Int(r)) => self.int_binary(l, op, r),
+            (Typed::Bool(l), Typed::Bool(r)) => self.bool_binary(l, op, r),
+            (Typed::Str(_), _) | (_, Typed::Str(_)) => self.string_binary(left, op, right),
+            // Arithmetic on an int and a float is done in floating point
+            (left, right) => {
+                let l = self.float_operand(left)?;
+                let r = self.float_operand(right)?;
+                self.float_binary(l, op, r)
+            }
+        }
+    }
+
+    fn int_binary(&mut self, l: IntValue<'ctx>, op: &BinaryOp, r: IntValue<'ctx>) -> Result<Typed<'ctx>, String> {
+        let i64_type = self.context.i64_type();
+        if let Some(predicate) = int_predicate(op) {
+            let result = self.builder.build_int_compare(predicate, l, r, "cmp").map_err(build_error)?;
+            return Ok(Typed::Bool(result));
+        }
+
+        let result = match op {
+            BinaryOp::Add => self.checked_arithmetic("llvm.sadd.with.overflow", l, r)?,
+            BinaryOp::Subtract => self.checked_arithmetic("llvm.ssub.with.overflow", l, r)?,
+            BinaryOp::Multiply => self.checked_arithmetic("llvm.smul.with.overflow", l, r)?,
+            BinaryOp::Divide | BinaryOp::Modulo => {
+                let zero = self.builder.build_int_compare(IntPredicate::EQ, r, i64_type.const_zero(), "zero")
+                    .map_err(build_error)?;
+                let message = if *op == BinaryOp::Divide { "Division by zero" } else { "Modulo by zero" };
+                self.check(zero, message)?;
+
+                // `i64::MIN / -1` doesn't fit in 64 bits; `i64::MIN % -1` is 0
+                let min = i64_type.const_int(i64::MIN as u64, true);
+                let is_min = self.builder.build_int_compare(IntPredicate::EQ, l, min, "is_min").map_err(build_error)?;
+                let is_minus_one = self.builder.build_int_compare(IntPredicate::EQ, r, i64_type.const_all_ones(), "is_minus_one")
+                    .map_err(build_error)?;
+                let overflow = self.builder.build_and(is_min, is_minus_one, "overflow").map_err(build_error)?;
+                if *op == BinaryOp::Divide {
+                    self.check(overflow, OVERFLOW)?;
+                    self.builder.build_int_signed_div(l, r, "div").map_err(build_error)?
+                } else {
+                    let one = i64_type.const_int(1, false);
+                    let r = self.builder.build_select(overflow, one, r, "divisor").map_err(build_error)?.into_int_value();
+                    self.builder.build_int_signed_rem(l, r, "rem").map_err(build_error)?
+                }
+            }
+            BinaryOp::BitAnd => self.builder.build_and(l, r, "and").map_err(build_error)?,
+            BinaryOp::BitOr => self.builder.build_or(l, r, "or").map_err(build_error)?,
+            BinaryOp::BitXor => self.builder.build_xor(l, r, "xor").map_err(build_error)?,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                // Out of range amounts are errors, as in the interpreter, rather
+                // than undefined behaviour
+                let limit = i64_type.const_int(63, false);
+                let out_of_range = self.builder.build_int_compare(IntPredicate::UGT, r, limit, "out_of_range")
+                    .map_err(build_error)?;
+                self.check(out_of_range, "Shift amount is out of range 0..63")?;
+
+                if *op == BinaryOp::ShiftRight {
+                    self.builder.build_right_shift(l, r, true, "shr").map_err(build_error)?
+                } else {
+                    let shifted = self.builder.build_left_shift(l, r, "shl").map_err(build_error)?;
+                    let back = self.builder.build_right_shift(shifted, r, true, "back").map_err(build_error)?;
+                    let overflow = self.builder.build_int_compare(IntPredicate::NE, back, l, "overflow")
+                        .map_err(build_error)?;
+                    self.check(overflow, OVERFLOW)?;
+                    shifted
+                }
+            }
+            _ => return Err(format!("Operator '{}' cannot be applied to int and int", op)),
+        };
+        Ok(Typed::Int(result))
+    }
+
+    fn float_binary(&mut self, l: FloatValue<'ctx>, op: &BinaryOp, r: FloatValue<'ctx>) -> Result<Typed<'ctx>, String> {
+        if let Some(predicate) = float_predicate(op) {
+            let result = self.builder.build_float_compare(predicate, l, r, "cmp").map_err(build_error)?;
+            return Ok(Typed::Bool(result));
+        }
+
+        let result = match op {
+            BinaryOp::Add => self.builder.build_float_add(l, r, "add").map_err(build_error)?,
+            BinaryOp::Subtract => self.builder.build_float_sub(l, r, "sub").map_err(build_error)?,
+            BinaryOp::Multiply => self.builder.build_float_mul(l, r, "mul").map_err(build_error)?,
+            BinaryOp::Divide | BinaryOp::Modulo => {
+                let zero = self.context.f64_type().const_zero();
+                let is_zero = self.builder.build_float_compare(FloatPredicate::OEQ, r, zero, "zero")
+                    .map_err(build_error)?;
+                if *op == BinaryOp::Divide {
+                    self.check(is_zero, "Division by zero")?;
+                    self.builder.build_float_div(l, r, "div").map_err(build_error)?
+                } else {
+                    self.check(is_zero, "Modulo by zero")?;
+                    self.builder.build_float_rem(l, r, "rem").map_err(build_error)?
+                }
+            }
+            _ => return Err(format!("Operator '{}' cannot be applied to float and float", op)),
+        };
+        Ok(Typed::Float(result))
+    }
+
+    // Both sides of `&&` and `||` are evaluated, as in the interpreter
+    fn bool_binary(&mut self, l: IntValue<'ctx>, op: &BinaryOp, r: IntValue<'ctx>) -> Result<Typed<'ctx>, String> {
+        let result = match op {
+            BinaryOp::And | BinaryOp::BitAnd => self.builder.build_and(l, r, "and"),
+            BinaryOp::Or | BinaryOp::BitOr => self.builder.build_or(l, r, "or"),
+            BinaryOp::BitXor => self.builder.build_xor(l, r, "xor"),
+            BinaryOp::Equal => self.builder.build_int_compare(IntPredicate::EQ, l, r, "eq"),
+            BinaryOp::NotEqual => self.builder.build_int_compare(IntPredicate::NE, l, r, "ne"),
+            _ => return Err(format!("Operator '{}' cannot be applied to bool and bool", op)),
+        };
+        Ok(Typed::Bool(result.map_err(build_error)?))
+    }
+
+    fn string_binary(&mut self, left: Typed<'ctx>, op: &BinaryOp, right: Typed<'ctx>) -> Result<Typed<'ctx>, String> {
+        match (left, op, right) {
+            (left, BinaryOp::Add, right) => {
+                let mut format = String::new();
+                let mut args = Vec::new();
+                self.format_value(left, &mut format, &mut args)?;
+                self.format_value(right, &mut format, &mut args)?;
+                Ok(Typed::Str(self.format_string(&format, &args)?))
+            }
+            (Typed::Str(l), BinaryOp::Equal | BinaryOp::NotEqual, Typed::Str(r)) => {
+                let strcmp = self.module.get_function("strcmp").unwrap();
+                let order = self.builder.build_call(strcmp, &[l.into(), r.into()], "strcmp").map_err(build_error)?;
+                let order = order.try_as_basic_value().left().unwrap().into_int_value();
+                let predicate = if *op == BinaryOp::Equal { IntPredicate::EQ } else { IntPredicate::NE };
+                let zero = self.context.i32_type().const_zero();
+                let result = self.builder.build_int_compare(predicate, order, zero, "cmp").map_err(build_error)?;
+                Ok(Typed::Bool(result))
+            }
+            (left, op, right) => {
+                Err(format!("Operator '{}' cannot be applied to {} and {}", op, left.ty(), right.ty()))
             }
-            _ => Err("Unsupported print expression type".to_string())
         }
     }
 
+    // `l op r` using one of LLVM's overflow-checking intrinsics
+    fn checked_arithmetic(&mut self, intrinsic: &str, l: IntValue<'ctx>, r: IntValue<'ctx>) -> Result<IntValue<'ctx>, String> {
+        let function = Intrinsic::find(intrinsic)
+            .and_then(|found| found.get_declaration(&self.module, &[self.context.i64_type().into()]))
+            .ok_or_else(|| format!("LLVM intrinsic {} is not available", intrinsic))?;
+        let result = self.builder.build_call(function, &[l.into(), r.into()], "checked").map_err(build_error)?;
+        let result = result.try_as_basic_value().left().unwrap().into_struct_value();
+
+        let value = self.builder.build_extract_value(result, 0, "value").map_err(build_error)?;
+        let overflow = self.builder.build_extract_value(result, 1, "overflow").map_err(build_error)?;
+        self.check(overflow.into_int_value(), OVERFLOW)?;
+        Ok(value.into_int_value())
+    }
+
+    fn unary(&mut self, op: &UnaryOp, value: Typed<'ctx>) -> Result<Typed<'ctx>, String> {
+        match (op, value) {
+            (UnaryOp::Negate, Typed::Int(value)) => {
+                let zero = self.context.i64_type().const_zero();
+                Ok(Typed::Int(self.checked_arithmetic("llvm.ssub.with.overflow", zero, value)?))
+            }
+            (UnaryOp::Negate, Typed::Float(value)) => {
+                Ok(Typed::Float(self.builder.build_float_neg(value, "neg").map_err(build_error)?))
+            }
+            (UnaryOp::Not, Typed::Bool(value)) | (UnaryOp::BitNot, Typed::Int(value)) => {
+                let result = self.builder.build_not(value, "not").map_err(build_error)?;
+                Ok(if *op == UnaryOp::Not { Typed::Bool(result) } else { Typed::Int(result) })
+            }
+            (UnaryOp::Not, Typed::Any(value)) => {
+                let value = self.unbox(value, &Type::Bool)?;
+                self.unary(op, value)
+            }
+            (UnaryOp::BitNot, Typed::Any(value)) => {
+                let value = self.unbox(value, &Type::Int)?;
+                self.unary(op, value)
+            }
+            (op, value) => Err(format!("Operator '{}' cannot be applied to {} in native code", op, value.ty())),
+        }
+    }
+
+    fn conditional(
+        &mut self,
+        condition: &Expression,
+        then_branch: &Expression,
+        else_branch: &Expression,
+    ) -> Result<Typed<'ctx>, String> {
+        let condition = self.condition(condition)?;
+        let then_block = self.append_block("cond_then");
+        let else_block = self.append_block("cond_else");
+        let end_block = self.append_block("cond_end");
+        self.builder.build_conditional_branch(condition, then_block, else_block).map_err(build_error)?;
+
+        self.builder.position_at_end(then_block);
+        let then_value = self.expression(then_branch)?;
+        let then_end = self.builder.get_insert_block().unwrap();
+
+        self.builder.position_at_end(else_block);
+        let else_value = self.expression(else_branch)?;
+
+        // Branches of different types give a tagged value
+        let ty = if then_value.ty() == else_value.ty() { then_value.ty() } else { Type::Any };
+        let else_value = self.convert(else_value, &ty)?;
+        let else_end = self.builder.get_insert_block().unwrap();
+        self.branch(end_block)?;
+
+        self.builder.position_at_end(then_end);
+        let then_value = self.convert(then_value, &ty)?;
+        let then_end = self.builder.get_insert_block().unwrap();
+        self.branch(end_block)?;
+
+        self.builder.position_at_end(end_block);
+        match (then_value.basic(), else_value.basic()) {
+            (Some(then_value), Some(else_value)) => {
+                let phi = self.builder.build_phi(self.llvm_type(&ty)?, "cond").map_err(build_error)?;
+                phi.add_incoming(&[(&then_value, then_end), (&else_value, else_end)]);
+                Ok(typed(&ty, phi.as_basic_value()))
+            }
+            _ => Ok(Typed::Void),
+        }
+    }
+
+    // Only calls to the program's own top-level functions are compiled;
+    // arguments are converted to the parameter types
+    fn call(&mut self, callee: &Expression, arguments: &[Expression]) -> Result<Typed<'ctx>, String> {
+        let name = match callee {
+            Expression::Identifier(name, _) => name,
+            _ => return Err(unsupported("Methods and function values")),
+        };
+        let function = self.functions.get(name).cloned()
+            .ok_or_else(|| format!("Function '{}' is not supported in native code yet", name))?;
+        if arguments.len() != function.params.len() {
+            return Err(format!(
+                "Function '{}' expects {} args, got {}",
+                name, function.params.len(), arguments.len()
+            ));
+        }
+
+        let mut args: Vec<BasicMetadataValueEnum> = Vec::new();
+        for (argument, ty) in arguments.iter().zip(&function.params) {
+            let value = self.expression(argument)?;
+            let value = self.convert(value, ty)?;
+            args.extend(value.basic().map(BasicMetadataValueEnum::from));
+        }
+        let result = self.builder.build_call(function.value, &args, "call").map_err(build_error)?;
+
+        Ok(match result.try_as_basic_value().left() {
+            Some(value) => typed(&function.return_type, value),
+            None => Typed::Void,
+        })
+    }
+
+    fn condition(&mut self, condition: &Expression) -> Result<IntValue<'ctx>, String> {
+        let value = self.expression(condition)?;
+        let ty = value.ty();
+        match self.convert(value, &Type::Bool) {
+            Ok(Typed::Bool(value)) => Ok(value),
+            _ => Err(format!("Condition must be bool, found {}", ty)),
+        }
+    }
+
+    fn int_operand(&mut self, value: Typed<'ctx>, what: &str) -> Result<IntValue<'ctx>, String> {
+        let ty = value.ty();
+        match self.convert(value, &Type::Int) {
+            Ok(Typed::Int(value)) => Ok(value),
+            _ => Err(format!("{} must be int, found {}", what, ty)),
+        }
+    }
+
+    fn float_operand(&mut self, value: Typed<'ctx>) -> Result<FloatValue<'ctx>, String> {
+        match value {
+            Typed::Float(value) => Ok(value),
+            Typed::Int(value) => {
+                let f64_type = self.context.f64_type();
+                self.builder.build_signed_int_to_float(value, f64_type, "float").map_err(build_error)
+            }
+            other => Err(format!("Expected a number, found {}", other.ty())),
+        }
+    }
+
+    // `value` as a value of type `ty`, boxing it into or unboxing it from `any`
+    fn convert(&mut self, value: Typed<'ctx>, ty: &Type) -> Result<Typed<'ctx>, String> {
+        match (value, ty) {
+            (value, Type::Any) => Ok(Typed::Any(self.box_value(value)?)),
+            (Typed::Any(value), ty) => self.unbox(value, ty),
+            (value, ty) if value.ty() == *ty => Ok(value),
+            (value, ty) => Err(format!("Expected {}, found {}", ty, value.ty())),
+        }
+    }
+
+    fn box_value(&mut self, value: Typed<'ctx>) -> Result<StructValue<'ctx>, String> {
+        let i64_type = self.context.i64_type();
+        let (tag, payload) = match value {
+            Typed::Any(value) => return Ok(value),
+            Typed::Void => (TAG_VOID, i64_type.const_zero()),
+            Typed::Int(value) => (TAG_INT, value),
+            Typed::Float(value) => {
+                let bits = self.builder.build_bitcast(value, i64_type, "bits").map_err(build_error)?;
+                (TAG_FLOAT, bits.into_int_value())
+            }
+            Typed::Bool(value) => {
+                (TAG_BOOL, self.builder.build_int_z_extend(value, i64_type, "bool").map_err(build_error)?)
+            }
+            Typed::Str(value) => {
+                (TAG_STRING, self.builder.build_ptr_to_int(value, i64_type, "address").map_err(build_error)?)
+            }
+        };
+
+        let tag = self.context.i8_type().const_int(tag, false);
+        let tagged = self.builder.build_insert_value(self.any_type().get_undef(), tag, 0, "tagged")
+            .map_err(build_error)?;
+        let tagged = self.builder.build_insert_value(tagged, payload, 1, "tagged").map_err(build_error)?;
+        Ok(tagged.into_struct_value())
+    }
+
+    // The value inside a tagged `any`, stopping the program if it isn't of type `ty`
+    fn unbox(&mut self, value: StructValue<'ctx>, ty: &Type) -> Result<Typed<'ctx>, String> {
+        let expected = match ty {
+            Type::Any => return Ok(Typed::Any(value)),
+            Type::Int => TAG_INT,
+            Type::Float => TAG_FLOAT,
+            Type::Bool => TAG_BOOL,
+            Type::String => TAG_STRING,
+            other => return Err(format!("Values of type {} are not supported in native code yet", other)),
+        };
+        let tag = self.builder.build_extract_value(value, 0, "tag").map_err(build_error)?.into_int_value();
+        let expected = self.context.i8_type().const_int(expected, false);
+        let mismatch = self.builder.build_int_compare(IntPredicate::NE, tag, expected, "mismatch")
+            .map_err(build_error)?;
+        self.check(mismatch, &format!("Expected a value of type {}", ty))?;
+        self.payload(value, ty)
+    }
+
+    // The payload of a tagged value known to hold a value of type `ty`
+    fn payload(&mut self, value: StructValue<'ctx>, ty: &Type) -> Result<Typed<'ctx>, String> {
+        let payload = self.builder.build_extract_value(value, 1, "payload").map_err(build_error)?.into_int_value();
+        Ok(match ty {
+            Type::Int => Typed::Int(payload),
+            Type::Float => {
+                let f64_type = self.context.f64_type();
+                Typed::Float(self.builder.build_bitcast(payload, f64_type, "float").map_err(build_error)?.into_float_value())
+            }
+            Type::Bool => {
+                let bool_type = self.context.bool_type();
+                Typed::Bool(self.builder.build_int_truncate(payload, bool_type, "bool").map_err(build_error)?)
+            }
+            Type::String => {
+                let string_type = self.string_type();
+                Typed::Str(self.builder.build_int_to_ptr(payload, string_type, "string").map_err(build_error)?)
+            }
+            _ => Typed::Void,
+        })
+    }
+
+    // Append the `printf` conversion for `value` to `format`, and its arguments
+    // to `args`. Values are printed the way the interpreter prints them, except
+    // that floats are printed with `%g`.
+    fn format_value(
+        &mut self,
+        value: Typed<'ctx>,
+        format: &mut String,
+        args: &mut Vec<BasicMetadataValueEnum<'ctx>>,
+    ) -> Result<(), String> {
+        match value {
+            Typed::Int(value) => {
+                format.push_str("%lld");
+                args.push(value.into());
+            }
+            Typed::Float(value) => {
+                format.push_str("%g");
+                args.push(value.into());
+            }
+            Typed::Bool(value) => {
+                let true_str = self.global_string("true")?;
+                let false_str = self.global_string("false")?;
+                let text = self.builder.build_select(value, true_str, false_str, "bool").map_err(build_error)?;
+                format.push_str("%s");
+                args.push(text.into());
+            }
+            Typed::Str(value) => {
+                format.push_str("%s");
+                args.push(value.into());
+            }
+            Typed::Any(value) => {
+                let function = match self.module.get_function("kode.any_to_string") {
+                    Some(function) => function,
+                    None => self.any_to_string()?,
+                };
+                let text = self.builder.build_call(function, &[value.into()], "text").map_err(build_error)?;
+                format.push_str("%s");
+                args.push(text.try_as_basic_value().left().unwrap().into());
+            }
+            Typed::Void => format.push_str("void"),
+        }
+        Ok(())
+    }
+
+    // Generate `kode.any_to_string`, which formats a tagged value according
+    // to its tag
+    fn any_to_string(&mut self) -> Result<FunctionValue<'ctx>, String> {
+        let fn_type = self.string_type().fn_type(&[self.any_type().into()], false);
+        let function = self.module.add_function("kode.any_to_string", fn_type, None);
+        let caller_block = self.builder.get_insert_block();
+        let caller = self.function.replace(function);
+
+        let entry = self.append_block("entry");
+        self.builder.position_at_end(entry);
+        let value = function.get_first_param().unwrap().into_struct_value();
+        let tag = self.builder.build_extract_value(value, 0, "tag").map_err(build_error)?.into_int_value();
+
+        let mut cases = Vec::new();
+        for (tag, ty) in [(TAG_INT, Type::Int), (TAG_FLOAT, Type::Float), (TAG_BOOL, Type::Bool), (TAG_STRING, Type::String)] {
+            cases.push((self.context.i8_type().const_int(tag, false), self.append_block("case"), ty));
+        }
+        let void_block = self.append_block("void");
+        let switch_cases: Vec<_> = cases.iter().map(|(tag, block, _)| (*tag, *block)).collect();
+        self.builder.build_switch(tag, void_block, &switch_cases).map_err(build_error)?;
+
+        self.builder.position_at_end(void_block);
+        let void = self.global_string("void")?;
+        self.builder.build_return(Some(&void)).map_err(build_error)?;
+
+        for (_, block, ty) in cases {
+            self.builder.position_at_end(block);
+            let value = self.payload(value, &ty)?;
+            let mut format = String::new();
+            let mut args = Vec::new();
+            self.format_value(value, &mut format, &mut args)?;
+            let text = self.format_string(&format, &args)?;
+            self.builder.build_return(Some(&text)).map_err(build_error)?;
+        }
+
+        self.function = caller;
+        if let Some(block) = caller_block {
+            self.builder.position_at_end(block);
+        }
+        Ok(function)
+    }
+
+    // Format `args` into a newly allocated string. Strings built at run time
+    // are never freed.
+    fn format_string(&mut self, format: &str, args: &[BasicMetadataValueEnum<'ctx>]) -> Result<PointerValue<'ctx>, String> {
+        let snprintf = self.module.get_function("snprintf").unwrap();
+        let malloc = self.module.get_function("malloc").unwrap();
+        let i64_type = self.context.i64_type();
+
+        // The first call only measures the result
+        let format_str = self.global_string(format)?;
+        let mut call_args: Vec<BasicMetadataValueEnum> =
+            vec![self.string_type().const_null().into(), i64_type.const_zero().into(), format_str.into()];
+        call_args.extend_from_slice(args);
+        let length = self.builder.build_call(snprintf, &call_args, "length").map_err(build_error)?;
+        let length = length.try_as_basic_value().left().unwrap().into_int_value();
+
+        let length = self.builder.build_int_s_extend(length, i64_type, "length").map_err(build_error)?;
+        let size = self.builder.build_int_add(length, i64_type.const_int(1, false), "size").map_err(build_error)?;
+        let buffer = self.builder.build_call(malloc, &[size.into()], "buffer").map_err(build_error)?;
+        let buffer = buffer.try_as_basic_value().left().unwrap().into_pointer_value();
+
+        call_args[0] = buffer.into();
+        call_args[1] = size.into();
+        self.builder.build_call(snprintf, &call_args, "format").map_err(build_error)?;
+        Ok(buffer)
+    }
+
+    // Stop the program with a runtime error if `failed` is true
+    fn check(&mut self, failed: IntValue<'ctx>, message: &str) -> Result<(), String> {
+        let error_block = self.append_block("error");
+        let ok_block = self.append_block("ok");
+        self.builder.build_conditional_branch(failed, error_block, ok_block).map_err(build_error)?;
+
+        self.builder.position_at_end(error_block);
+        let dprintf = self.module.get_function("dprintf").unwrap();
+        let exit = self.module.get_function("exit").unwrap();
+        let i32_type = self.context.i32_type();
+        let format_str = self.global_string("Runtime error: %s\n")?;
+        let message = self.global_string(message)?;
+        let stderr = i32_type.const_int(2, false);
+        self.builder.build_call(dprintf, &[stderr.into(), format_str.into(), message.into()], "error")
+            .map_err(build_error)?;
+        self.builder.build_call(exit, &[i32_type.const_int(1, false).into()], "exit").map_err(build_error)?;
+        self.builder.build_unreachable().map_err(build_error)?;
+
+        self.builder.position_at_end(ok_block);
+        Ok(())
+    }
+
+    // The process exit code for the value returned by the program, as
+    // `interpreter::exit_code` computes it
+    fn exit_code(&mut self, value: Typed<'ctx>) -> Result<IntValue<'ctx>, String> {
+        let i32_type = self.context.i32_type();
+        match value {
+            Typed::Int(value) => self.builder.build_int_truncate(value, i32_type, "code").map_err(build_error),
+            Typed::Any(value) => {
+                let tag = self.builder.build_extract_value(value, 0, "tag").map_err(build_error)?.into_int_value();
+                let int_tag = self.context.i8_type().const_int(TAG_INT, false);
+                let is_int = self.builder.build_int_compare(IntPredicate::EQ, tag, int_tag, "is_int")
+                    .map_err(build_error)?;
+                let Typed::Int(payload) = self.payload(value, &Type::Int)? else {
+                    unreachable!("int payloads are ints");
+                };
+                let code = self.builder.build_int_truncate(payload, i32_type, "code").map_err(build_error)?;
+                let code = self.builder.build_select(is_int, code, i32_type.const_zero(), "code").map_err(build_error)?;
+                Ok(code.into_int_value())
+            }
+            _ => Ok(i32_type.const_zero()),
+        }
+    }
+
+    fn assign(&mut self, name: &str, value: Typed<'ctx>) -> Result<Typed<'ctx>, String> {
+        let local = self.lookup(name)?;
+        // A stack slot holds values of one type, so a variable whose type was
+        // inferred can't change it
+        if !local.annotated && local.ty != Type::Any && value.ty() != local.ty {
+            return Err(format!(
+                "Variable '{}' changes type from {} to {}; annotate it as 'any' to compile it natively",
+                name, local.ty, value.ty()
+            ));
+        }
+        let value = self.convert(value, &local.ty)?;
+        self.store(local.ptr, value)?;
+        Ok(value)
+    }
+
+    fn declare(&mut self, name: &str, local: Local<'ctx>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), local);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Local<'ctx>, String> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned().ok_or_else(|| {
+            format!("Undefined variable '{}' (functions can't use top-level variables in native code)", name)
+        })
+    }
+
+    fn load(&mut self, local: &Local<'ctx>, name: &str) -> Result<Typed<'ctx>, String> {
+        let value = self.builder.build_load(self.llvm_type(&local.ty)?, local.ptr, name).map_err(build_error)?;
+        Ok(typed(&local.ty, value))
+    }
+
+    fn store(&mut self, ptr: PointerValue<'ctx>, value: Typed<'ctx>) -> Result<(), String> {
+        if let Some(value) = value.basic() {
+            self.builder.build_store(ptr, value).map_err(build_error)?;
+        }
+        Ok(())
+    }
+
+    // Stack slots go at the start of the entry block, where LLVM can turn
+    // them into registers and loops don't grow the stack
+    fn entry_alloca(&self, ty: &Type, name: &str) -> Result<PointerValue<'ctx>, String> {
+        let llvm_type = self.llvm_type(ty)?;
+        let entry = self.current_function().get_first_basic_block().unwrap();
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(instruction) => builder.position_before(&instruction),
+            None => builder.position_at_end(entry),
+        }
+        builder.build_alloca(llvm_type, name).map_err(build_error)
+    }
+
+    fn llvm_type(&self, ty: &Type) -> Result<BasicTypeEnum<'ctx>, String> {
+        match ty {
+            Type::Int => Ok(self.context.i64_type().into()),
+            Type::Float => Ok(self.context.f64_type().into()),
+            Type::Bool => Ok(self.context.bool_type().into()),
+            Type::String => Ok(self.string_type().into()),
+            Type::Any => Ok(self.any_type().into()),
+            other => Err(format!("Values of type {} are not supported in native code yet", other)),
+        }
+    }
+
+    fn string_type(&self) -> PointerType<'ctx> {
+        self.context.i8_type().ptr_type(AddressSpace::default())
+    }
+
+    // A tag and a 64-bit payload holding an int, the bits of a float, a bool
+    // or the address of a string
+    fn any_type(&self) -> StructType<'ctx> {
+        let fields = [self.context.i8_type().into(), self.context.i64_type().into()];
+        self.context.struct_type(&fields, false)
+    }
+
+    fn global_string(&self, s: &str) -> Result<PointerValue<'ctx>, String> {
+        let global = self.builder.build_global_string_ptr(s, "str")
+            .map_err(|e| format!("Failed to build string: {:?}", e))?;
+        Ok(global.as_pointer_value())
+    }
+
+    fn current_function(&self) -> FunctionValue<'ctx> {
+        self.function.expect("no function is being generated")
+    }
+
+    fn append_block(&self, name: &str) -> BasicBlock<'ctx> {
+        self.context.append_basic_block(self.current_function(), name)
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.builder.get_insert_block().and_then(|block| block.get_terminator()).is_some()
+    }
+
+    // Jump to `block` unless the current block already ends in a jump or return
+    fn branch(&self, block: BasicBlock<'ctx>) -> Result<(), String> {
+        if !self.is_terminated() {
+            self.builder.build_unconditional_branch(block).map_err(build_error)?;
+        }
+        Ok(())
+    }
+
     pub fn write_object_file(&self, path: &Path) -> Result<(), String> {
         Target::initialize_native(&InitializationConfig::default())
             .map_err(|e| format!("Failed to initialize target: {}", e))?;
@@ -140,12 +1206,56 @@ impl<'ctx> CodeGenerator<'ctx> {
                 } else {
                     OptimizationLevel::None
                 },
-                RelocMode::Static,  // Change to static relocation
+                RelocMode::PIC,  // C compilers link position-independent executables by default
                 CodeModel::Small,
             ).ok_or_else(|| "Failed to create target machine".to_string())?;
-        
+
         machine.write_to_file(&self.module, inkwell::targets::FileType::Object, path)
             .map_err(|e| format!("Failed to write object file: {}", e))
     }
 
-}
\ No newline at end of file
+}
+
+// Wrap an LLVM value of type `ty` computed by generated code
+fn typed<'ctx>(ty: &Type, value: BasicValueEnum<'ctx>) -> Typed<'ctx> {
+    match ty {
+        Type::Int => Typed::Int(value.into_int_value()),
+        Type::Float => Typed::Float(value.into_float_value()),
+        Type::Bool => Typed::Bool(value.into_int_value()),
+        Type::String => Typed::Str(value.into_pointer_value()),
+        _ => Typed::Any(value.into_struct_value()),
+    }
+}
+
+fn int_predicate(op: &BinaryOp) -> Option<IntPredicate> {
+    match op {
+        BinaryOp::Equal => Some(IntPredicate::EQ),
+        BinaryOp::NotEqual => Some(IntPredicate::NE),
+        BinaryOp::LessThan => Some(IntPredicate::SLT),
+        BinaryOp::GreaterThan => Some(IntPredicate::SGT),
+        BinaryOp::LessThanOrEqual => Some(IntPredicate::SLE),
+        BinaryOp::GreaterThanOrEqual => Some(IntPredicate::SGE),
+        _ => None,
+    }
+}
+
+// Comparisons with NaN are false, except `!=`
+fn float_predicate(op: &BinaryOp) -> Option<FloatPredicate> {
+    match op {
+        BinaryOp::Equal => Some(FloatPredicate::OEQ),
+        BinaryOp::NotEqual => Some(FloatPredicate::UNE),
+        BinaryOp::LessThan => Some(FloatPredicate::OLT),
+        BinaryOp::GreaterThan => Some(FloatPredicate::OGT),
+        BinaryOp::LessThanOrEqual => Some(FloatPredicate::OLE),
+        BinaryOp::GreaterThanOrEqual => Some(FloatPredicate::OGE),
+        _ => None,
+    }
+}
+
+fn unsupported(what: &str) -> String {
+    format!("{} are not supported in native code yet", what)
+}
+
+fn build_error(error: BuilderError) -> String {
+    format!("Failed to build instruction: {:?}", error)
+}
@@ -0,0 +1,591 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use serde_json::{Value as Json, json};
+
+use crate::ast::Located;
+use crate::interpreter::{self, Debugger, Interpreter, Value};
+use crate::io::Io;
+use crate::rpc::{read_message, write_message};
+
+// The interpreter runs the program on a single thread
+const THREAD: u64 = 1;
+
+// Error that unwinds the program when the client ends the session
+const STOPPED: &str = "Stopped by the debugger";
+
+/// Debug `program`, read from `path`, for a client speaking the Debug Adapter
+/// Protocol over stdin and stdout. The program starts once the client has
+/// sent `launch` and `configurationDone`, and the session lasts until the
+/// client disconnects.
+pub fn serve(
+    mut interpreter: Interpreter,
+    program: Vec<Located>,
+    path: &Path,
+) -> Result<i32, String> {
+    // Requests are read on their own thread so that they can arrive while
+    // the program runs
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = io::stdin().lock();
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let session = Rc::new(RefCell::new(Session {
+        messages,
+        client: Client::default(),
+        program: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        launched: false,
+        configured: false,
+        terminated: false,
+        disconnected: false,
+        breakpoints: HashMap::new(),
+        step: Step::Continue,
+        stopped_at: None,
+        references: Vec::new(),
+    }));
+
+    // Breakpoints are set between `initialize` and `configurationDone`
+    loop {
+        let mut session = session.borrow_mut();
+        if session.launched && session.configured {
+            break;
+        }
+        let Ok(request) = session.messages.recv() else {
+            return Ok(0);
+        };
+        if let Action::End = session.handle(&request, None)? {
+            return Ok(0);
+        }
+    }
+
+    let client = session.borrow().client.clone();
+    interpreter.set_io(Console {
+        stdout: Output {
+            client: client.clone(),
+            category: "stdout",
+        },
+        stderr: Output {
+            client,
+            category: "stderr",
+        },
+    });
+    interpreter.attach_debugger(Box::new(Attached(session.clone())));
+    let result = interpreter.run(program);
+
+    let mut session = session.borrow_mut();
+    if session.disconnected {
+        return Ok(0);
+    }
+    let exit_code = match result {
+        _ if session.terminated => None,
+        Ok(value) => Some(interpreter::exit_code(&value)),
+        Err(e) => {
+            session.output("stderr", &format!("Runtime error: {}\n", e))?;
+            Some(1)
+        }
+    };
+    if let Some(exit_code) = exit_code {
+        session.event("exited", json!({ "exitCode": exit_code }))?;
+    }
+    session.event("terminated", json!({}))?;
+
+    while let Ok(request) = session.messages.recv() {
+        session.handle(&request, None)?;
+        if session.disconnected {
+            break;
+        }
+    }
+    Ok(0)
+}
+
+// How the program runs until it next stops
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Continue,
+    Entry,
+    Pause,
+    Into,
+    Over(usize), // Stop at the next statement at most this deep in the call stack
+    Out(usize),  // Stop at the next statement less deep than this
+}
+
+// What a request asks of the program
+enum Action {
+    None,
+    Resume,
+    End,
+}
+
+struct Breakpoint {
+    line: usize,
+    condition: Option<String>,
+}
+
+struct Session {
+    messages: Receiver<Json>,
+    client: Client,
+    program: PathBuf,
+    launched: bool,
+    configured: bool,
+    terminated: bool,
+    disconnected: bool,
+    breakpoints: HashMap<PathBuf, Vec<Breakpoint>>, // By canonical path of their file
+    step: Step,
+    stopped_at: Option<((PathBuf, usize, usize), usize)>, // File, line and depth, and the statement
+    references: Vec<Vec<(String, Value)>>, // Variables shown while stopped, by reference - 1
+}
+
+// The session as the interpreter's debugger
+struct Attached(Rc<RefCell<Session>>);
+
+impl Debugger for Attached {
+    fn statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        statement: &Located,
+    ) -> Result<(), String> {
+        self.0.borrow_mut().statement(interpreter, statement)
+    }
+}
+
+// Messages to the client, numbered in the order they are sent. Shared by the
+// session and the program's output, which can be written while the session
+// is busy, such as by an expression the client evaluates.
+#[derive(Clone, Default)]
+struct Client(Rc<Cell<u64>>);
+
+impl Client {
+    fn send(&self, mut message: Json) -> Result<(), String> {
+        let seq = self.0.get() + 1;
+        self.0.set(seq);
+        message["seq"] = json!(seq);
+        write_message(&mut io::stdout().lock(), &message)
+    }
+}
+
+// The program's I/O under the debugger: output goes to the client as output
+// events, and there is no input, as stdin carries the protocol
+struct Console {
+    stdout: Output,
+    stderr: Output,
+}
+
+struct Output {
+    client: Client,
+    category: &'static str,
+}
+
+impl Io for Console {
+    fn stdout(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn stderr(&mut self) -> &mut dyn Write {
+        &mut self.stderr
+    }
+
+    fn stdin(&mut self) -> Box<dyn BufRead + '_> {
+        Box::new(io::empty())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(bytes);
+        let event = json!({ "type": "event", "event": "output", "body": { "category": self.category, "output": text } });
+        // A client that went away ends the program at its next statement
+        let _ = self.client.send(event);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Session {
+    fn statement(
+        &mut self,
+        interpreter: &mut Interpreter,
+        statement: &Located,
+    ) -> Result<(), String> {
+        // Requests sent while the program runs
+        loop {
+            match self.messages.try_recv() {
+                Ok(request) => {
+                    if let Action::End = self.handle(&request, None)? {
+                        return Err(STOPPED.to_string());
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.disconnected = true;
+                    return Err(STOPPED.to_string());
+                }
+            }
+        }
+
+        let Some(reason) = self.stop_reason(interpreter, statement) else {
+            return Ok(());
+        };
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD, "allThreadsStopped": true }),
+        )?;
+        loop {
+            let Ok(request) = self.messages.recv() else {
+                self.disconnected = true;
+                return Err(STOPPED.to_string());
+            };
+            match self.handle(&request, Some(&mut *interpreter))? {
+                Action::Resume => {
+                    self.references.clear();
+                    return Ok(());
+                }
+                Action::End => return Err(STOPPED.to_string()),
+                Action::None => {}
+            }
+        }
+    }
+
+    // Why the program stops before `statement`, if it does
+    fn stop_reason(
+        &mut self,
+        interpreter: &mut Interpreter,
+        statement: &Located,
+    ) -> Option<&'static str> {
+        let call_stack = interpreter.call_stack();
+        let file = call_stack
+            .last()?
+            .file
+            .clone()
+            .unwrap_or_else(|| self.program.clone());
+        let position = (file, statement.line, call_stack.len());
+        let address = statement as *const Located as usize;
+
+        // The statements after the one stopped at on its line don't stop
+        // again; the same statement does when a loop runs it again
+        match &self.stopped_at {
+            Some((at, stopped)) if *at == position => {
+                if address > *stopped {
+                    return None;
+                }
+            }
+            _ => self.stopped_at = None,
+        }
+
+        let depth = position.2;
+        let stepped = match self.step {
+            Step::Continue => None,
+            Step::Entry => Some("entry"),
+            Step::Pause => Some("pause"),
+            Step::Into => Some("step"),
+            Step::Over(from) => (depth <= from).then_some("step"),
+            Step::Out(from) => (depth < from).then_some("step"),
+        };
+        let reason = match stepped {
+            Some(reason) => reason,
+            None if self.breakpoint_hit(interpreter, &position.0, position.1) => "breakpoint",
+            None => return None,
+        };
+        self.step = Step::Continue;
+        self.stopped_at = Some((position, address));
+        Some(reason)
+    }
+
+    fn breakpoint_hit(&mut self, interpreter: &mut Interpreter, file: &Path, line: usize) -> bool {
+        let breakpoint = self
+            .breakpoints
+            .get(file)
+            .and_then(|breakpoints| breakpoints.iter().find(|b| b.line == line));
+        let Some(breakpoint) = breakpoint else {
+            return false;
+        };
+        let Some(condition) = breakpoint.condition.clone() else {
+            return true;
+        };
+
+        // A condition that can't be evaluated stops the program to say so
+        let problem = match interpreter.evaluate(&condition) {
+            Ok(Value::Bool(hit)) => return hit,
+            Ok(other) => format!("is {}, not a boolean", show(&other)),
+            Err(e) => format!("failed: {}", e),
+        };
+        let _ = self.output(
+            "console",
+            &format!("Breakpoint condition '{}' {}\n", condition, problem),
+        );
+        true
+    }
+
+    // Answer a request, with the interpreter if the program is stopped
+    fn handle(
+        &mut self,
+        request: &Json,
+        interpreter: Option<&mut Interpreter>,
+    ) -> Result<Action, String> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let mut action = Action::None;
+
+        let body = match (command, interpreter) {
+            ("initialize", _) => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsEvaluateForHovers": true,
+                "supportsTerminateRequest": true,
+            })),
+            ("launch", _) => {
+                if arguments["stopOnEntry"].as_bool() == Some(true) {
+                    self.step = Step::Entry;
+                }
+                self.launched = true;
+                Ok(Json::Null)
+            }
+            ("configurationDone", _) => {
+                self.configured = true;
+                Ok(Json::Null)
+            }
+            ("setBreakpoints", _) => Ok(self.set_breakpoints(arguments)),
+            ("threads", _) => Ok(json!({ "threads": [{ "id": THREAD, "name": "main" }] })),
+            ("pause", _) => {
+                self.step = Step::Pause;
+                Ok(Json::Null)
+            }
+            ("disconnect" | "terminate", _) => {
+                self.terminated = true;
+                self.disconnected = command == "disconnect";
+                action = Action::End;
+                Ok(Json::Null)
+            }
+            ("continue" | "next" | "stepIn" | "stepOut", Some(interpreter)) => {
+                let depth = interpreter.call_stack().len();
+                self.step = match command {
+                    "continue" => Step::Continue,
+                    "next" => Step::Over(depth),
+                    "stepIn" => Step::Into,
+                    _ => Step::Out(depth),
+                };
+                action = Action::Resume;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            ("stackTrace", Some(interpreter)) => Ok(self.stack_trace(interpreter)),
+            ("scopes", Some(interpreter)) => self.scopes(interpreter, arguments),
+            ("variables", Some(_)) => self.variables(arguments),
+            ("evaluate", Some(interpreter)) => self.evaluate(interpreter, arguments),
+            (
+                "continue" | "next" | "stepIn" | "stepOut" | "stackTrace" | "scopes" | "variables"
+                | "evaluate",
+                None,
+            ) => Err("The program is not paused".to_string()),
+            _ => Err(format!("Unsupported request '{}'", command)),
+        };
+
+        self.respond(request, body)?;
+        if command == "initialize" {
+            self.event("initialized", json!({}))?;
+        }
+        Ok(action)
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let path = arguments["source"]["path"]
+            .as_str()
+            .map(|path| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)));
+        let breakpoints: Vec<Breakpoint> = arguments["breakpoints"]
+            .as_array()
+            .map(|breakpoints| {
+                breakpoints
+                    .iter()
+                    .map(|b| Breakpoint {
+                        line: b["line"].as_u64().unwrap_or_default() as usize,
+                        condition: b["condition"]
+                            .as_str()
+                            .filter(|c| !c.trim().is_empty())
+                            .map(str::to_string),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let verified: Vec<Json> = breakpoints
+            .iter()
+            .map(|b| json!({ "verified": path.is_some(), "line": b.line }))
+            .collect();
+        if let Some(path) = path {
+            self.breakpoints.insert(path, breakpoints);
+        }
+        json!({ "breakpoints": verified })
+    }
+
+    // Frame ids are indexes in the interpreter's call stack
+    fn stack_trace(&self, interpreter: &Interpreter) -> Json {
+        let frames: Vec<Json> = interpreter
+            .call_stack()
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(id, frame)| {
+                let path = frame.file.as_ref().unwrap_or(&self.program);
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_default();
+                json!({
+                    "id": id,
+                    "name": frame.function,
+                    "source": { "name": name, "path": path },
+                    "line": frame.line,
+                    "column": 1,
+                })
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    fn scopes(&mut self, interpreter: &Interpreter, arguments: &Json) -> Result<Json, String> {
+        let frame = arguments["frameId"].as_u64().unwrap_or_default() as usize;
+        let innermost = interpreter.call_stack().len() - 1;
+        if frame > innermost {
+            return Err(format!("Unknown frame {}", frame));
+        }
+
+        // Inner scopes shadow outer ones
+        let mut locals = BTreeMap::new();
+        for scope in interpreter.frame_scopes(frame) {
+            locals.extend(
+                scope
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone())),
+            );
+        }
+        let mut scopes = vec![json!({
+            "name": "Locals",
+            "variablesReference": self.reference(locals.into_iter().collect()),
+            "expensive": false,
+        })];
+        // Callers may belong to other files, whose globals are set aside
+        if frame == innermost {
+            let globals: BTreeMap<_, _> = interpreter
+                .globals()
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            scopes.push(json!({
+                "name": "Globals",
+                "variablesReference": self.reference(globals.into_iter().collect()),
+                "expensive": false,
+            }));
+        }
+        Ok(json!({ "scopes": scopes }))
+    }
+
+    fn variables(&mut self, arguments: &Json) -> Result<Json, String> {
+        let reference = arguments["variablesReference"].as_u64().unwrap_or_default() as usize;
+        let variables = reference
+            .checked_sub(1)
+            .and_then(|index| self.references.get(index))
+            .cloned()
+            .ok_or_else(|| format!("Unknown variables reference {}", reference))?;
+
+        let variables: Vec<Json> = variables
+            .into_iter()
+            .map(|(name, value)| {
+                json!({
+                    "name": name,
+                    "value": show(&value),
+                    "variablesReference": self.children(&value),
+                })
+            })
+            .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn evaluate(
+        &mut self,
+        interpreter: &mut Interpreter,
+        arguments: &Json,
+    ) -> Result<Json, String> {
+        let frame = arguments["frameId"].as_u64().map(|frame| frame as usize);
+        if frame.is_some_and(|frame| frame + 1 != interpreter.call_stack().len()) {
+            return Err("Expressions can only be evaluated in the innermost frame".to_string());
+        }
+        let value = interpreter.evaluate(arguments["expression"].as_str().unwrap_or_default())?;
+        Ok(json!({ "result": show(&value), "variablesReference": self.children(&value) }))
+    }
+
+    // Reference to the elements, entries or fields of a value, or 0 if it has none
+    fn children(&mut self, value: &Value) -> usize {
+        let children: Vec<(String, Value)> = match value {
+            Value::Array(elements) => elements
+                .iter()
+                .enumerate()
+                .map(|(i, element)| (format!("[{}]", i), element.clone()))
+                .collect(),
+            Value::Map(entries) => entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+            Value::Struct { fields, .. } => fields.clone(),
+            Value::Enum { values, .. } => values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (i.to_string(), value.clone()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if children.is_empty() {
+            0
+        } else {
+            self.reference(children)
+        }
+    }
+
+    fn reference(&mut self, variables: Vec<(String, Value)>) -> usize {
+        self.references.push(variables);
+        self.references.len()
+    }
+
+    fn respond(&mut self, request: &Json, body: Result<Json, String>) -> Result<(), String> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": body.is_ok(),
+        });
+        match body {
+            Ok(Json::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.client.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> Result<(), String> {
+        self.client
+            .send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn output(&mut self, category: &str, text: &str) -> Result<(), String> {
+        self.event("output", json!({ "category": category, "output": text }))
+    }
+}
+
+// A value as the variables view shows it, with strings quoted
+fn show(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
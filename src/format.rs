@@ -0,0 +1,487 @@
+use crate::ast::Span;
+use crate::lexer::{Comment, Lexer, Token, takes_expression};
+use crate::parser::Parser;
+
+const INDENT: &str = "    ";
+
+/// Format Kode source code the canonical way: four spaces of indentation per
+/// block, opening braces on the line of their statement, `else` and `catch`
+/// after the closing brace, spaces around binary operators and none inside
+/// parentheses and brackets. Comments, line breaks inside statements and
+/// single blank lines are kept. Formatting formatted code changes nothing.
+pub fn format(source_name: &str, source: &str) -> Result<String, String> {
+    Parser::new(source_name, source)
+        .and_then(|mut parser| parser.parse())
+        .map_err(|e| format!("Parse error: {}", e))?;
+
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize()?;
+    let mut formatter = Formatter::new(source, &tokens, lexer.spans(), lexer.comments());
+    formatter.run();
+    let formatted = formatter.out;
+
+    // Only whitespace may change, so the tokens must read the same
+    let mut relexer = Lexer::new(&formatted);
+    relexer.tokenize()?;
+    let texts = |source: &str, spans: &[Span]| -> Vec<String> {
+        spans
+            .iter()
+            .map(|span| source[span.start..span.end].to_string())
+            .collect()
+    };
+    if texts(source, lexer.spans()) != texts(&formatted, relexer.spans()) {
+        return Err(format!("Formatting {} would change its code", source_name));
+    }
+    Ok(formatted)
+}
+
+/// What a pair of braces, brackets or parentheses holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Block,  // Statements: a body of a function or of control flow, an `impl`
+    Arms,   // The arms of a `match`
+    Fields, // A struct or enum declaration, a struct literal or pattern
+    Map,    // A map literal or type
+    Paren,
+    Bracket,
+}
+
+struct Frame {
+    kind: Kind,
+    outer: usize,                 // Indentation of the line that opens the frame
+    inner: usize,                 // Indentation of the lines inside
+    inline: bool,                 // A block or `match` kept on one line
+    statement: bool,              // Braces of a statement rather than of an expression
+    expect: Option<(Kind, bool)>, // What the next `{` opens, after a keyword such as `if`
+    ternaries: usize,             // `?` waiting for their `:`
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Prev {
+    Start,
+    Token(usize),
+    LineComment,
+    BlockComment,
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    tokens: &'a [Token],
+    spans: &'a [Span],
+    comments: &'a [Comment],
+    closers: Vec<usize>, // Index of the matching closing token of each opening one
+    out: String,
+    indent: usize, // Indentation of the current output line
+    frames: Vec<Frame>,
+    prev: Prev,
+    last: Option<usize>,   // Last token written
+    unary: bool,           // The last token is a unary operator
+    statement_start: bool, // The next token starts a statement or a `match` arm
+    continues: bool,       // The next `else` or `catch` belongs to a statement
+    line_break: bool,      // The next token goes on a new line
+}
+
+impl<'a> Formatter<'a> {
+    fn new(
+        source: &'a str,
+        tokens: &'a [Token],
+        spans: &'a [Span],
+        comments: &'a [Comment],
+    ) -> Self {
+        let mut closers = vec![0; tokens.len()];
+        let mut open = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                Token::LParen | Token::LBrace | Token::LBracket => open.push(i),
+                Token::RParen | Token::RBrace | Token::RBracket => {
+                    if let Some(start) = open.pop() {
+                        closers[start] = i;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let root = Frame {
+            kind: Kind::Block,
+            outer: 0,
+            inner: 0,
+            inline: false,
+            statement: true,
+            expect: None,
+            ternaries: 0,
+        };
+        Self {
+            source,
+            tokens,
+            spans,
+            comments,
+            closers,
+            out: String::new(),
+            indent: 0,
+            frames: vec![root],
+            prev: Prev::Start,
+            last: None,
+            unary: false,
+            statement_start: true,
+            continues: false,
+            line_break: false,
+        }
+    }
+
+    fn run(&mut self) {
+        let mut comments = self.comments.iter().peekable();
+        let mut end = 0; // Where the last token or comment ends
+        for i in 0..self.tokens.len() {
+            if self.tokens[i] == Token::EOF {
+                break;
+            }
+            while let Some(comment) = comments.next_if(|c| c.span.start < self.spans[i].start) {
+                self.comment(comment, end);
+                end = comment.span.end;
+            }
+            self.token(i, end);
+            end = self.spans[i].end;
+        }
+        for comment in comments {
+            self.comment(comment, end);
+            end = comment.span.end;
+        }
+
+        let trimmed = self.out.trim_end().len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    fn top(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    // Line breaks in the source between byte offsets
+    fn lines(&self, start: usize, end: usize) -> usize {
+        self.source[start..end].matches('\n').count()
+    }
+
+    fn new_line(&mut self, breaks: usize, indent: usize) {
+        for _ in 0..breaks.min(2) {
+            self.out.push('\n');
+        }
+        self.out.push_str(&INDENT.repeat(indent));
+        self.indent = indent;
+    }
+
+    // Indentation of a line inside the innermost frame: statements and arms
+    // broken over several lines indent their continuation lines once more,
+    // except for an `else` or `catch`
+    fn inner_indent(&self) -> usize {
+        let top = self.top();
+        let continuation = matches!(top.kind, Kind::Block | Kind::Arms)
+            && !self.statement_start
+            && !self.continues;
+        top.inner + continuation as usize
+    }
+
+    fn comment(&mut self, comment: &Comment, end: usize) {
+        let lines = self.lines(end, comment.span.start);
+        if self.prev == Prev::Start {
+            // Nothing to separate the first comment from
+        } else if comment.trailing && self.prev != Prev::LineComment {
+            let gap = self.source[end..comment.span.start].len().max(1);
+            self.out.push_str(&" ".repeat(gap));
+        } else {
+            let after_open = matches!(self.prev, Prev::Token(i) if self.is_opening(i));
+            let breaks = if after_open { 1 } else { lines.max(1) };
+            self.new_line(breaks, self.inner_indent());
+            self.line_break = false;
+        }
+
+        self.out.push_str(&comment.text);
+        self.prev = if comment.text.starts_with("//") {
+            Prev::LineComment
+        } else {
+            Prev::BlockComment
+        };
+    }
+
+    fn is_opening(&self, i: usize) -> bool {
+        matches!(
+            self.tokens[i],
+            Token::LParen | Token::LBrace | Token::LBracket
+        )
+    }
+
+    fn token(&mut self, i: usize, end: usize) {
+        let token = &self.tokens[i];
+        let opens = match token {
+            Token::LBrace => Some(self.brace_kind()),
+            Token::LParen => Some((Kind::Paren, false)),
+            Token::LBracket => Some((Kind::Bracket, false)),
+            _ => None,
+        };
+        let closes = matches!(token, Token::RParen | Token::RBrace | Token::RBracket);
+
+        let breaks = self.breaks_before(i, end, opens.map(|(kind, _)| kind));
+        if self.prev == Prev::Start {
+            // The first token starts the file
+        } else if breaks > 0 {
+            let indent = if closes {
+                self.top().outer
+            } else {
+                self.inner_indent()
+            };
+            self.new_line(breaks, indent);
+        } else if self.space_before(i) {
+            self.out.push(' ');
+        }
+        self.out
+            .push_str(&self.source[self.spans[i].start..self.spans[i].end]);
+
+        self.after_token(i, opens);
+    }
+
+    // What a `{` opens, and whether it belongs to a statement
+    fn brace_kind(&mut self) -> (Kind, bool) {
+        let statement_start = self.statement_start;
+        let prev = self.last.map(|i| &self.tokens[i]);
+        let top = self.frames.last_mut().unwrap();
+
+        if let Some(prev) = prev.filter(|prev| takes_expression(prev)) {
+            // An arm of a `match` statement is a block, other values are maps
+            return if *prev == Token::FatArrow && top.kind == Kind::Arms && top.statement {
+                (Kind::Block, true)
+            } else {
+                (Kind::Map, false)
+            };
+        }
+        if let Some(expect) = top.expect.take() {
+            return expect;
+        }
+        if top.kind == Kind::Block && statement_start {
+            return (Kind::Block, true);
+        }
+        match prev {
+            Some(Token::Identifier(_)) => (Kind::Fields, false),
+            _ => (Kind::Map, false),
+        }
+    }
+
+    fn breaks_before(&self, i: usize, end: usize, opens: Option<Kind>) -> usize {
+        let lines = self.lines(end, self.spans[i].start).min(2);
+        let last = match self.prev {
+            Prev::Start => return 0,
+            Prev::LineComment => return lines.max(1),
+            Prev::BlockComment => None,
+            Prev::Token(last) => Some(last),
+        };
+        let token = &self.tokens[i];
+        let last_token = last.map(|last| &self.tokens[last]);
+
+        // Tokens that stay on the line of the token before them
+        let joined = match token {
+            Token::Semicolon | Token::Comma => true,
+            Token::Else | Token::Catch => last_token == Some(&Token::RBrace),
+            Token::LBrace => {
+                !self.statement_start
+                    && matches!(opens, Some(Kind::Block | Kind::Arms | Kind::Fields))
+            }
+            _ => false,
+        };
+        if joined && last.is_some() {
+            return 0;
+        }
+
+        let top = self.top();
+        let closes_block =
+            *token == Token::RBrace && matches!(top.kind, Kind::Block | Kind::Arms) && !top.inline;
+        let after_open = last.is_some_and(|last| self.is_opening(last));
+        let before_close = matches!(token, Token::RParen | Token::RBrace | Token::RBracket);
+        if after_open || before_close {
+            // No blank lines at the edges of a block
+            if self.line_break || closes_block {
+                1
+            } else {
+                lines.min(1)
+            }
+        } else if self.line_break {
+            lines.max(1)
+        } else {
+            lines
+        }
+    }
+
+    fn space_before(&self, i: usize) -> bool {
+        let Prev::Token(last) = self.prev else {
+            return true;
+        };
+        let token = &self.tokens[i];
+        let prev = &self.tokens[last];
+        let top = self.top();
+
+        match token {
+            Token::Comma
+            | Token::Semicolon
+            | Token::Dot
+            | Token::RParen
+            | Token::RBracket
+            | Token::PlusPlus
+            | Token::MinusMinus => return false,
+            Token::Colon => return top.ternaries > 0,
+            _ => {}
+        }
+        if self.unary {
+            // `- -x` must not become `--x`
+            return *prev == Token::Minus && matches!(token, Token::Minus | Token::MinusMinus);
+        }
+        match prev {
+            Token::LParen | Token::LBracket | Token::Dot | Token::DotDot => return false,
+            Token::LBrace => return top.kind != Kind::Map && *token != Token::RBrace,
+            _ => {}
+        }
+        match token {
+            Token::DotDot => *prev == Token::Comma,
+            Token::LParen => !(self.ends_operand(prev) || matches!(prev, Token::Fn | Token::Main)),
+            Token::LBracket => !self.ends_operand(prev),
+            Token::RBrace => top.kind != Kind::Map,
+            _ => true,
+        }
+    }
+
+    // Whether a token ends an operand, so that a `-` after it is binary and a
+    // `(` or `[` after it is a call or an index
+    fn ends_operand(&self, token: &Token) -> bool {
+        !self.statement_start
+            && matches!(
+                token,
+                Token::Number(_)
+                    | Token::BigInt(_)
+                    | Token::Float(_)
+                    | Token::Bool(_)
+                    | Token::String(_)
+                    | Token::InterpolatedString(_)
+                    | Token::Identifier(_)
+                    | Token::RParen
+                    | Token::RBracket
+                    | Token::RBrace
+            )
+    }
+
+    fn after_token(&mut self, i: usize, opens: Option<(Kind, bool)>) {
+        let token = &self.tokens[i];
+        let statement_start = self.statement_start;
+        let continues = self.continues;
+        self.unary = match token {
+            Token::Minus => !self
+                .last
+                .is_some_and(|last| self.ends_operand(&self.tokens[last])),
+            Token::Not | Token::BitNot => true,
+            _ => false,
+        };
+        self.prev = Prev::Token(i);
+        self.last = Some(i);
+        self.statement_start = false;
+        self.continues = false;
+        self.line_break = false;
+
+        if let Some((kind, statement)) = opens {
+            let close = self.closers[i];
+            let inline = matches!(kind, Kind::Block | Kind::Arms) && self.is_inline(i, close);
+            // The body of a statement lines up with the statement, even if
+            // its header is broken over several lines
+            let top = self.top();
+            let outer = if statement && top.kind == Kind::Block {
+                top.inner
+            } else {
+                self.indent
+            };
+            self.frames.push(Frame {
+                kind,
+                outer,
+                inner: outer + 1,
+                inline,
+                statement,
+                expect: None,
+                ternaries: 0,
+            });
+            if matches!(kind, Kind::Block | Kind::Arms) {
+                self.statement_start = true;
+                self.line_break = !inline;
+            }
+            return;
+        }
+
+        let top = self.frames.last_mut().unwrap();
+        match token {
+            Token::RParen | Token::RBrace | Token::RBracket => {
+                let frame = self.frames.pop().unwrap();
+                if self.frames.is_empty() {
+                    // Unbalanced, which the parser doesn't accept
+                    self.frames.push(frame);
+                    return;
+                }
+                if *token == Token::RBrace && frame.statement {
+                    let parent = self.top();
+                    match &self.tokens[i + 1] {
+                        Token::Else | Token::Catch => self.continues = true,
+                        Token::Semicolon
+                        | Token::Comma
+                        | Token::Dot
+                        | Token::RParen
+                        | Token::RBracket
+                        | Token::LParen => {}
+                        _ => {
+                            self.line_break =
+                                matches!(parent.kind, Kind::Block | Kind::Arms) && !parent.inline;
+                            self.statement_start = true;
+                        }
+                    }
+                }
+            }
+            Token::Semicolon => {
+                top.expect = None;
+                if top.kind == Kind::Block {
+                    self.line_break = !top.inline;
+                    self.statement_start = true;
+                }
+            }
+            Token::Comma if top.kind == Kind::Arms => {
+                self.line_break = !top.inline;
+                self.statement_start = true;
+            }
+            Token::FatArrow => top.expect = None,
+            Token::Question => top.ternaries += 1,
+            Token::Colon => top.ternaries = top.ternaries.saturating_sub(1),
+            Token::Pub => self.statement_start = statement_start,
+            Token::If | Token::While | Token::For | Token::Fn | Token::Try | Token::Impl => {
+                top.expect = Some((Kind::Block, statement_start || continues));
+                self.continues = continues; // `if` of an `else if`
+            }
+            Token::Else | Token::Catch => {
+                top.expect = Some((Kind::Block, continues));
+                self.continues = continues;
+            }
+            Token::Match => top.expect = Some((Kind::Arms, statement_start)),
+            Token::Struct | Token::Enum => top.expect = Some((Kind::Fields, statement_start)),
+            _ => {}
+        }
+    }
+
+    // A block or `match` stays on one line if it is empty, or if it was
+    // written on one line and holds at most one statement
+    fn is_inline(&self, open: usize, close: usize) -> bool {
+        let (start, end) = (self.spans[open].end, self.spans[close].start);
+        let has_comments = self
+            .comments
+            .iter()
+            .any(|c| c.span.start >= start && c.span.end <= end);
+        if close == open + 1 {
+            return !has_comments;
+        }
+        let statements = self.tokens[open + 1..close]
+            .iter()
+            .filter(|t| **t == Token::Semicolon)
+            .count();
+        self.spans[open].line == self.spans[close].line && statements <= 1
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::interpreter::{MapKey, Value};
+
+/// A Rust type that a Kode value converts to, for the results of calls into
+/// Kode, globals and the parameters of native functions
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, String>;
+}
+
+/// A Rust type that converts to a Kode value, for the arguments of calls into
+/// Kode, globals and the results of native functions
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// Arguments of a call into Kode: a tuple of values that convert to Kode
+/// values, or the Kode values themselves
+pub trait IntoArgs {
+    fn into_args(self) -> Vec<Value>;
+}
+
+/// What a native function returns: a value, or a `Result` whose error
+/// becomes a runtime error of the Kode program
+pub trait IntoResult {
+    fn into_result(self) -> Result<Value, String>;
+}
+
+/// A Rust function that Kode programs can call, registered with
+/// `Interpreter::register_fn`. Implemented for functions and closures of up
+/// to six parameters that implement `FromValue`, returning an `IntoResult`.
+pub trait NativeFunction<Args> {
+    /// Call the function as `name`, the name Kode calls it by
+    fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, String>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Number(n) => Ok(n),
+            other => Err(format!("Expected an integer, got {:?}", other)),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Float(n) => Ok(n),
+            Value::Number(n) => Ok(n as f64),
+            other => Err(format!("Expected a number, got {:?}", other)),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            other => Err(format!("Expected a boolean, got {:?}", other)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::String(s) => Ok(s),
+            other => Err(format!("Expected a string, got {:?}", other)),
+        }
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Void => Ok(()),
+            other => Err(format!("Expected void, got {:?}", other)),
+        }
+    }
+}
+
+// `void` is `None`
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Void => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Array(elements) => elements.into_iter().map(T::from_value).collect(),
+            other => Err(format!("Expected an array, got {:?}", other)),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Map(entries) => entries
+                .into_iter()
+                .map(|(key, value)| match key {
+                    MapKey::String(key) => Ok((key, T::from_value(value)?)),
+                    other => Err(format!(
+                        "Expected a map with string keys, got key {}",
+                        other
+                    )),
+                })
+                .collect(),
+            other => Err(format!("Expected a map, got {:?}", other)),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl IntoValue for i32 {
+    fn into_value(self) -> Value {
+        Value::Number(self.into())
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Void
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Void, IntoValue::into_value)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::Array(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        let entries: BTreeMap<MapKey, Value> = self
+            .into_iter()
+            .map(|(key, value)| (MapKey::String(key), value.into_value()))
+            .collect();
+        Value::Map(entries)
+    }
+}
+
+impl IntoArgs for Vec<Value> {
+    fn into_args(self) -> Vec<Value> {
+        self
+    }
+}
+
+impl<T: IntoValue> IntoResult for T {
+    fn into_result(self) -> Result<Value, String> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue> IntoResult for Result<T, String> {
+    fn into_result(self) -> Result<Value, String> {
+        self.map(IntoValue::into_value)
+    }
+}
+
+// `IntoArgs` for tuples and `NativeFunction` for functions of each arity
+macro_rules! arity {
+    ($($arg:ident),*) => {
+        impl<$($arg: IntoValue),*> IntoArgs for ($($arg,)*) {
+            #[allow(non_snake_case)]
+            fn into_args(self) -> Vec<Value> {
+                let ($($arg,)*) = self;
+                vec![$($arg.into_value()),*]
+            }
+        }
+
+        impl<Function, Output, $($arg),*> NativeFunction<($($arg,)*)> for Function
+        where
+            Function: Fn($($arg),*) -> Output,
+            Output: IntoResult,
+            $($arg: FromValue),*
+        {
+            #[allow(non_snake_case)]
+            fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+                const EXPECTED: usize = <[&str]>::len(&[$(stringify!($arg)),*]);
+                let count = args.len();
+                let Ok([$($arg),*]) = <[Value; EXPECTED]>::try_from(args) else {
+                    return Err(format!("Function '{}' expects {} args, got {}", name, EXPECTED, count));
+                };
+                $(let $arg = $arg::from_value($arg).map_err(|e| format!("In function '{}': {}", name, e))?;)*
+                self($($arg),*).into_result()
+            }
+        }
+    };
+}
+
+arity!();
+arity!(A);
+arity!(A, B);
+arity!(A, B, C);
+arity!(A, B, C, D);
+arity!(A, B, C, D, E);
+arity!(A, B, C, D, E, F);
@@ -1,20 +1,43 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use crate::ast::{Statement, Expression, BinaryOp, UnaryOp};
+use crate::ast::{Located, Statement, Expression, BinaryOp, UnaryOp, Variant, MatchArm, ArmBody, Pattern, InterpolationPart};
 use crate::parser::Parser;
+use crate::modules::ModuleResolver;
+use crate::host::{FromValue, IntoArgs, IntoValue, NativeFunction};
+use crate::io::{Io, StdIo};
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(i64),
+    BigInt(BigInt), // Only for integers that do not fit in a Number
     Float(f64),
     Bool(bool),
     Void,
     String(String),
     Array(Vec<Value>),
+    Map(BTreeMap<MapKey, Value>),
+    Struct {
+        name: String,
+        module: Option<PathBuf>, // File that declares the struct, None for the main program
+        fields: Vec<(String, Value)>, // In declaration order
+    },
+    Enum {
+        name: String,
+        module: Option<PathBuf>, // File that declares the enum, None for the main program
+        variant: String,
+        values: Vec<Value>,
+    },
+    Range {
+        start: i64,
+        end: i64, // Excluded
+    },
+    Enumerate(Box<Value>), // `enumerate(iterable)`, read lazily by `for`
     Closure {
         params: Vec<String>,
-        body: Vec<Statement>,
+        body: Vec<Located>,
         env: Environment,
     }
 }
@@ -23,6 +46,7 @@ impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
+            Value::BigInt(n) => write!(f, "{}", n),
             Value::Float(n) => write!(f, "{}", n),
             Value::Bool(b) => write!(f, "{}", b),
             Value::Void => write!(f, "void"),
@@ -37,28 +61,207 @@ impl std::fmt::Display for Value {
                 }
                 write!(f, "]")
             },
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            },
+            Value::Struct { name, fields, .. } => {
+                write!(f, "{} {{", name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    write!(f, "{} {}: {}", if i > 0 { "," } else { "" }, field, value)?;
+                }
+                write!(f, "{}}}", if fields.is_empty() { "" } else { " " })
+            },
+            Value::Enum { name, variant, values, .. } => {
+                write!(f, "{}.{}", name, variant)?;
+                if !values.is_empty() {
+                    write!(f, "(")?;
+                    for (i, value) in values.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", value)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            },
+            Value::Range { start, end } => write!(f, "{}..{}", start, end),
+            Value::Enumerate(iterable) => write!(f, "enumerate({})", iterable),
             Value::Closure { .. } => write!(f, "<function>"),
         }
     }
 }
 
+/// Map keys, kept sorted so maps print and iterate in a fixed order
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Bool(bool),
+    Number(i64),
+    String(String),
+}
+
+impl MapKey {
+    fn from_value(value: &Value) -> Result<MapKey, String> {
+        match value {
+            Value::Bool(b) => Ok(MapKey::Bool(*b)),
+            Value::Number(n) => Ok(MapKey::Number(*n)),
+            Value::String(s) => Ok(MapKey::String(s.clone())),
+            other => Err(format!("Map keys must be strings, integers or booleans, got {:?}", other)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            MapKey::Bool(b) => Value::Bool(*b),
+            MapKey::Number(n) => Value::Number(*n),
+            MapKey::String(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for MapKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapKey::Bool(b) => write!(f, "{}", b),
+            MapKey::Number(n) => write!(f, "{}", n),
+            MapKey::String(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Function {
     file_prefix: String,
     name: String,
     is_main: bool,
+    is_public: bool,
     params: Vec<String>,
-    body: Vec<Statement>,
+    body: Vec<Located>,
+    module: Option<PathBuf>, // Module the function was imported from
+}
+
+// A `struct` declaration and the methods of its `impl` blocks
+#[derive(Debug, Clone)]
+struct StructDef {
+    fields: Vec<String>,
+    methods: HashMap<String, Function>,
+}
+
+// An `enum` declaration
+#[derive(Debug, Clone)]
+struct EnumDef {
+    variants: Vec<Variant>,
+}
+
+// Structs and enums are keyed by the file that declares them, None for the
+// main program, and their name
+type TypeKey = (Option<PathBuf>, String);
+
+// One step from a variable to the element being read or assigned
+enum PathSegment {
+    Index(Value),  // `[index]`
+    Field(String), // `.field`
+}
+
+// Where a `for ... in` loop is in its iterable
+enum LoopIter {
+    Values(std::vec::IntoIter<Value>), // Arrays, strings and maps
+    Range { next: i64, end: i64 },
+    Struct(Value), // A struct with `has_next(self)` and `next(self)` methods
+    Enumerate { inner: Box<LoopIter>, index: i64 },
+}
+
+// Names made available by the `import` statements of one file
+#[derive(Debug, Clone, Default)]
+struct ImportTable {
+    aliases: HashMap<String, PathBuf>, // Name usable as `name.function()` -> module
+    names: HashMap<String, (PathBuf, String)>, // `from m import f` name -> (module, function)
+}
+
+#[derive(Debug, Clone)]
+struct Module {
+    name: String,
+    functions: HashMap<String, Function>,
+    imports: ImportTable,
+    globals: HashMap<String, Value>, // Top-level `let` bindings, parked here while another file executes
+    exported: Option<HashSet<String>>, // Names declared `pub`, or None to export everything
+}
+
+impl Module {
+    // A module that marks nothing `pub` exports all of its names
+    fn is_exported(&self, name: &str) -> bool {
+        self.exported.as_ref().is_none_or(|exported| exported.contains(name))
+    }
 }
 
 type Environment = Vec<HashMap<String, Value>>;
 
+// A Rust function registered by the host, called with the name Kode calls it by
+type Native = Box<dyn Fn(&str, Vec<Value>) -> Result<Value, String>>;
+
+/// A call being executed, as a debugger shows it
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub function: String,
+    pub file: Option<PathBuf>, // Module executing in the call, None for the main program
+    pub line: usize,           // Line of the statement executing in the call
+    scopes: Environment,       // Local scopes set aside while the call calls another
+}
+
+/// Gets control before each statement of a program run with a debugger
+/// attached, to pause it at breakpoints and while stepping
+pub trait Debugger {
+    /// Called before `statement` executes, with `call_stack()` positioned at
+    /// it. Returning an error stops the program.
+    fn statement(&mut self, interpreter: &mut Interpreter, statement: &Located) -> Result<(), String>;
+}
+
 pub struct Interpreter {
     variables: Environment,
     functions: HashMap<String, Function>,
-    imported_modules: HashMap<String, HashMap<String, Function>>,
+    structs: HashMap<TypeKey, StructDef>, // Struct types of the program and all loaded modules
+    enums: HashMap<TypeKey, EnumDef>, // Enum types, kept the same way
+    imported_modules: HashMap<PathBuf, Module>, // Loaded once per run, keyed by canonical path
+    imports: ImportTable, // Imports of the main program
+    main_globals: HashMap<String, Value>, // Globals of the main program while a module executes
+    loading_modules: Vec<(String, PathBuf)>, // Import chain being loaded, for cycle detection
+    resolver: ModuleResolver,
     current_function: Option<String>, // Track current function for better error messages
+    current_module: Option<PathBuf>, // Module of the executing function, if imported
     base_path: PathBuf,
+    program_args: Vec<String>, // Arguments forwarded after `--` on the command line
+    call_stack: Vec<CallFrame>, // Top-level code, then the functions it called
+    debugger: Option<Box<dyn Debugger>>,
+    natives: HashMap<String, Native>,
+    io: Box<dyn Io>,
+}
+
+/// Convert the value returned by the entry point into a process exit code.
+/// Integers a process can't exit with, outside 0..=255, are a failure (1).
+pub fn exit_code(value: &Value) -> u8 {
+    match value {
+        Value::Number(n) => u8::try_from(*n).unwrap_or(1),
+        Value::BigInt(_) => 1,
+        _ => 0,
+    }
+}
+
+/// Names of the functions `Interpreter::call_builtin` provides
+pub const BUILTINS: &[&str] = &[
+    "args", "env", "input", "read_line", "eprint", "len", "enumerate", "keys", "values", "has", "remove",
+];
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
@@ -66,9 +269,26 @@ impl Interpreter {
         Interpreter {
             variables: vec![HashMap::new()],
             functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
             imported_modules: HashMap::new(),
+            imports: ImportTable::default(),
+            main_globals: HashMap::new(),
+            loading_modules: Vec::new(),
+            resolver: ModuleResolver::new(),
             current_function: None,
+            current_module: None,
             base_path: PathBuf::from("."),
+            program_args: Vec::new(),
+            call_stack: vec![CallFrame {
+                function: "<program>".to_string(),
+                file: None,
+                line: 0,
+                scopes: Vec::new(),
+            }],
+            debugger: None,
+            natives: HashMap::new(),
+            io: Box::new(StdIo::new()),
         }
     }
 
@@ -76,11 +296,171 @@ impl Interpreter {
         self.base_path = path.to_path_buf();
     }
 
-    pub fn run(&mut self, program: Vec<Statement>) -> Result<(), String> {
-        // First pass: register function definitions and handle imports
-        for stmt in &program {
-            match stmt {
-                Statement::FunctionDef { file_prefix, is_main, name, params, body } => {
+    pub fn set_program_args(&mut self, args: Vec<String>) {
+        self.program_args = args;
+    }
+
+    /// Add a directory searched for imported modules after the importing
+    /// file's own directory
+    pub fn add_search_path(&mut self, path: PathBuf) {
+        self.resolver.add_search_path(path);
+    }
+
+    /// Make a package dependency importable under its name
+    pub fn add_package(&mut self, name: String, entry: PathBuf) {
+        self.resolver.add_package(name, entry);
+    }
+
+    /// Run Kode source the way the REPL runs a line: its declarations become
+    /// callable and its top-level statements execute, with variables staying
+    /// defined between calls. An entry point such as `main` is not called.
+    /// Returns the value of a top-level `return` or of a final expression
+    /// statement, or void.
+    pub fn eval(&mut self, source: &str) -> Result<Value, String> {
+        let program = Parser::new("<eval>", source)?.parse()?;
+        let (statements, last) = match program.split_last() {
+            Some((Located { statement: Statement::Expression(expr), .. }, rest)) => (rest, Some(expr)),
+            _ => (program.as_slice(), None),
+        };
+
+        if let Some(value) = self.run_script(statements)? {
+            return Ok(value);
+        }
+        match last {
+            Some(expr) => self.eval_expr(expr),
+            None => Ok(Value::Void),
+        }
+    }
+
+    /// Call the Kode function `name` with arguments converted from Rust
+    /// values, such as a tuple, and convert its result
+    pub fn call<T: FromValue>(&mut self, name: &str, args: impl IntoArgs) -> Result<T, String> {
+        let value = self.call_function(name, args.into_args())?;
+        T::from_value(value)
+    }
+
+    /// A global variable of the program, converted to a Rust value
+    pub fn global<T: FromValue>(&self, name: &str) -> Result<T, String> {
+        let value = self.main_scope().get(name).cloned()
+            .ok_or_else(|| format!("Undefined variable '{}'", name))?;
+        T::from_value(value)
+    }
+
+    /// Define or replace a global variable of the program
+    pub fn set_global(&mut self, name: &str, value: impl IntoValue) {
+        let value = value.into_value();
+        let globals = match self.current_module {
+            Some(_) => &mut self.main_globals,
+            None => &mut self.variables[0],
+        };
+        globals.insert(name.to_string(), value);
+    }
+
+    /// Make a Rust function or closure callable from Kode as `name`. Its
+    /// parameters convert with `FromValue` and its result with `IntoValue`,
+    /// and an `Err` it returns is a runtime error of the program. Functions
+    /// the program defines take precedence over it, and it takes precedence
+    /// over built-in functions of the same name.
+    pub fn register_fn<Args: 'static, F: NativeFunction<Args> + 'static>(&mut self, name: &str, function: F) {
+        self.natives.insert(name.to_string(), Box::new(move |name, args| function.call(name, args)));
+    }
+
+    // Globals of the main program, wherever execution is
+    fn main_scope(&self) -> &HashMap<String, Value> {
+        match self.current_module {
+            Some(_) => &self.main_globals,
+            None => &self.variables[0],
+        }
+    }
+
+    /// Send what the program prints to `io` and read its input from it
+    /// instead of the process's stdout, stderr and stdin
+    pub fn set_io(&mut self, io: impl Io + 'static) {
+        self.io = Box::new(io);
+    }
+
+    /// Hand control to `debugger` before every statement
+    pub fn attach_debugger(&mut self, debugger: Box<dyn Debugger>) {
+        self.debugger = Some(debugger);
+    }
+
+    /// The calls being executed, outermost first
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.call_stack
+    }
+
+    /// Local scopes of call `frame` of `call_stack()`, outermost first. The
+    /// scopes of callers are only kept while a debugger is attached.
+    pub fn frame_scopes(&self, frame: usize) -> &[HashMap<String, Value>] {
+        if frame + 1 == self.call_stack.len() {
+            &self.variables[1..]
+        } else {
+            &self.call_stack[frame].scopes
+        }
+    }
+
+    /// Global variables of the file executing
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.variables[0]
+    }
+
+    /// Evaluate an expression in the scope of the statement executing, for
+    /// the conditions and watches of a debugger
+    pub fn evaluate(&mut self, source: &str) -> Result<Value, String> {
+        let expr = Parser::new("<expression>", source)?.parse_expression()?;
+        self.eval_expr(&expr)
+    }
+
+    /// Run a program and return the value produced by its entry point.
+    ///
+    /// Top-level statements always execute in order first, so `let` bindings
+    /// outside functions become globals visible to every function. If the
+    /// program defines `main` or `app` it is called afterwards; otherwise the
+    /// program is a script and the top-level statements are all there is.
+    pub fn run(&mut self, program: Vec<Located>) -> Result<Value, String> {
+        if let Some(val) = self.run_script(&program)? {
+            return Ok(val);
+        }
+
+        // Check for main function as entry point
+        let entry_point = if self.functions.contains_key("main") {
+            "main"
+        } else if self.functions.contains_key("app") {
+            "app"
+        } else {
+            return Ok(Value::Void);
+        };
+        
+        // Execute main or app function as the entry point, handing it the
+        // program arguments if it declares a parameter for them
+        let entry_args = match self.functions.get(entry_point) {
+            Some(function) if function.params.len() == 1 => vec![self.args_value()],
+            _ => vec![],
+        };
+        self.call_function(entry_point, entry_args)
+    }
+
+    /// Register declarations and execute top-level statements in order
+    /// without calling an entry point. Variables stay defined between calls,
+    /// which is what the REPL relies on. Returns the value of a top-level
+    /// `return`, which ends the program.
+    pub fn run_script(&mut self, program: &[Located]) -> Result<Option<Value>, String> {
+        self.register_declarations(program)?;
+
+        for stmt in program {
+            if let Some(val) = self.eval_statement(stmt)? {
+                return Ok(Some(val));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // First pass: register function definitions and handle imports
+    fn register_declarations(&mut self, program: &[Located]) -> Result<(), String> {
+        for stmt in program {
+            match &stmt.statement {
+                Statement::FunctionDef { file_prefix, is_main, is_public, name, params, body, .. } => {
                     let func_name = if *is_main {
                         "main".to_string()
                     } else {
@@ -93,47 +473,171 @@ impl Interpreter {
                             file_prefix: file_prefix.clone(),
                             name: func_name,
                             is_main: *is_main,
+                            is_public: *is_public,
                             params: params.clone(), 
-                            body: body.clone() 
+                            body: body.clone(),
+                            module: None,
                         }
                     );
                 }
-                Statement::Import(module_name) => {
-                    self.import_module(module_name)?;
+                Statement::Struct { name, fields } => self.register_struct(name, fields, None)?,
+                Statement::Enum { name, variants } => self.register_enum(name, variants, None)?,
+                Statement::Import { .. } | Statement::ImportFrom { .. } => {
+                    let mut imports = std::mem::take(&mut self.imports);
+                    let base_path = self.base_path.clone();
+                    let result = self.bind_import(&mut imports, stmt, &base_path);
+                    self.imports = imports;
+                    result?;
                 }
                 _ => {}
             }
         }
 
-        // Check for main function as entry point
-        let has_main = self.functions.contains_key("main");
-        let has_app = self.functions.contains_key("app");
-        
-        if !has_main && !has_app {
-            return Err("No entry point found. Either 'main' function or 'app' function is required.".to_string());
+        // Methods may be declared before their struct
+        for stmt in program {
+            if let Statement::Impl { name, methods } = &stmt.statement {
+                self.register_methods(name, methods, None)?;
+            }
         }
-        
-        // Execute main or app function as the entry point
-        let entry_point = if has_main { "main" } else { "app" };
-        self.call_function(entry_point, vec![])?;
-        
+
+        Ok(())
+    }
+
+    fn register_struct(&mut self, name: &str, fields: &[String], module: Option<PathBuf>) -> Result<(), String> {
+        let key = (module, name.to_string());
+        if self.enums.contains_key(&key) {
+            return Err(format!("'{}' is already defined as an enum", name));
+        }
+        self.structs.insert(key, StructDef {
+            fields: fields.to_vec(),
+            methods: HashMap::new(),
+        });
         Ok(())
     }
 
-    fn import_module(&mut self, module_name: &str) -> Result<(), String> {
-        // Construct the path to the module
-        let mut module_path = self.base_path.clone();
-        module_path.push(format!("{}.kode", module_name));
+    fn register_enum(&mut self, name: &str, variants: &[Variant], module: Option<PathBuf>) -> Result<(), String> {
+        let key = (module, name.to_string());
+        if self.structs.contains_key(&key) {
+            return Err(format!("'{}' is already defined as a struct", name));
+        }
+        self.enums.insert(key, EnumDef {
+            variants: variants.to_vec(),
+        });
+        Ok(())
+    }
+
+    // Methods are declared in the file of their struct
+    fn register_methods(&mut self, name: &str, methods: &[Located], module: Option<PathBuf>) -> Result<(), String> {
+        let def = self.structs
+            .get_mut(&(module.clone(), name.to_string()))
+            .ok_or(format!("Cannot implement methods for unknown struct '{}'", name))?;
+        for method in methods {
+            if let Statement::FunctionDef { file_prefix, name: method_name, params, body, .. } = &method.statement {
+                def.methods.insert(method_name.clone(), Function {
+                    file_prefix: file_prefix.clone(),
+                    name: format!("{}.{}", name, method_name),
+                    is_main: false,
+                    is_public: true,
+                    params: params.clone(),
+                    body: body.clone(),
+                    module: module.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn args_value(&self) -> Value {
+        Value::Array(self.program_args.iter().cloned().map(Value::String).collect())
+    }
+
+    // Load the module named by an import statement and record the names it
+    // makes available in `imports`
+    fn bind_import(&mut self, imports: &mut ImportTable, stmt: &Statement, from_dir: &Path) -> Result<(), String> {
+        match stmt {
+            Statement::Import { module, alias } => {
+                let path = self.import_module(module, from_dir)?;
+                
+                let alias = alias.as_ref().unwrap_or(module);
+                if let Some(existing) = imports.aliases.get(alias)
+                    && *existing != path
+                {
+                    return Err(format!("Cannot import '{}' as '{}': the name is already used by module '{}'", 
+                                     module, alias, self.imported_modules[existing].name));
+                }
+                imports.aliases.insert(alias.clone(), path);
+            }
+            Statement::ImportFrom { module, names } => {
+                let path = self.import_module(module, from_dir)?;
+                
+                for name in names {
+                    let target = &self.imported_modules[&path];
+                    // Structs and enums can't be `pub` and are always exported
+                    let key = (Some(path.clone()), name.clone());
+                    let is_type = self.structs.contains_key(&key) || self.enums.contains_key(&key);
+                    if !target.functions.contains_key(name) && !target.globals.contains_key(name) && !is_type {
+                        return Err(format!("Module '{}' has no function, variable or type '{}'", module, name));
+                    }
+                    if !is_type && !target.is_exported(name) {
+                        return Err(format!("Cannot import '{}' from '{}': it is private to the module", name, module));
+                    }
+                    if let Some((existing, _)) = imports.names.get(name)
+                        && *existing != path
+                    {
+                        return Err(format!("Cannot import '{}' from '{}': already imported from '{}'", 
+                                         name, module, self.imported_modules[existing].name));
+                    }
+                    imports.names.insert(name.clone(), (path.clone(), name.clone()));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Resolve, load and cache a module, returning its canonical path
+    fn import_module(&mut self, module_name: &str, from_dir: &Path) -> Result<PathBuf, String> {
+        let module_path = self.resolver.resolve(module_name, &[from_dir, &self.base_path])?;
         
-        let module_path_str = module_path.to_string_lossy().to_string();
+        if self.imported_modules.contains_key(&module_path) {
+            return Ok(module_path);
+        }
+        
+        if let Some(start) = self.loading_modules.iter().position(|(_, path)| *path == module_path) {
+            let cycle: Vec<&str> = self.loading_modules[start..].iter()
+                .map(|(name, _)| name.as_str())
+                .chain(std::iter::once(module_name))
+                .collect();
+            return Err(format!("Cyclic import: {}", cycle.join(" -> ")));
+        }
+        
+        self.loading_modules.push((module_name.to_string(), module_path.clone()));
+        let result = self.load_module(module_name, &module_path);
+        self.loading_modules.pop();
+        let (module, globals) = result?;
         
-        // Check if the module exists
-        if !module_path.exists() {
-            return Err(format!("Module '{}' not found at path '{}'", module_name, module_path_str));
+        // Run the module's top-level `let` bindings once, in its own context
+        self.imported_modules.insert(module_path.clone(), module);
+        let saved = self.enter_context(Some(module_path.clone()));
+        let mut result = Ok(());
+        for stmt in &globals {
+            if let Err(e) = self.eval_statement(stmt) {
+                result = Err(format!("In module '{}': {}", module_name, e));
+                break;
+            }
         }
+        self.leave_context(saved);
+        result?;
+        
+        Ok(module_path)
+    }
+
+    // Parse a module, returning it along with its top-level `let` statements
+    fn load_module(&mut self, module_name: &str, module_path: &Path) -> Result<(Module, Vec<Located>), String> {
+        let module_path_str = module_path.to_string_lossy().to_string();
         
         // Read and parse the module
-        let source_code = match fs::read_to_string(&module_path) {
+        let source_code = match fs::read_to_string(module_path) {
             Ok(contents) => contents,
             Err(e) => return Err(format!("Error reading module '{}': {}", module_name, e)),
         };
@@ -148,48 +652,292 @@ impl Interpreter {
             Err(e) => return Err(format!("Parse error on module '{}': {}", module_name, e)),
         };
         
-        // Extract function definitions from the module
+        // Extract function definitions and follow the module's own imports,
+        // which resolve relative to the module's directory
+        let module_dir = module_path.parent().unwrap_or_else(|| Path::new("."));
         let mut module_functions = HashMap::new();
+        let mut imports = ImportTable::default();
+        let mut globals = Vec::new();
+        let mut exported = HashSet::new();
+        let mut impls = Vec::new();
         
         for stmt in module_ast {
-            if let Statement::FunctionDef { file_prefix, is_main: _, name, params, body } = stmt {
-                module_functions.insert(
-                    name.clone(), 
-                    Function { 
-                        file_prefix, 
-                        name: name.clone(),
-                        is_main: false, // Imported functions are never main
-                        params, 
-                        body 
+            match stmt.statement {
+                Statement::FunctionDef { file_prefix, is_main: _, is_public, name, params, body, .. } => {
+                    if is_public {
+                        exported.insert(name.clone());
+                    }
+                    module_functions.insert(
+                        name.clone(), 
+                        Function { 
+                            file_prefix, 
+                            name: name.clone(),
+                            is_main: false, // Imported functions are never main
+                            is_public,
+                            params, 
+                            body,
+                            module: Some(module_path.to_path_buf()),
+                        }
+                    );
+                }
+                Statement::Let { ref name, is_public, .. } => {
+                    if is_public {
+                        exported.insert(name.clone());
                     }
-                );
+                    globals.push(stmt);
+                }
+                Statement::Import { .. } | Statement::ImportFrom { .. } => {
+                    self.bind_import(&mut imports, &stmt, module_dir)
+                        .map_err(|e| format!("In module '{}': {}", module_name, e))?;
+                }
+                Statement::Struct { name, fields } => {
+                    self.register_struct(&name, &fields, Some(module_path.to_path_buf()))
+                        .map_err(|e| format!("In module '{}': {}", module_name, e))?;
+                }
+                Statement::Enum { name, variants } => {
+                    self.register_enum(&name, &variants, Some(module_path.to_path_buf()))
+                        .map_err(|e| format!("In module '{}': {}", module_name, e))?;
+                }
+                Statement::Impl { name, methods } => impls.push((name, methods)),
+                _ => {}
             }
         }
         
-        self.imported_modules.insert(module_name.to_string(), module_functions);
-        Ok(())
+        for (name, methods) in impls {
+            self.register_methods(&name, &methods, Some(module_path.to_path_buf()))
+                .map_err(|e| format!("In module '{}': {}", module_name, e))?;
+        }
+        
+        let module = Module {
+            name: module_name.to_string(),
+            functions: module_functions,
+            imports,
+            globals: HashMap::new(),
+            exported: if exported.is_empty() { None } else { Some(exported) },
+        };
+        Ok((module, globals))
+    }
+
+    // Make `module` (None for the main program) the executing context: its
+    // globals become the bottom scope and the caller's local scopes are set
+    // aside. Returns what `leave_context` needs to restore the caller.
+    fn enter_context(&mut self, module: Option<PathBuf>) -> (Option<PathBuf>, Environment) {
+        let caller_scopes = self.variables.split_off(1);
+        if module != self.current_module {
+            let active = self.current_module.clone();
+            self.switch_globals(&active, &module);
+        }
+        let previous_module = std::mem::replace(&mut self.current_module, module);
+        (previous_module, caller_scopes)
+    }
+
+    fn leave_context(&mut self, (previous_module, caller_scopes): (Option<PathBuf>, Environment)) {
+        self.variables.truncate(1);
+        if previous_module != self.current_module {
+            let active = self.current_module.clone();
+            self.switch_globals(&active, &previous_module);
+        }
+        self.current_module = previous_module;
+        self.variables.extend(caller_scopes);
+    }
+
+    // Park the active global scope with its owner and activate the globals of `to`
+    fn switch_globals(&mut self, from: &Option<PathBuf>, to: &Option<PathBuf>) {
+        let active = std::mem::take(&mut self.variables[0]);
+        match from {
+            Some(module) => self.module_mut(module).globals = active,
+            None => self.main_globals = active,
+        }
+        self.variables[0] = match to {
+            Some(module) => std::mem::take(&mut self.module_mut(module).globals),
+            None => std::mem::take(&mut self.main_globals),
+        };
+    }
+
+    fn module_mut(&mut self, module: &Path) -> &mut Module {
+        self.imported_modules.get_mut(module).expect("module is loaded before it executes")
+    }
+
+    // Import table of the file whose code is currently executing
+    fn current_imports(&self) -> &ImportTable {
+        match &self.current_module {
+            Some(module) => &self.imported_modules[module].imports,
+            None => &self.imports,
+        }
     }
 
     fn call_function(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
-        // First, check if it's a local function
-        if let Some(function) = self.functions.get(name).cloned() {
+        match &self.current_module {
+            // Inside an imported module, its own functions take precedence
+            Some(module) => {
+                if let Some(function) = self.imported_modules[module].functions.get(name).cloned() {
+                    return self.execute_function(function, args);
+                }
+            }
+            // First, check if it's a local function
+            None => {
+                if let Some(function) = self.functions.get(name).cloned() {
+                    return self.execute_function(function, args);
+                }
+            }
+        }
+        
+        // Names brought in with `from module import name`
+        if let Some((module, function_name)) = self.current_imports().names.get(name)
+            && let Some(function) = self.imported_modules[module].functions.get(function_name).cloned()
+        {
             return self.execute_function(function, args);
         }
         
-        // Check in imported modules
-        for (module_name, module_functions) in &self.imported_modules {
-            if let Some(function) = module_functions.get(name).cloned() {
-                return self.execute_function(function, args);
+        if let Some(native) = self.natives.get(name) {
+            return native(name, args);
+        }
+
+        if let Some(result) = self.call_builtin(name, &args) {
+            return result;
+        }
+        
+        // Check in imported modules, refusing to guess when several define the name
+        let mut candidates: Vec<&PathBuf> = self.current_imports().aliases.values()
+            .filter(|module| {
+                let module = &self.imported_modules[*module];
+                module.functions.contains_key(name) && module.is_exported(name)
+            })
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        
+        match candidates.as_slice() {
+            [] => Err(format!("Undefined function '{}'", name)),
+            [module] => {
+                let function = self.imported_modules[*module].functions[name].clone();
+                self.execute_function(function, args)
+            }
+            modules => {
+                let mut modules: Vec<String> = modules.iter()
+                    .map(|m| format!("'{}'", self.imported_modules[*m].name))
+                    .collect();
+                modules.sort();
+                Err(format!("Function '{}' is ambiguous: it is defined in modules {}; call it as module.{}()", 
+                          name, modules.join(", "), name))
             }
         }
+    }
+    
+    // Module alias named by the callee of `alias.name(...)` or
+    // `util.strings.name(...)`, unless a variable shadows it
+    fn module_alias(&self, expr: &Expression) -> Option<String> {
+        let alias = dotted_name(expr)?;
+        let root = alias.split('.').next().unwrap_or_default();
         
-        Err(format!("Undefined function '{}'", name))
+        if self.get_variable(root).is_none() && self.current_imports().aliases.contains_key(&alias) {
+            Some(alias)
+        } else {
+            None
+        }
     }
     
+    // Call `alias.name(...)` where `alias` names an imported module
+    fn call_qualified(&mut self, alias: &str, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let module = &self.imported_modules[&self.current_imports().aliases[alias]];
+        match module.functions.get(name).cloned() {
+            Some(_) if !module.is_exported(name) => {
+                Err(format!("Function '{}' is private to module '{}'", name, module.name))
+            }
+            Some(function) => self.execute_function(function, args),
+            None => Err(format!("Module '{}' has no function '{}'", module.name, name)),
+        }
+    }
+    
+    // Built-in functions, shadowed by user definitions of the same name
+    fn call_builtin(&mut self, name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+        let result = match (name, args) {
+            ("args", []) => Ok(self.args_value()),
+            ("args", _) => Err(format!("Function 'args' expects 0 args, got {}", args.len())),
+            ("env", [Value::String(var)]) => Ok(std::env::var(var).map(Value::String).unwrap_or(Value::Void)),
+            ("env", [other]) => Err(format!("Function 'env' expects a string, got {:?}", other)),
+            ("env", _) => Err(format!("Function 'env' expects 1 arg, got {}", args.len())),
+            ("input", [] | [_]) => self.input(args.first()),
+            ("input", _) => Err(format!("Function 'input' expects 0 or 1 args, got {}", args.len())),
+            ("read_line", []) => self.read_line().map(Value::String),
+            ("read_line", _) => Err(format!("Function 'read_line' expects 0 args, got {}", args.len())),
+            ("eprint", [value]) => self.write_stderr(&format!("{}\n", value)).map(|_| Value::Void),
+            ("eprint", _) => Err(format!("Function 'eprint' expects 1 arg, got {}", args.len())),
+            ("len", [Value::Array(elements)]) => Ok(Value::Number(elements.len() as i64)),
+            ("len", [Value::String(s)]) => Ok(Value::Number(s.chars().count() as i64)),
+            ("len", [Value::Map(entries)]) => Ok(Value::Number(entries.len() as i64)),
+            ("len", [Value::Range { start, end }]) => Ok(Value::Number(end.saturating_sub(*start).max(0))),
+            ("len", [other]) => Err(format!("Function 'len' expects an array, string, map or range, got {:?}", other)),
+            ("len", _) => Err(format!("Function 'len' expects 1 arg, got {}", args.len())),
+            ("enumerate", [iterable]) => Ok(Value::Enumerate(Box::new(iterable.clone()))),
+            ("enumerate", _) => Err(format!("Function 'enumerate' expects 1 arg, got {}", args.len())),
+            ("keys", [Value::Map(entries)]) => Ok(Value::Array(entries.keys().map(MapKey::to_value).collect())),
+            ("keys", [other]) => Err(format!("Function 'keys' expects a map, got {:?}", other)),
+            ("keys", _) => Err(format!("Function 'keys' expects 1 arg, got {}", args.len())),
+            ("values", [Value::Map(entries)]) => Ok(Value::Array(entries.values().cloned().collect())),
+            ("values", [other]) => Err(format!("Function 'values' expects a map, got {:?}", other)),
+            ("values", _) => Err(format!("Function 'values' expects 1 arg, got {}", args.len())),
+            ("has", [Value::Map(entries), key]) => {
+                MapKey::from_value(key).map(|key| Value::Bool(entries.contains_key(&key)))
+            }
+            ("has", [other, _]) => Err(format!("Function 'has' expects a map, got {:?}", other)),
+            ("has", _) => Err(format!("Function 'has' expects 2 args, got {}", args.len())),
+            ("remove", [Value::Map(entries), key]) => MapKey::from_value(key).map(|key| {
+                let mut entries = entries.clone();
+                entries.remove(&key);
+                Value::Map(entries)
+            }),
+            ("remove", [other, _]) => Err(format!("Function 'remove' expects a map, got {:?}", other)),
+            ("remove", _) => Err(format!("Function 'remove' expects 2 args, got {}", args.len())),
+            _ => return None,
+        };
+        Some(result)
+    }
+    
+    fn write_stdout(&mut self, text: &str) -> Result<(), String> {
+        self.io.stdout().write_all(text.as_bytes())
+            .map_err(|e| format!("Error writing output: {}", e))
+    }
+
+    fn write_stderr(&mut self, text: &str) -> Result<(), String> {
+        self.io.stderr().write_all(text.as_bytes())
+            .map_err(|e| format!("Error writing output: {}", e))
+    }
+
+    // `input()`: show the prompt, if any, and read a line, which must be there
+    fn input(&mut self, prompt: Option<&Value>) -> Result<Value, String> {
+        if let Some(prompt) = prompt {
+            self.write_stdout(&prompt.to_string())?;
+            self.io.stdout().flush().map_err(|e| format!("Error writing output: {}", e))?;
+        }
+        let mut line = self.read_line()?;
+        if line.is_empty() {
+            return Err("Function 'input' reached the end of input".to_string());
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Value::String(line))
+    }
+
+    // `read_line()`: the next line of input with its line ending, so that
+    // only the end of input reads as ""
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut line = String::new();
+        self.io.stdin().read_line(&mut line)
+            .map_err(|e| format!("Error reading input: {}", e))?;
+        Ok(line)
+    }
+
     fn execute_function(&mut self, function: Function, args: Vec<Value>) -> Result<Value, String> {
-        let previous_function = self.current_function.clone();
-        self.current_function = Some(function.name.clone());
-        
+        self.call_with_locals(function, args).map(|(value, _)| value)
+    }
+    
+    // Run a function, also returning its local variables as they were when it returned
+    fn call_with_locals(&mut self, function: Function, args: Vec<Value>) -> Result<(Value, HashMap<String, Value>), String> {
         if args.len() != function.params.len() {
             return Err(format!(
                 "Function '{}' expects {} args, got {}",
@@ -197,6 +945,14 @@ impl Interpreter {
             ));
         }
 
+        let previous_function = self.current_function.clone();
+        self.current_function = Some(function.name.clone());
+        self.enter_call(&function.name);
+
+        // Functions see the globals of their own file and their own locals,
+        // never the locals of their caller
+        let saved = self.enter_context(function.module.clone());
+
         // New function scope
         let mut scope = HashMap::new();
         for (param, arg) in function.params.iter().zip(args) {
@@ -204,35 +960,61 @@ impl Interpreter {
         }
         self.variables.push(scope);
 
-        let mut return_value = Value::Void;
+        let mut result = Ok(Value::Void);
 
         // Loop through each statement in the function body
         for (i, stmt) in function.body.iter().enumerate() {
             match self.eval_statement(stmt) {
                 Ok(Some(val)) => {
-                    return_value = val;
+                    result = Ok(val);
                     break;
                 }
                 Ok(None) => {}
                 Err(e) => {
                     // Add context to the error
-                    let error_msg = format!("In function '{}', statement #{}: {}", 
-                                         function.name, i + 1, e);
-                    self.variables.pop();
-                    self.current_function = previous_function;
-                    return Err(error_msg);
+                    result = Err(format!("In function '{}', statement #{}: {}", 
+                                         function.name, i + 1, e));
+                    break;
                 }
             }
         }
 
-        self.variables.pop();
+        let locals = self.variables.get_mut(1).map(std::mem::take).unwrap_or_default();
+        self.leave_context(saved);
+        self.call_stack.pop();
         self.current_function = previous_function;
-        Ok(return_value)
+        result.map(|value| (value, locals))
+    }
+
+    // Push a frame on the call stack, keeping the caller's locals for a debugger
+    fn enter_call(&mut self, function: &str) {
+        if self.debugger.is_some() {
+            let caller = self.call_stack.last_mut().expect("top-level frame stays on the call stack");
+            caller.scopes = self.variables[1..].to_vec();
+        }
+        self.call_stack.push(CallFrame {
+            function: function.to_string(),
+            file: self.current_module.clone(),
+            line: 0,
+            scopes: Vec::new(),
+        });
     }
 
-    fn eval_statement(&mut self, stmt: &Statement) -> Result<Option<Value>, String> {
+    fn eval_statement(&mut self, stmt: &Located) -> Result<Option<Value>, String> {
+        if let Some(mut debugger) = self.debugger.take() {
+            let frame = self.call_stack.last_mut().expect("top-level frame stays on the call stack");
+            frame.line = stmt.line;
+            frame.file = self.current_module.clone();
+            let result = debugger.statement(self, stmt);
+            self.debugger = Some(debugger);
+            result?;
+        }
+        self.execute_statement(stmt)
+    }
+
+    fn execute_statement(&mut self, stmt: &Statement) -> Result<Option<Value>, String> {
         match stmt {
-            Statement::Let { name, value } => {
+            Statement::Let { name, value, .. } => {
                 let val = self.eval_expr(value)?;
                 self.set_variable(name, val);
                 Ok(None)
@@ -246,7 +1028,7 @@ impl Interpreter {
 
             Statement::Print(expr) => {
                 let val = self.eval_expr(expr)?;
-                println!("{}", val);
+                self.write_stdout(&format!("{}\n", val))?;
                 Ok(None)
             }
 
@@ -298,13 +1080,43 @@ impl Interpreter {
                 Ok(None)
             }
 
+            Statement::ForIn { names, iterable, body } => {
+                let iterable = self.eval_expr(iterable)?;
+                let mut iter = self.loop_iter(iterable, names.len() == 2)?;
+                
+                while let Some(item) = self.next_item(&mut iter)? {
+                    // A fresh scope per iteration, so closures keep their own values
+                    self.variables.push(HashMap::new());
+                    match (names.as_slice(), item) {
+                        ([name], item) => self.set_variable(name, item),
+                        ([first, second], Value::Array(pair)) if pair.len() == 2 => {
+                            self.set_variable(first, pair[0].clone());
+                            self.set_variable(second, pair[1].clone());
+                        }
+                        (_, other) => {
+                            self.variables.pop();
+                            return Err(format!("Cannot unpack {} into loop variables {}", other, names.join(", ")));
+                        }
+                    }
+                    
+                    for stmt in body {
+                        if let Some(ret) = self.eval_statement(stmt)? {
+                            self.variables.pop();
+                            return Ok(Some(ret));
+                        }
+                    }
+                    self.variables.pop();
+                }
+                Ok(None)
+            }
+
             Statement::For { init, condition, update, body } => {
                 // Create a new scope for the for loop variables
                 self.variables.push(HashMap::new());
                 
                 // Initialize
                 if let Some(init_stmt) = init {
-                    self.eval_statement(init_stmt)?;
+                    self.execute_statement(init_stmt)?;
                 }
                 
                 // Prevent infinite loops with a reasonable limit
@@ -331,7 +1143,7 @@ impl Interpreter {
                     
                     // Update
                     if let Some(update_stmt) = update {
-                        self.eval_statement(update_stmt)?;
+                        self.execute_statement(update_stmt)?;
                     }
                     
                     // If no condition, run body only once
@@ -344,7 +1156,28 @@ impl Interpreter {
                 Ok(None)
             }
 
-            Statement::FunctionDef { .. } => Ok(None), // Already handled in `run`
+            Statement::FunctionDef { .. }
+            | Statement::Struct { .. }
+            | Statement::Impl { .. }
+            | Statement::Enum { .. } => Ok(None), // Already handled in `run`
+            
+            Statement::Match { subject, arms } => {
+                let value = self.eval_expr(subject)?;
+                let arm = self.enter_arm(&value, arms)?;
+                let result = match &arm.body {
+                    ArmBody::Expression(expr) => self.eval_expr(expr).map(|_| None),
+                    ArmBody::Block(statements) => (|| {
+                        for stmt in statements {
+                            if let Some(val) = self.eval_statement(stmt)? {
+                                return Ok(Some(val));
+                            }
+                        }
+                        Ok(None)
+                    })(),
+                };
+                self.variables.pop();
+                result
+            }
 
             Statement::Expression(expr) => {
                 self.eval_expr(expr)?;
@@ -363,9 +1196,12 @@ impl Interpreter {
                 Ok(None)
             }
             
-            Statement::Import(_) => Ok(None), // Already handled in `run`
+            Statement::Import { .. } | Statement::ImportFrom { .. } => Ok(None), // Already handled in `run`
             
             Statement::Try { body, catch } => {
+                // Scopes entered by the failing statement are left open on error
+                let depth = self.variables.len();
+                
                 // Execute try block
                 let try_result = (|| -> Result<Option<Value>, String> {
                     for stmt in body {
@@ -380,6 +1216,8 @@ impl Interpreter {
                 match try_result {
                     Ok(val) => Ok(val),
                     Err(_) => {
+                        self.variables.truncate(depth);
+                        
                         // Execute catch block
                         for stmt in catch {
                             if let Some(val) = self.eval_statement(stmt)? {
@@ -396,12 +1234,13 @@ impl Interpreter {
     fn eval_expr(&mut self, expr: &Expression) -> Result<Value, String> {
         match expr {
             Expression::Number(n) => Ok(Value::Number(*n)),
+            Expression::BigInt(n) => Ok(integer(n.clone())),
             Expression::Float(f) => Ok(Value::Float(*f)),
             Expression::Bool(b) => Ok(Value::Bool(*b)),
             Expression::String(s) => Ok(Value::String(s.clone())),
             
-            Expression::Identifier(name) => {
-                self.get_variable(name).ok_or(format!("Undefined variable '{}'", name))
+            Expression::Identifier(name, scope) => {
+                self.get_variable_at(name, *scope).ok_or(format!("Undefined variable '{}'", name))
             }
             
             Expression::Binary { left, op, right } => {
@@ -410,14 +1249,66 @@ impl Interpreter {
                 self.eval_binary_op(&left_val, op, &right_val)
             }
             
+            Expression::CompoundAssign { target, op, value } => {
+                // Indexes in the target are evaluated once, before the value
+                let (root, path) = self.resolve_place(target)?.ok_or("Invalid assignment target")?;
+                let root_val = self.get_variable(&root).ok_or(format!("Undefined variable '{}'", root))?;
+                let current = path.iter().try_fold(&root_val, element)?.clone();
+                let value = self.eval_expr(value)?;
+                let result = self.eval_binary_op(&current, op, &value)?;
+                self.store_place(&root, &path, result.clone())?;
+                Ok(result)
+            }
+            
+            Expression::Range { start, end } => match (self.eval_expr(start)?, self.eval_expr(end)?) {
+                (Value::Number(start), Value::Number(end)) => Ok(Value::Range { start, end }),
+                (start, end) => Err(format!("Range bounds must be integers, got {}..{}", start, end)),
+            },
+            
+            Expression::Conditional { condition, then_branch, else_branch } => {
+                match self.eval_expr(condition)? {
+                    Value::Bool(true) => self.eval_expr(then_branch),
+                    Value::Bool(false) => self.eval_expr(else_branch),
+                    other => Err(format!("Condition of '?:' must be a boolean, got {:?}", other)),
+                }
+            }
+            
             Expression::Call { callee, arguments } => {
+                // `target[index] = value` and `target.field = value` are parsed
+                // as calls to `__array_assign` and `__field_assign`
+                if let (Expression::Identifier(name, _), [target, key, value]) = (&**callee, arguments.as_slice())
+                    && (name == "__array_assign" || name == "__field_assign")
+                {
+                    let value = self.eval_expr(value)?;
+                    let (root, mut path) = self.resolve_place(target)?
+                        .ok_or("Invalid assignment target")?;
+                    path.push(match (name.as_str(), key) {
+                        ("__field_assign", Expression::String(field)) => PathSegment::Field(field.clone()),
+                        _ => PathSegment::Index(self.eval_expr(key)?),
+                    });
+                    self.store_place(&root, &path, value.clone())?;
+                    return Ok(value);
+                }
+                
                 let args = arguments
                     .iter()
                     .map(|a| self.eval_expr(a))
                     .collect::<Result<Vec<_>, _>>()?;
                 
-                if let Expression::Identifier(name) = &**callee {
-                    self.call_function(name, args)
+                // `module.function(...)`, unless a variable has the module's name
+                if let Expression::FieldAccess { object, field } = &**callee {
+                    if let Some(alias) = self.module_alias(object) {
+                        return self.call_qualified(&alias, field, args);
+                    }
+                    return self.call_method(object, field, args);
+                }
+                
+                if let Expression::Identifier(name, scope) = &**callee {
+                    // A variable holding a closure takes precedence over a function
+                    match self.get_variable_at(name, *scope) {
+                        Some(Value::Closure { params, body, env }) => self.call_closure(params, body, env, args),
+                        _ => self.call_function(name, args),
+                    }
                 } else {
                     // Handle closure calls
                     let callee_val = self.eval_expr(callee)?;
@@ -432,9 +1323,15 @@ impl Interpreter {
             Expression::Unary { op, expr } => {
                 let val = self.eval_expr(expr)?;
                 match (op, &val) {
-                    (UnaryOp::Negate, Value::Number(n)) => Ok(Value::Number(-n)),
+                    (UnaryOp::Negate, Value::Number(n)) => Ok(n
+                        .checked_neg()
+                        .map(Value::Number)
+                        .unwrap_or_else(|| Value::BigInt(-BigInt::from(*n)))),
+                    (UnaryOp::Negate, Value::BigInt(n)) => Ok(integer(-n)),
                     (UnaryOp::Negate, Value::Float(f)) => Ok(Value::Float(-f)),
                     (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (UnaryOp::BitNot, Value::Number(n)) => Ok(Value::Number(!n)),
+                    (UnaryOp::BitNot, Value::BigInt(n)) => Ok(integer(!n)),
                     _ => Err(format!("Unsupported unary operator '{:?}' for value {:?}", op, val)),
                 }
             }
@@ -447,6 +1344,57 @@ impl Interpreter {
                 Ok(Value::Array(values))
             }
             
+            Expression::Map { entries } => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let key = MapKey::from_value(&self.eval_expr(key)?)?;
+                    map.insert(key, self.eval_expr(value)?);
+                }
+                Ok(Value::Map(map))
+            }
+            
+            Expression::StructLiteral { name, fields } => {
+                let key = self.type_key(&self.structs, name, None)
+                    .ok_or_else(|| missing_type(&self.structs, "struct", name))?;
+                let declared = self.structs[&key].fields.clone();
+                if let Some((field, _)) = fields.iter().find(|(field, _)| !declared.contains(field)) {
+                    return Err(format!("Struct '{}' has no field '{}'", name, field));
+                }
+                if let Some(missing) = declared.iter().find(|field| !fields.iter().any(|(f, _)| f == *field)) {
+                    return Err(format!("Missing field '{}' in '{}' literal", missing, name));
+                }
+                
+                // Evaluate in source order, store in declaration order
+                let mut values = Vec::new();
+                for (field, expr) in fields {
+                    values.push((field.clone(), self.eval_expr(expr)?));
+                }
+                values.sort_by_key(|(field, _)| declared.iter().position(|f| f == field));
+                Ok(Value::Struct { name: name.clone(), module: key.0, fields: values })
+            }
+            
+            Expression::Interpolation { parts } => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(s) => text.push_str(s),
+                        InterpolationPart::Expression { expr, .. } => text.push_str(&self.eval_expr(expr)?.to_string()),
+                    }
+                }
+                Ok(Value::String(text))
+            }
+            
+            Expression::Match { subject, arms } => {
+                let value = self.eval_expr(subject)?;
+                let arm = self.enter_arm(&value, arms)?;
+                let result = match &arm.body {
+                    ArmBody::Expression(expr) => self.eval_expr(expr),
+                    ArmBody::Block(_) => Err("Block arms are only allowed in a 'match' statement".to_string()),
+                };
+                self.variables.pop();
+                result
+            }
+            
             Expression::ArrayAccess { array, index } => {
                 let array_val = self.eval_expr(array)?;
                 let index_val = self.eval_expr(index)?;
@@ -480,10 +1428,44 @@ impl Interpreter {
                         
                         Ok(Value::String(chars[i].to_string()))
                     },
+                    // An index promoted to a big integer is past any array or string
+                    (Value::Array(elements), Value::BigInt(i)) => {
+                        Err(format!("Array index out of bounds: {} (array length: {})", i, elements.len()))
+                    },
+                    (Value::String(s), Value::BigInt(i)) => {
+                        Err(format!("String index out of bounds: {} (string length: {})", i, s.chars().count()))
+                    },
+                    (Value::Map(entries), key) => {
+                        let key = MapKey::from_value(&key)?;
+                        entries.get(&key).cloned().ok_or(format!("Key {} not found in map", key))
+                    },
                     (non_array, _) => Err(format!("Cannot index non-array type: {:?}", non_array)),
                 }
             }
             
+            Expression::FieldAccess { object, field } => {
+                if let Some(alias) = self.module_alias(object) {
+                    let module = &self.imported_modules[&self.current_imports().aliases[&alias]];
+                    return match module.globals.get(field) {
+                        Some(_) if !module.is_exported(field) => {
+                            Err(format!("Variable '{}' is private to module '{}'", field, module.name))
+                        }
+                        Some(value) => Ok(value.clone()),
+                        None if module.functions.contains_key(field) => {
+                            Err(format!("Module function '{}.{}' must be called", alias, field))
+                        }
+                        None => Err(format!("Module '{}' has no variable '{}'", module.name, field)),
+                    };
+                }
+                if let Some((name, module)) = self.type_name(object)
+                    && let Some(key) = self.type_key(&self.enums, name, module)
+                {
+                    return self.enum_variant(&key, field, Vec::new(), false);
+                }
+                let object_val = self.eval_expr(object)?;
+                element(&object_val, &PathSegment::Field(field.clone())).cloned()
+            }
+            
             Expression::Closure { params, body } => {
                 // Capture the current environment
                 let env = self.variables.clone();
@@ -498,22 +1480,21 @@ impl Interpreter {
     
     fn call_closure(&mut self, 
                    params: Vec<String>, 
-                   body: Vec<Statement>, 
+                   body: Vec<Located>, 
                    captured_env: Environment,
                    args: Vec<Value>) -> Result<Value, String> {
-        // Save current environment
-        let previous_env = std::mem::replace(&mut self.variables, captured_env);
-        let previous_function = self.current_function.clone();
-        self.current_function = Some("<closure>".to_string());
-        
         if args.len() != params.len() {
-            self.variables = previous_env;
-            self.current_function = previous_function;
             return Err(format!(
                 "Closure expects {} args, got {}",
                 params.len(), args.len()
             ));
         }
+        self.enter_call("<closure>");
+
+        // Save current environment
+        let previous_env = std::mem::replace(&mut self.variables, captured_env);
+        let previous_function = self.current_function.clone();
+        self.current_function = Some("<closure>".to_string());
 
         // Create new scope for arguments
         let mut scope = HashMap::new();
@@ -536,6 +1517,7 @@ impl Interpreter {
                     let error_msg = format!("In closure, statement #{}: {}", i + 1, e);
                     self.variables.pop();
                     self.variables = previous_env;
+                    self.call_stack.pop();
                     self.current_function = previous_function;
                     return Err(error_msg);
                 }
@@ -544,6 +1526,7 @@ impl Interpreter {
 
         self.variables.pop();
         self.variables = previous_env;
+        self.call_stack.pop();
         self.current_function = previous_function;
         
         Ok(return_value)
@@ -552,23 +1535,37 @@ impl Interpreter {
     fn eval_binary_op(&self, left: &Value, op: &BinaryOp, right: &Value) -> Result<Value, String> {
         match (left, right) {
             (Value::Number(l), Value::Number(r)) => match op {
-                BinaryOp::Add => Ok(Value::Number(l + r)),
-                BinaryOp::Subtract => Ok(Value::Number(l - r)),
-                BinaryOp::Multiply => Ok(Value::Number(l * r)),
-                BinaryOp::Divide => {
-                    if *r == 0 {
-                        Err("Division by zero".to_string())
-                    } else {
-                        Ok(Value::Number(l / r))
-                    }
-                },
-                BinaryOp::Modulo => {
-                    if *r == 0 {
-                        Err("Modulo by zero".to_string())
-                    } else {
-                        Ok(Value::Number(l % r))
+                BinaryOp::Add
+                | BinaryOp::Subtract
+                | BinaryOp::Multiply
+                | BinaryOp::Divide
+                | BinaryOp::Modulo
+                | BinaryOp::ShiftLeft => {
+                    let result = match op {
+                        BinaryOp::Add => l.checked_add(*r),
+                        BinaryOp::Subtract => l.checked_sub(*r),
+                        BinaryOp::Multiply => l.checked_mul(*r),
+                        // Truncates toward zero; only `i64::MIN / -1` overflows
+                        BinaryOp::Divide if *r == 0 => return Err("Division by zero".to_string()),
+                        BinaryOp::Divide => l.checked_div(*r),
+                        BinaryOp::Modulo if *r == 0 => return Err("Modulo by zero".to_string()),
+                        BinaryOp::Modulo => l.checked_rem(*r),
+                        _ => {
+                            let amount = shift_amount(&BigInt::from(*r))?;
+                            Some(l << amount).filter(|shifted| shifted >> amount == *l)
+                        }
+                    };
+                    match result {
+                        Some(n) => Ok(Value::Number(n)),
+                        // Too large for 64 bits: redo it as a bigint
+                        None => big_binary_op(&BigInt::from(*l), op, &BigInt::from(*r)),
                     }
                 },
+                BinaryOp::BitAnd => Ok(Value::Number(l & r)),
+                BinaryOp::BitOr => Ok(Value::Number(l | r)),
+                BinaryOp::BitXor => Ok(Value::Number(l ^ r)),
+                // Arithmetic shift: negative numbers stay negative
+                BinaryOp::ShiftRight => Ok(Value::Number(l >> shift_amount(&BigInt::from(*r))?)),
                 BinaryOp::Equal => Ok(Value::Bool(l == r)),
                 BinaryOp::NotEqual => Ok(Value::Bool(l != r)),
                 BinaryOp::LessThan => Ok(Value::Bool(l < r)),
@@ -578,6 +1575,11 @@ impl Interpreter {
                 _ => Err(format!("Unsupported binary operator '{:?}' for numbers", op)),
             },
             
+            // Integers too large for 64 bits
+            (Value::BigInt(l), Value::BigInt(r)) => big_binary_op(l, op, r),
+            (Value::BigInt(l), Value::Number(r)) => big_binary_op(l, op, &BigInt::from(*r)),
+            (Value::Number(l), Value::BigInt(r)) => big_binary_op(&BigInt::from(*l), op, r),
+            
             (Value::Float(l), Value::Float(r)) => match op {
                 BinaryOp::Add => Ok(Value::Float(l + r)),
                 BinaryOp::Subtract => Ok(Value::Float(l - r)),
@@ -589,6 +1591,13 @@ impl Interpreter {
                         Ok(Value::Float(l / r))
                     }
                 },
+                BinaryOp::Modulo => {
+                    if *r == 0.0 {
+                        Err("Modulo by zero".to_string())
+                    } else {
+                        Ok(Value::Float(l % r))
+                    }
+                },
                 BinaryOp::Equal => Ok(Value::Bool(l == r)),
                 BinaryOp::NotEqual => Ok(Value::Bool(l != r)),
                 BinaryOp::LessThan => Ok(Value::Bool(l < r)),
@@ -598,56 +1607,18 @@ impl Interpreter {
                 _ => Err(format!("Unsupported binary operator '{:?}' for floats", op)),
             },
             
-            // Mixed number and float operations
-            (Value::Number(l), Value::Float(r)) => {
-                let left_float = *l as f64;
-                match op {
-                    BinaryOp::Add => Ok(Value::Float(left_float + r)),
-                    BinaryOp::Subtract => Ok(Value::Float(left_float - r)),
-                    BinaryOp::Multiply => Ok(Value::Float(left_float * r)),
-                    BinaryOp::Divide => {
-                        if *r == 0.0 {
-                            Err("Division by zero".to_string())
-                        } else {
-                            Ok(Value::Float(left_float / r))
-                        }
-                    },
-                    BinaryOp::Equal => Ok(Value::Bool((left_float - r).abs() < f64::EPSILON)),
-                    BinaryOp::NotEqual => Ok(Value::Bool((left_float - r).abs() >= f64::EPSILON)),
-                    BinaryOp::LessThan => Ok(Value::Bool(left_float < *r)),
-                    BinaryOp::GreaterThan => Ok(Value::Bool(left_float > *r)),
-                    BinaryOp::LessThanOrEqual => Ok(Value::Bool(left_float <= *r)),
-                    BinaryOp::GreaterThanOrEqual => Ok(Value::Bool(left_float >= *r)),
-                    _ => Err(format!("Unsupported binary operator '{:?}' for mixed number types", op)),
-                }
-            },
-            
-            (Value::Float(l), Value::Number(r)) => {
-                let right_float = *r as f64;
-                match op {
-                    BinaryOp::Add => Ok(Value::Float(l + right_float)),
-                    BinaryOp::Subtract => Ok(Value::Float(l - right_float)),
-                    BinaryOp::Multiply => Ok(Value::Float(l * right_float)),
-                    BinaryOp::Divide => {
-                        if *r == 0 {
-                            Err("Division by zero".to_string())
-                        } else {
-                            Ok(Value::Float(l / right_float))
-                        }
-                    },
-                    BinaryOp::Equal => Ok(Value::Bool((l - right_float).abs() < f64::EPSILON)),
-                    BinaryOp::NotEqual => Ok(Value::Bool((l - right_float).abs() >= f64::EPSILON)),
-                    BinaryOp::LessThan => Ok(Value::Bool(*l < right_float)),
-                    BinaryOp::GreaterThan => Ok(Value::Bool(*l > right_float)),
-                    BinaryOp::LessThanOrEqual => Ok(Value::Bool(*l <= right_float)),
-                    BinaryOp::GreaterThanOrEqual => Ok(Value::Bool(*l >= right_float)),
-                    _ => Err(format!("Unsupported binary operator '{:?}' for mixed number types", op)),
-                }
-            },
+            // Mixed number and float operations promote the number to a float
+            (Value::Number(l), Value::Float(_)) => self.eval_binary_op(&Value::Float(*l as f64), op, right),
+            (Value::Float(_), Value::Number(r)) => self.eval_binary_op(left, op, &Value::Float(*r as f64)),
+            (Value::BigInt(l), Value::Float(_)) => self.eval_binary_op(&Value::Float(big_to_f64(l)), op, right),
+            (Value::Float(_), Value::BigInt(r)) => self.eval_binary_op(left, op, &Value::Float(big_to_f64(r))),
             
             (Value::Bool(l), Value::Bool(r)) => match op {
                 BinaryOp::And => Ok(Value::Bool(*l && *r)),
                 BinaryOp::Or => Ok(Value::Bool(*l || *r)),
+                BinaryOp::BitAnd => Ok(Value::Bool(l & r)),
+                BinaryOp::BitOr => Ok(Value::Bool(l | r)),
+                BinaryOp::BitXor => Ok(Value::Bool(l ^ r)),
                 BinaryOp::Equal => Ok(Value::Bool(l == r)),
                 BinaryOp::NotEqual => Ok(Value::Bool(l != r)),
                 _ => Err(format!("Unsupported operator '{:?}' for booleans", op)),
@@ -670,20 +1641,424 @@ impl Interpreter {
                 _ => Err(format!("Unsupported operator '{:?}' between string and float", op)),
             },
             
+            (Value::String(l), Value::BigInt(r)) => match op {
+                BinaryOp::Add => Ok(Value::String(format!("{}{}", l, r))),
+                _ => Err(format!("Unsupported operator '{:?}' between string and number", op)),
+            },
+            
             (Value::Number(l), Value::String(r)) => match op {
                 BinaryOp::Add => Ok(Value::String(format!("{}{}", l, r))),
                 _ => Err(format!("Unsupported operator '{:?}' between number and string", op)),
             },
             
+            (Value::BigInt(l), Value::String(r)) => match op {
+                BinaryOp::Add => Ok(Value::String(format!("{}{}", l, r))),
+                _ => Err(format!("Unsupported operator '{:?}' between number and string", op)),
+            },
+            
             (Value::Float(l), Value::String(r)) => match op {
                 BinaryOp::Add => Ok(Value::String(format!("{}{}", l, r))),
                 _ => Err(format!("Unsupported operator '{:?}' between float and string", op)),
             },
             
+            // Enum values are equal when they are the same variant of the same
+            // enum with equal values
+            (
+                Value::Enum { name: l_name, module: l_module, variant: l_variant, values: l_values },
+                Value::Enum { name: r_name, module: r_module, variant: r_variant, values: r_values },
+            ) if matches!(op, BinaryOp::Equal | BinaryOp::NotEqual) => {
+                let mut equal = l_name == r_name
+                    && l_module == r_module
+                    && l_variant == r_variant
+                    && l_values.len() == r_values.len();
+                for (l, r) in l_values.iter().zip(r_values) {
+                    if !equal {
+                        break;
+                    }
+                    equal = matches!(self.eval_binary_op(l, &BinaryOp::Equal, r)?, Value::Bool(true));
+                }
+                Ok(Value::Bool(equal == matches!(op, BinaryOp::Equal)))
+            }
+            
             _ => Err(format!("Type mismatch in binary operation: {:?} {:?} {:?}", left, op, right)),
         }
     }
 
+    // `receiver.method(args)`. A method whose first parameter is `self` gets
+    // the receiver as that argument, and changes it makes to `self` are written
+    // back when the receiver is a variable or an element of one.
+    // `Type.method(args)` or `module.Type.method(args)` calls a method that
+    // takes no `self`.
+    fn call_method(&mut self, object: &Expression, method: &str, args: Vec<Value>) -> Result<Value, String> {
+        if let Some((name, module)) = self.type_name(object) {
+            if let Some(key) = self.type_key(&self.enums, name, module.clone()) {
+                return self.enum_variant(&key, method, args, true);
+            }
+            if let Some(key) = self.type_key(&self.structs, name, module) {
+                let function = self.structs[&key].methods
+                    .get(method)
+                    .cloned()
+                    .ok_or(format!("Struct '{}' has no method '{}'", name, method))?;
+                return self.execute_function(function, args);
+            }
+        }
+        
+        let place = self.resolve_place(object)?;
+        let receiver = match &place {
+            Some((root, path)) => {
+                let root_val = self.get_variable(root).ok_or(format!("Undefined variable '{}'", root))?;
+                path.iter().try_fold(&root_val, element)?.clone()
+            }
+            None => self.eval_expr(object)?,
+        };
+        
+        let (type_name, module, fields) = match &receiver {
+            Value::Struct { name, module, fields } => (name, module, fields),
+            other => return Err(format!("Cannot call method '{}' on value {:?}", method, other)),
+        };
+        let key = (module.clone(), type_name.clone());
+        let function = match self.structs.get(&key).and_then(|def| def.methods.get(method)) {
+            Some(function) => function.clone(),
+            None => {
+                // A field holding a closure can be called like a method
+                if let Some((_, Value::Closure { params, body, env })) = fields.iter().find(|(f, _)| f == method) {
+                    return self.call_closure(params.clone(), body.clone(), env.clone(), args);
+                }
+                return Err(format!("Struct '{}' has no method '{}'", type_name, method));
+            }
+        };
+        if function.params.first().map(String::as_str) != Some("self") {
+            return Err(format!(
+                "Method '{}' has no 'self' parameter; call it as '{}.{}(...)'",
+                function.name, type_name, method
+            ));
+        }
+        
+        let mut method_args = vec![receiver];
+        method_args.extend(args);
+        let (result, mut locals) = self.call_with_locals(function, method_args)?;
+        
+        if let (Some((root, path)), Some(updated)) = (place, locals.remove("self"))
+            && self.variables.iter().any(|scope| scope.contains_key(&root))
+        {
+            self.store_place(&root, &path, updated)?;
+        }
+        Ok(result)
+    }
+    
+    // Call `receiver.method()` on a struct that is not stored in a variable,
+    // returning the result and `self` as the method left it
+    fn call_on(&mut self, receiver: Value, method: &str) -> Result<(Value, Value), String> {
+        let (type_name, module) = match &receiver {
+            Value::Struct { name, module, .. } => (name.clone(), module.clone()),
+            other => return Err(format!("Cannot call method '{}' on value {:?}", method, other)),
+        };
+        let function = self.structs
+            .get(&(module, type_name.clone()))
+            .and_then(|def| def.methods.get(method))
+            .cloned()
+            .ok_or(format!("Struct '{}' has no method '{}'", type_name, method))?;
+        if function.params.first().map(String::as_str) != Some("self") {
+            return Err(format!("Method '{}.{}' must take 'self' to be used by 'for'", type_name, method));
+        }
+        
+        let (result, mut locals) = self.call_with_locals(function, vec![receiver.clone()])?;
+        Ok((result, locals.remove("self").unwrap_or(receiver)))
+    }
+    
+    // Start a `for` loop over `iterable`. A map gives its keys, or
+    // `[key, value]` pairs when the loop has two variables. A struct is
+    // iterable through an `iter(self)` method returning something iterable,
+    // or by being an iterator with `has_next(self)` and `next(self)`.
+    fn loop_iter(&mut self, iterable: Value, pairs: bool) -> Result<LoopIter, String> {
+        Ok(match iterable {
+            Value::Array(elements) => LoopIter::Values(elements.into_iter()),
+            Value::String(s) => LoopIter::Values(
+                s.chars().map(|c| Value::String(c.to_string())).collect::<Vec<_>>().into_iter(),
+            ),
+            Value::Map(entries) => LoopIter::Values(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| if pairs { Value::Array(vec![key.to_value(), value]) } else { key.to_value() })
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            ),
+            Value::Range { start, end } => LoopIter::Range { next: start, end },
+            Value::Enumerate(inner) => LoopIter::Enumerate {
+                inner: Box::new(self.loop_iter(*inner, false)?),
+                index: 0,
+            },
+            Value::Struct { ref name, ref module, .. } => {
+                let methods = self.structs.get(&(module.clone(), name.clone())).map(|def| &def.methods);
+                let has = |method: &str| methods.is_some_and(|methods| methods.contains_key(method));
+                if has("iter") {
+                    let (iterable, _) = self.call_on(iterable, "iter")?;
+                    return self.loop_iter(iterable, pairs);
+                }
+                if !has("has_next") || !has("next") {
+                    return Err(format!(
+                        "Struct '{}' is not iterable; give it an 'iter' method, or 'has_next' and 'next' methods",
+                        name
+                    ));
+                }
+                LoopIter::Struct(iterable)
+            }
+            other => return Err(format!("Cannot iterate over {:?}", other)),
+        })
+    }
+    
+    // The next value of a `for` loop, or None when it is done
+    fn next_item(&mut self, iter: &mut LoopIter) -> Result<Option<Value>, String> {
+        match iter {
+            LoopIter::Values(values) => Ok(values.next()),
+            LoopIter::Range { next, end } => {
+                if *next >= *end {
+                    return Ok(None);
+                }
+                *next += 1;
+                Ok(Some(Value::Number(*next - 1)))
+            }
+            LoopIter::Struct(iterator) => {
+                let (has_next, updated) = self.call_on(iterator.clone(), "has_next")?;
+                *iterator = updated;
+                match has_next {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => return Ok(None),
+                    other => return Err(format!("'has_next' must return a boolean, got {:?}", other)),
+                }
+                let (item, updated) = self.call_on(iterator.clone(), "next")?;
+                *iterator = updated;
+                Ok(Some(item))
+            }
+            LoopIter::Enumerate { inner, index } => {
+                let item = self.next_item(inner)?.map(|item| Value::Array(vec![Value::Number(*index), item]));
+                *index += 1;
+                Ok(item)
+            }
+        }
+    }
+    
+    // `Name` or `module.Name` used where a type can appear, unless `Name` is
+    // a variable, with the module it names
+    fn type_name<'e>(&self, expr: &'e Expression) -> Option<(&'e str, Option<PathBuf>)> {
+        match expr {
+            Expression::Identifier(name, _) if self.get_variable(name).is_none() => Some((name, None)),
+            Expression::FieldAccess { object, field } => {
+                let alias = self.module_alias(object)?;
+                Some((field, Some(self.current_imports().aliases[&alias].clone())))
+            }
+            _ => None,
+        }
+    }
+
+    // The struct or enum of `types` that `name` means in the executing file:
+    // with a module given, that module's; otherwise the file's own, one
+    // brought in with `from module import Name`, or the only one so named
+    fn type_key<T>(&self, types: &HashMap<TypeKey, T>, name: &str, module: Option<PathBuf>) -> Option<TypeKey> {
+        if module.is_some() {
+            let key = (module, name.to_string());
+            return types.contains_key(&key).then_some(key);
+        }
+        let own = (self.current_module.clone(), name.to_string());
+        if types.contains_key(&own) {
+            return Some(own);
+        }
+        if let Some((path, imported)) = self.current_imports().names.get(name) {
+            let key = (Some(path.clone()), imported.clone());
+            if types.contains_key(&key) {
+                return Some(key);
+            }
+        }
+        let mut declared = types.keys().filter(|(_, declared)| declared == name);
+        match (declared.next(), declared.next()) {
+            (Some(key), None) => Some(key.clone()),
+            _ => None,
+        }
+    }
+    
+    // Build `Enum.Variant` (`called` false) or `Enum.Variant(values)`
+    fn enum_variant(&self, key: &TypeKey, variant: &str, values: Vec<Value>, called: bool) -> Result<Value, String> {
+        let (module, enum_name) = key;
+        let def = &self.enums[key];
+        let declared = def.variants
+            .iter()
+            .find(|v| v.name == variant)
+            .ok_or(format!("Enum '{}' has no variant '{}'", enum_name, variant))?;
+        
+        match (declared.fields.len(), called) {
+            (0, true) => Err(format!(
+                "Variant '{}.{}' has no values; write it without parentheses", enum_name, variant
+            )),
+            (n, false) if n > 0 => Err(format!(
+                "Variant '{}.{}' expects {} values; call it as '{}.{}(...)'", enum_name, variant, n, enum_name, variant
+            )),
+            (n, _) if n != values.len() => Err(format!(
+                "Variant '{}.{}' expects {} values, got {}", enum_name, variant, n, values.len()
+            )),
+            _ => Ok(Value::Enum {
+                name: enum_name.clone(),
+                module: module.clone(),
+                variant: variant.to_string(),
+                values,
+            }),
+        }
+    }
+    
+    // Find the first arm whose pattern matches `value` and whose guard holds.
+    // Its bindings are left in a new scope that the caller pops after
+    // running the arm.
+    fn enter_arm<'a>(&mut self, value: &Value, arms: &'a [MatchArm]) -> Result<&'a MatchArm, String> {
+        for arm in arms {
+            let mut bindings = HashMap::new();
+            if !self.match_pattern(&arm.pattern, value, &mut bindings)? {
+                continue;
+            }
+            self.variables.push(bindings);
+            
+            let guard = match &arm.guard {
+                Some(guard) => self.eval_expr(guard),
+                None => Ok(Value::Bool(true)),
+            };
+            match guard {
+                Ok(Value::Bool(true)) => return Ok(arm),
+                Ok(Value::Bool(false)) => {}
+                Ok(other) => {
+                    self.variables.pop();
+                    return Err(format!("Match guard must be a boolean, got {:?}", other));
+                }
+                Err(e) => {
+                    self.variables.pop();
+                    return Err(e);
+                }
+            }
+            self.variables.pop();
+        }
+        Err(format!("No match arm matches value {}", value))
+    }
+    
+    fn match_pattern(&self, pattern: &Pattern, value: &Value, bindings: &mut HashMap<String, Value>) -> Result<bool, String> {
+        match (pattern, value) {
+            (Pattern::Wildcard, _) => Ok(true),
+            (Pattern::Binding(name), _) => {
+                bindings.insert(name.clone(), value.clone());
+                Ok(true)
+            }
+            (Pattern::Literal(literal), _) => Ok(literal_matches(literal, value)),
+            
+            (Pattern::Array { elements, rest }, Value::Array(values)) => {
+                let fits = match rest {
+                    Some(_) => values.len() >= elements.len(),
+                    None => values.len() == elements.len(),
+                };
+                if !fits {
+                    return Ok(false);
+                }
+                for (pattern, value) in elements.iter().zip(values) {
+                    if !self.match_pattern(pattern, value, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                if let Some(Some(name)) = rest {
+                    bindings.insert(name.clone(), Value::Array(values[elements.len()..].to_vec()));
+                }
+                Ok(true)
+            }
+            (Pattern::Array { .. }, _) => Ok(false),
+            
+            (Pattern::Struct { name, fields }, _) => {
+                let key = self.type_key(&self.structs, name, None)
+                    .ok_or_else(|| format!("{} in pattern", missing_type(&self.structs, "struct", name)))?;
+                let declared = &self.structs[&key].fields;
+                if let Some((field, _)) = fields.iter().find(|(field, _)| !declared.contains(field)) {
+                    return Err(format!("Struct '{}' has no field '{}'", name, field));
+                }
+                match value {
+                    Value::Struct { name: value_name, module, .. } if *value_name == key.1 && *module == key.0 => {
+                        for (field, pattern) in fields {
+                            let value = element(value, &PathSegment::Field(field.clone()))?;
+                            if !self.match_pattern(pattern, value, bindings)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            
+            (Pattern::Enum { name, variant, fields }, _) => {
+                let key = self.type_key(&self.enums, name, None)
+                    .ok_or_else(|| format!("{} in pattern", missing_type(&self.enums, "enum", name)))?;
+                let declared = self.enums[&key]
+                    .variants
+                    .iter()
+                    .find(|v| v.name == *variant)
+                    .ok_or(format!("Enum '{}' has no variant '{}'", name, variant))?;
+                if declared.fields.len() != fields.len() {
+                    return Err(format!(
+                        "Pattern for '{}.{}' has {} values, but the variant has {}",
+                        name, variant, fields.len(), declared.fields.len()
+                    ));
+                }
+                match value {
+                    Value::Enum { name: value_name, module, variant: value_variant, values }
+                        if *value_name == key.1 && *module == key.0 && value_variant == variant =>
+                    {
+                        for (pattern, value) in fields.iter().zip(values) {
+                            if !self.match_pattern(pattern, value, bindings)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            }
+        }
+    }
+    
+    // Split an expression such as `grid[1].cells[x]` into its variable and
+    // the path to the element, evaluating indexes left to right. Returns None
+    // for expressions that don't denote a variable or an element of one.
+    fn resolve_place(&mut self, expr: &Expression) -> Result<Option<(String, Vec<PathSegment>)>, String> {
+        let mut accesses = Vec::new();
+        let mut root = expr;
+        loop {
+            match root {
+                Expression::ArrayAccess { array, .. } => {
+                    accesses.push(root);
+                    root = array;
+                }
+                Expression::FieldAccess { object, .. } if self.module_alias(object).is_none() => {
+                    accesses.push(root);
+                    root = object;
+                }
+                _ => break,
+            }
+        }
+        
+        let name = match root {
+            Expression::Identifier(name, _) => name.clone(),
+            _ => return Ok(None),
+        };
+        let mut path = Vec::new();
+        for access in accesses.into_iter().rev() {
+            path.push(match access {
+                Expression::ArrayAccess { index, .. } => PathSegment::Index(self.eval_expr(index)?),
+                Expression::FieldAccess { field, .. } => PathSegment::Field(field.clone()),
+                _ => unreachable!(),
+            });
+        }
+        Ok(Some((name, path)))
+    }
+    
+    // Store `value` at `path` inside the variable `name`
+    fn store_place(&mut self, name: &str, path: &[PathSegment], value: Value) -> Result<(), String> {
+        let mut container = self.get_variable(name).ok_or(format!("Undefined variable '{}'", name))?;
+        set_element(&mut container, path, value)?;
+        self.assign_variable(name, container)
+    }
+
     fn set_variable(&mut self, name: &str, val: Value) {
         if let Some(scope) = self.variables.last_mut() {
             scope.insert(name.to_string(), val);
@@ -700,12 +2075,173 @@ impl Interpreter {
         Err(format!("Cannot assign to undefined variable '{}'", name))
     }
 
+    // Look `name` up in the scope the resolver found for it, searching all
+    // scopes when it wasn't resolved (as in the REPL) or isn't there
+    fn get_variable_at(&self, name: &str, scope: Option<usize>) -> Option<Value> {
+        match scope.and_then(|index| self.variables.get(index)?.get(name)) {
+            Some(val) => Some(val.clone()),
+            None => self.get_variable(name),
+        }
+    }
+
     fn get_variable(&self, name: &str) -> Option<Value> {
         for scope in self.variables.iter().rev() {
             if let Some(val) = scope.get(name) {
                 return Some(val.clone());
             }
         }
-        None
+        
+        // Module variables brought in with `from module import name`
+        let (module, var_name) = self.current_imports().names.get(name)?;
+        self.imported_modules[module].globals.get(var_name).cloned()
     }
-}
\ No newline at end of file
+}
+
+// Why `name` names none of `types`: it is undeclared, or declared by
+// several modules and not imported by name
+fn missing_type<T>(types: &HashMap<TypeKey, T>, kind: &str, name: &str) -> String {
+    if types.keys().filter(|(_, declared)| declared == name).count() > 1 {
+        format!("The {} '{}' is declared in several modules; import one with `from module import {}`", kind, name, name)
+    } else {
+        format!("Undefined {} '{}'", kind, name)
+    }
+}
+
+// The field of a struct reached through `segment`. Array and map indexing
+// stays in `eval_expr`, which also handles strings.
+fn element<'v>(container: &'v Value, segment: &PathSegment) -> Result<&'v Value, String> {
+    match (container, segment) {
+        (Value::Struct { name, fields, .. }, PathSegment::Field(field)) => fields
+            .iter()
+            .find(|(f, _)| f == field)
+            .map(|(_, value)| value)
+            .ok_or(format!("Struct '{}' has no field '{}'", name, field)),
+        (Value::Array(elements), PathSegment::Index(Value::Number(i))) => usize::try_from(*i)
+            .ok()
+            .and_then(|i| elements.get(i))
+            .ok_or(format!("Array index out of bounds: {} (array length: {})", i, elements.len())),
+        (Value::Array(elements), PathSegment::Index(Value::BigInt(i))) => {
+            Err(format!("Array index out of bounds: {} (array length: {})", i, elements.len()))
+        }
+        (Value::Map(entries), PathSegment::Index(key)) => {
+            let key = MapKey::from_value(key)?;
+            entries.get(&key).ok_or(format!("Key {} not found in map", key))
+        }
+        (other, PathSegment::Field(field)) => Err(format!("Cannot access field '{}' on value {:?}", field, other)),
+        (other, PathSegment::Index(index)) => Err(format!("Cannot index {:?} with {:?}", other, index)),
+    }
+}
+
+// Replace the element at `path` inside nested arrays, maps and structs.
+// Assigning to a missing map key inserts it; intermediate keys must exist.
+fn set_element(container: &mut Value, path: &[PathSegment], value: Value) -> Result<(), String> {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            *container = value;
+            return Ok(());
+        }
+    };
+    
+    let slot = match (container, segment) {
+        (Value::Array(elements), PathSegment::Index(Value::Number(i))) => {
+            let len = elements.len();
+            usize::try_from(*i)
+                .ok()
+                .and_then(|i| elements.get_mut(i))
+                .ok_or(format!("Array index out of bounds: {} (array length: {})", i, len))?
+        }
+        (Value::Array(elements), PathSegment::Index(Value::BigInt(i))) => {
+            return Err(format!("Array index out of bounds: {} (array length: {})", i, elements.len()));
+        }
+        (Value::Array(_), PathSegment::Index(other)) => {
+            return Err(format!("Array index must be an integer, got {:?}", other));
+        }
+        (Value::Map(entries), PathSegment::Index(key)) => {
+            let key = MapKey::from_value(key)?;
+            if rest.is_empty() {
+                entries.insert(key, value);
+                return Ok(());
+            }
+            entries.get_mut(&key).ok_or(format!("Key {} not found in map", key))?
+        }
+        (Value::Struct { name, fields, .. }, PathSegment::Field(field)) => fields
+            .iter_mut()
+            .find(|(f, _)| f == field)
+            .map(|(_, value)| value)
+            .ok_or(format!("Struct '{}' has no field '{}'", name, field))?,
+        (other, PathSegment::Field(field)) => {
+            return Err(format!("Cannot assign field '{}' on value {:?}", field, other));
+        }
+        (other, PathSegment::Index(_)) => return Err(format!("Cannot assign to an element of {:?}", other)),
+    };
+    set_element(slot, rest, value)
+}
+
+// Literal patterns match values of the same type; integers also match equal floats
+fn literal_matches(literal: &Expression, value: &Value) -> bool {
+    match (literal, value) {
+        (Expression::Number(l), Value::Number(v)) => l == v,
+        (Expression::BigInt(l), Value::BigInt(v)) => l == v,
+        (Expression::Number(l), Value::Float(v)) => *l as f64 == *v,
+        (Expression::Float(l), Value::Float(v)) => l == v,
+        (Expression::Float(l), Value::Number(v)) => *l == *v as f64,
+        (Expression::String(l), Value::String(v)) => l == v,
+        (Expression::Bool(l), Value::Bool(v)) => l == v,
+        _ => false,
+    }
+}
+
+// A Number when `n` fits in 64 bits, so equal integers are always the same variant
+fn integer(n: BigInt) -> Value {
+    match n.to_i64() {
+        Some(n) => Value::Number(n),
+        None => Value::BigInt(n),
+    }
+}
+
+fn big_to_f64(n: &BigInt) -> f64 {
+    n.to_f64().unwrap_or(f64::NAN)
+}
+
+fn shift_amount(amount: &BigInt) -> Result<usize, String> {
+    amount
+        .to_usize()
+        .filter(|amount| *amount < 64)
+        .ok_or_else(|| format!("Shift amount {} is out of range 0..63", amount))
+}
+
+// Integer arithmetic without overflow, for results that may not fit in 64 bits
+fn big_binary_op(l: &BigInt, op: &BinaryOp, r: &BigInt) -> Result<Value, String> {
+    let result = match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Subtract => l - r,
+        BinaryOp::Multiply => l * r,
+        BinaryOp::Divide if r.is_zero() => return Err("Division by zero".to_string()),
+        BinaryOp::Divide => l / r,
+        BinaryOp::Modulo if r.is_zero() => return Err("Modulo by zero".to_string()),
+        BinaryOp::Modulo => l % r,
+        BinaryOp::BitAnd => l & r,
+        BinaryOp::BitOr => l | r,
+        BinaryOp::BitXor => l ^ r,
+        BinaryOp::ShiftLeft => l << shift_amount(r)?,
+        BinaryOp::ShiftRight => l >> shift_amount(r)?,
+        BinaryOp::Equal => return Ok(Value::Bool(l == r)),
+        BinaryOp::NotEqual => return Ok(Value::Bool(l != r)),
+        BinaryOp::LessThan => return Ok(Value::Bool(l < r)),
+        BinaryOp::GreaterThan => return Ok(Value::Bool(l > r)),
+        BinaryOp::LessThanOrEqual => return Ok(Value::Bool(l <= r)),
+        BinaryOp::GreaterThanOrEqual => return Ok(Value::Bool(l >= r)),
+        _ => return Err(format!("Unsupported binary operator '{:?}' for numbers", op)),
+    };
+    Ok(integer(result))
+}
+
+// `a.b.c` written as nested field accesses on an identifier
+fn dotted_name(expr: &Expression) -> Option<String> {
+    match expr {
+        Expression::Identifier(name, _) => Some(name.clone()),
+        Expression::FieldAccess { object, field } => Some(format!("{}.{}", dotted_name(object)?, field)),
+        _ => None,
+    }
+}
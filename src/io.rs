@@ -0,0 +1,143 @@
+use std::cell::{RefCell, RefMut};
+use std::io::{self, BufRead, Cursor, Read, Stderr, Stdout, Write};
+use std::rc::Rc;
+
+/// Where a program's `print`, `eprint`, `input()` and `read_line()` go,
+/// set with `Interpreter::set_io`
+pub trait Io {
+    fn stdout(&mut self) -> &mut dyn Write;
+    fn stderr(&mut self) -> &mut dyn Write;
+
+    /// Input is borrowed per read, so that the process's stdin stays free
+    /// for the host between reads
+    fn stdin(&mut self) -> Box<dyn BufRead + '_>;
+}
+
+/// The process's stdout, stderr and stdin, used unless a host sets another `Io`
+pub struct StdIo {
+    stdout: Stdout,
+    stderr: Stderr,
+}
+
+impl StdIo {
+    pub fn new() -> Self {
+        StdIo {
+            stdout: io::stdout(),
+            stderr: io::stderr(),
+        }
+    }
+}
+
+impl Default for StdIo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Io for StdIo {
+    fn stdout(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn stderr(&mut self) -> &mut dyn Write {
+        &mut self.stderr
+    }
+
+    fn stdin(&mut self) -> Box<dyn BufRead + '_> {
+        Box::new(io::stdin().lock())
+    }
+}
+
+/// Input given up front and output kept in memory, for tests and hosts that
+/// capture what a program prints. Clones share the same buffers, so a clone
+/// kept before `set_io` reads what the program wrote.
+///
+/// ```
+/// use kode_compiler::{Interpreter, MemoryIo};
+///
+/// let io = MemoryIo::new("Ada\n");
+/// let mut kode = Interpreter::new();
+/// kode.set_io(io.clone());
+/// kode.eval(r#"let name = input("Name: "); print "Hello, ${name}";"#)?;
+/// assert_eq!(io.stdout_text(), "Name: Hello, Ada\n");
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct MemoryIo {
+    stdin: Rc<RefCell<Cursor<Vec<u8>>>>,
+    stdout: Buffer,
+    stderr: Buffer,
+}
+
+impl MemoryIo {
+    pub fn new(input: &str) -> Self {
+        MemoryIo {
+            stdin: Rc::new(RefCell::new(Cursor::new(input.as_bytes().to_vec()))),
+            ..Self::default()
+        }
+    }
+
+    /// Everything written to stdout so far
+    pub fn stdout_text(&self) -> String {
+        self.stdout.text()
+    }
+
+    /// Everything written to stderr so far
+    pub fn stderr_text(&self) -> String {
+        self.stderr.text()
+    }
+}
+
+impl Io for MemoryIo {
+    fn stdout(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn stderr(&mut self) -> &mut dyn Write {
+        &mut self.stderr
+    }
+
+    fn stdin(&mut self) -> Box<dyn BufRead + '_> {
+        Box::new(Input(self.stdin.borrow_mut()))
+    }
+}
+
+// Output shared between the clones of a `MemoryIo`
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Input shared between the clones of a `MemoryIo`, borrowed for one read
+struct Input<'a>(RefMut<'a, Cursor<Vec<u8>>>);
+
+impl Read for Input<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl BufRead for Input<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.0.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.0.consume(amount)
+    }
+}
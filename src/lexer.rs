@@ -1,11 +1,17 @@
 use std::str::FromStr;
 
+use num_bigint::BigInt;
+
+use crate::ast::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Number(i64),
+    BigInt(BigInt), // Integer literal too large for 64 bits
     Float(f64),
     Bool(bool),
     String(String),
+    InterpolatedString(Vec<StringPart>), // String literal containing `${...}`
     Identifier(String),
 
     // Keywords
@@ -16,9 +22,17 @@ pub enum Token {
     Else,
     While,
     For,
+    In,     // for x in iterable
     Print,
     Main,   // Added for the 'main' keyword
     Import, // Added for import system
+    As,     // import module as alias
+    From,   // from module import name
+    Pub,    // Exported module declaration
+    Struct, // struct type declaration
+    Impl,   // Methods of a struct type
+    Enum,   // enum type declaration
+    Match,  // Pattern matching
     Try,    // Added for error handling
     Catch,  // Added for error handling
 
@@ -28,6 +42,15 @@ pub enum Token {
     Star,
     Slash,
     Percent, // Modulo operator
+    PlusEqual,    // +=
+    MinusEqual,   // -=
+    StarEqual,    // *=
+    SlashEqual,   // /=
+    PercentEqual, // %=
+    PlusPlus,     // ++
+    MinusMinus,   // --
+    Question,     // ? of the conditional operator
+    Arrow,        // -> before a return type
     Equal,       // '='
     EqualEqual,  // '=='
     NotEqual,
@@ -38,6 +61,12 @@ pub enum Token {
     And,         // &&
     Or,          // ||
     Not,         // !
+    BitAnd,      // &
+    BitOr,       // |
+    BitXor,      // ^
+    BitNot,      // ~
+    ShiftLeft,   // <<
+    ShiftRight,  // >>
     
     // Symbols
     LParen,      // (
@@ -48,16 +77,86 @@ pub enum Token {
     RBracket,    // ]
     Comma,
     Semicolon,
+    Colon,       // :
     Dot,         // .
+    DotDot,      // .. rest of an array pattern
+    FatArrow,    // => between a match pattern and its arm
 
     EOF,
 }
 
+/// A piece of an interpolated string literal
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Code { tokens: Vec<Token>, spans: Vec<Span>, span: Span }, // Tokens of one `${...}`
+}
+
+/// A comment, which the lexer keeps out of the token stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String, // Including the `//` or `/* */`
+    pub span: Span,
+    pub trailing: bool, // Follows code on the same line
+}
+
+/// Words that are read as keywords rather than identifiers
+pub const KEYWORDS: &[&str] = &[
+    "let", "fn", "return", "if", "else", "while", "for", "in", "print", "true", "false", "main",
+    "import", "as", "from", "pub", "struct", "impl", "enum", "match", "try", "catch",
+];
+
+/// Whether a `{` after `token` starts an expression, such as a map literal,
+/// rather than a block
+pub fn takes_expression(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Equal
+            | Token::PlusEqual
+            | Token::MinusEqual
+            | Token::StarEqual
+            | Token::SlashEqual
+            | Token::PercentEqual
+            | Token::Plus
+            | Token::Minus
+            | Token::Star
+            | Token::Slash
+            | Token::Percent
+            | Token::EqualEqual
+            | Token::NotEqual
+            | Token::LessThan
+            | Token::GreaterThan
+            | Token::LessThanOrEqual
+            | Token::GreaterThanOrEqual
+            | Token::And
+            | Token::Or
+            | Token::Not
+            | Token::BitAnd
+            | Token::BitOr
+            | Token::BitXor
+            | Token::ShiftLeft
+            | Token::ShiftRight
+            | Token::Question
+            | Token::Colon
+            | Token::Comma
+            | Token::LParen
+            | Token::LBracket
+            | Token::Arrow
+            | Token::FatArrow
+            | Token::Return
+            | Token::Print
+            | Token::In
+    )
+}
+
 pub struct Lexer<'a> {
     input: &'a str,
     pos: usize,
     line: usize,
     column: usize,
+    offset: usize, // Byte offset of `input` in the file, for code inside `${...}`
+    spans: Vec<Span>, // Where each token is
+    comments: Vec<Comment>,
 }
 
 impl<'a> Lexer<'a> {
@@ -67,6 +166,9 @@ impl<'a> Lexer<'a> {
             pos: 0,
             line: 1,
             column: 1,
+            offset: 0,
+            spans: Vec::new(),
+            comments: Vec::new(),
         }
     }
 
@@ -74,10 +176,22 @@ impl<'a> Lexer<'a> {
         (self.line, self.column)
     }
 
+    /// Where each token returned by `tokenize` is in the source
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Comments found by `tokenize`, in order
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
     pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
         let mut tokens = Vec::new();
 
         while let Some(ch) = self.peek_char() {
+            let (line, column, start) = (self.line, self.column, self.pos);
+            let count = tokens.len();
             match ch {
                 c if c.is_whitespace() => {
                     if c == '\n' {
@@ -97,22 +211,26 @@ impl<'a> Lexer<'a> {
                         }
                         self.consume_char();
                     }
+                    self.add_comment(start, line, column);
                 }
 
                 '/' if self.peek_char_at(1) == Some('*') => {
                     // Block comment
                     self.consume_char(); // consume /
                     self.consume_char(); // consume *
+                    self.column += 2;
                     
                     let mut nesting = 1;
                     while nesting > 0 {
                         if self.peek_char() == Some('*') && self.peek_char_at(1) == Some('/') {
                             self.consume_char(); // consume *
                             self.consume_char(); // consume /
+                            self.column += 2;
                             nesting -= 1;
                         } else if self.peek_char() == Some('/') && self.peek_char_at(1) == Some('*') {
                             self.consume_char(); // consume /
                             self.consume_char(); // consume *
+                            self.column += 2;
                             nesting += 1;
                         } else if self.peek_char() == None {
                             return Err("Unterminated block comment".into());
@@ -126,20 +244,46 @@ impl<'a> Lexer<'a> {
                             self.consume_char();
                         }
                     }
+                    self.add_comment(start, line, column);
                 }
 
+                '+' if self.peek_char_at(1) == Some('+') => { tokens.push(Token::PlusPlus); self.consume_char(); self.consume_char(); self.column += 2; }
+                '+' if self.peek_char_at(1) == Some('=') => { tokens.push(Token::PlusEqual); self.consume_char(); self.consume_char(); self.column += 2; }
+                '-' if self.peek_char_at(1) == Some('>') => { tokens.push(Token::Arrow); self.consume_char(); self.consume_char(); self.column += 2; }
+                '-' if self.peek_char_at(1) == Some('-') => { tokens.push(Token::MinusMinus); self.consume_char(); self.consume_char(); self.column += 2; }
+                '-' if self.peek_char_at(1) == Some('=') => { tokens.push(Token::MinusEqual); self.consume_char(); self.consume_char(); self.column += 2; }
+                '*' if self.peek_char_at(1) == Some('=') => { tokens.push(Token::StarEqual); self.consume_char(); self.consume_char(); self.column += 2; }
+                '/' if self.peek_char_at(1) == Some('=') => { tokens.push(Token::SlashEqual); self.consume_char(); self.consume_char(); self.column += 2; }
+                '%' if self.peek_char_at(1) == Some('=') => { tokens.push(Token::PercentEqual); self.consume_char(); self.consume_char(); self.column += 2; }
                 '+' => { tokens.push(Token::Plus); self.consume_char(); self.column += 1; }
                 '-' => { tokens.push(Token::Minus); self.consume_char(); self.column += 1; }
                 '*' => { tokens.push(Token::Star); self.consume_char(); self.column += 1; }
                 '/' => { tokens.push(Token::Slash); self.consume_char(); self.column += 1; }
                 '%' => { tokens.push(Token::Percent); self.consume_char(); self.column += 1; }
-                '.' => { tokens.push(Token::Dot); self.consume_char(); self.column += 1; }
+                '^' => { tokens.push(Token::BitXor); self.consume_char(); self.column += 1; }
+                '~' => { tokens.push(Token::BitNot); self.consume_char(); self.column += 1; }
+                '?' => { tokens.push(Token::Question); self.consume_char(); self.column += 1; }
+                '.' => {
+                    if self.peek_char_at(1) == Some('.') {
+                        tokens.push(Token::DotDot);
+                        self.consume_char(); self.consume_char();
+                        self.column += 2;
+                    } else {
+                        tokens.push(Token::Dot);
+                        self.consume_char();
+                        self.column += 1;
+                    }
+                }
 
                 '=' => {
                     if self.peek_char_at(1) == Some('=') {
                         tokens.push(Token::EqualEqual);
                         self.consume_char(); self.consume_char();
                         self.column += 2;
+                    } else if self.peek_char_at(1) == Some('>') {
+                        tokens.push(Token::FatArrow);
+                        self.consume_char(); self.consume_char();
+                        self.column += 2;
                     } else {
                         tokens.push(Token::Equal);
                         self.consume_char();
@@ -164,6 +308,10 @@ impl<'a> Lexer<'a> {
                         tokens.push(Token::LessThanOrEqual);
                         self.consume_char(); self.consume_char();
                         self.column += 2;
+                    } else if self.peek_char_at(1) == Some('<') {
+                        tokens.push(Token::ShiftLeft);
+                        self.consume_char(); self.consume_char();
+                        self.column += 2;
                     } else {
                         tokens.push(Token::LessThan);
                         self.consume_char();
@@ -176,6 +324,10 @@ impl<'a> Lexer<'a> {
                         tokens.push(Token::GreaterThanOrEqual);
                         self.consume_char(); self.consume_char();
                         self.column += 2;
+                    } else if self.peek_char_at(1) == Some('>') {
+                        tokens.push(Token::ShiftRight);
+                        self.consume_char(); self.consume_char();
+                        self.column += 2;
                     } else {
                         tokens.push(Token::GreaterThan);
                         self.consume_char();
@@ -189,7 +341,9 @@ impl<'a> Lexer<'a> {
                         self.consume_char(); self.consume_char();
                         self.column += 2;
                     } else {
-                        return Err(format!("Unexpected character '&' at line {}, column {}", self.line, self.column));
+                        tokens.push(Token::BitAnd);
+                        self.consume_char();
+                        self.column += 1;
                     }
                 }
 
@@ -199,7 +353,9 @@ impl<'a> Lexer<'a> {
                         self.consume_char(); self.consume_char();
                         self.column += 2;
                     } else {
-                        return Err(format!("Unexpected character '|' at line {}, column {}", self.line, self.column));
+                        tokens.push(Token::BitOr);
+                        self.consume_char();
+                        self.column += 1;
                     }
                 }
 
@@ -211,6 +367,7 @@ impl<'a> Lexer<'a> {
                 ']' => { tokens.push(Token::RBracket); self.consume_char(); self.column += 1; }
                 ',' => { tokens.push(Token::Comma); self.consume_char(); self.column += 1; }
                 ';' => { tokens.push(Token::Semicolon); self.consume_char(); self.column += 1; }
+                ':' => { tokens.push(Token::Colon); self.consume_char(); self.column += 1; }
 
                 '"' => {
                     let token = self.read_string()?;
@@ -218,7 +375,7 @@ impl<'a> Lexer<'a> {
                 }
 
                 c if c.is_ascii_digit() => {
-                    let token = self.read_number();
+                    let token = self.read_number()?;
                     tokens.push(token);
                 }
 
@@ -232,50 +389,106 @@ impl<'a> Lexer<'a> {
                                      ch, self.line, self.column));
                 }
             }
+            if tokens.len() > count {
+                self.spans.push(Span { line, column, start: self.offset + start, end: self.offset + self.pos });
+            }
         }
 
         tokens.push(Token::EOF);
+        let end = self.offset + self.pos;
+        self.spans.push(Span { line: self.line, column: self.column, start: end, end });
         Ok(tokens)
     }
 
-    fn read_number(&mut self) -> Token {
-        let start = self.pos;
-        let start_col = self.column;
+    // Record the comment starting at byte `start` of the input
+    fn add_comment(&mut self, start: usize, line: usize, column: usize) {
+        let trailing = self.spans.last().is_some_and(|span| span.line == line);
+        self.comments.push(Comment {
+            text: self.input[start..self.pos].to_string(),
+            span: Span { line, column, start: self.offset + start, end: self.offset + self.pos },
+            trailing,
+        });
+    }
+
+    // Decimal integers and floats with optional `_` separators and exponent,
+    // or integers with a `0x`, `0o` or `0b` prefix
+    fn read_number(&mut self) -> Result<Token, String> {
+        let (line, column) = (self.line, self.column);
+        
+        let radix = match (self.peek_char(), self.peek_char_at(1)) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('o' | 'O')) => 8,
+            (Some('0'), Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            let prefix = &self.input[self.pos..self.pos + 2];
+            self.consume_char(); self.consume_char();
+            self.column += 2;
+            
+            let digits = self.read_digits(|c| c.is_digit(radix))?;
+            if digits.is_empty() {
+                return Err(format!("Expected digits after '{}' at line {}, column {}", prefix, line, column));
+            }
+            return Ok(integer_token(&digits, radix));
+        }
+        
+        let mut number_str = self.read_digits(|c| c.is_ascii_digit())?;
         let mut is_float = false;
         
-        // Read integer part
-        while let Some(c) = self.peek_char() {
-            if c.is_ascii_digit() {
-                self.consume_char();
-                self.column += 1;
-            } else if c == '.' && !is_float {
-                // Check if followed by a digit to confirm it's a float
-                if let Some(next_c) = self.peek_char_at(1) {
-                    if next_c.is_ascii_digit() {
-                        is_float = true;
-                        self.consume_char(); // consume dot
-                        self.column += 1;
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
+        // Fraction, only if a digit follows the dot
+        if self.peek_char() == Some('.') && self.peek_char_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.consume_char();
+            self.column += 1;
+            number_str.push('.');
+            number_str.push_str(&self.read_digits(|c| c.is_ascii_digit())?);
+            is_float = true;
+        }
+        
+        // Exponent such as `e10`, `E-3` or `e+2`
+        if matches!(self.peek_char(), Some('e' | 'E')) {
+            let sign_len = if matches!(self.peek_char_at(1), Some('+' | '-')) { 1 } else { 0 };
+            if self.peek_char_at(1 + sign_len).is_some_and(|c| c.is_ascii_digit()) {
+                for _ in 0..=sign_len {
+                    number_str.push(self.peek_char().unwrap_or('e'));
+                    self.consume_char();
+                    self.column += 1;
                 }
-            } else {
-                break;
+                number_str.push_str(&self.read_digits(|c| c.is_ascii_digit())?);
+                is_float = true;
             }
         }
         
-        let number_str = &self.input[start..self.pos];
-        
         if is_float {
-            let float_val = f64::from_str(number_str).unwrap_or(0.0);
-            Token::Float(float_val)
+            f64::from_str(&number_str)
+                .map(Token::Float)
+                .map_err(|e| format!("Invalid number {} at line {}, column {}: {}", number_str, line, column, e))
         } else {
-            let int_val = i64::from_str(number_str).unwrap_or(0);
-            Token::Number(int_val)
+            Ok(integer_token(&number_str, 10))
         }
     }
+    
+    // Digits accepted by `is_digit`, skipping `_` separators, which must each
+    // sit between two digits
+    fn read_digits(&mut self, is_digit: impl Fn(char) -> bool) -> Result<String, String> {
+        let mut digits = String::new();
+        while let Some(c) = self.peek_char() {
+            if is_digit(c) {
+                digits.push(c);
+            } else if c == '_' {
+                let after_digit = self.input[..self.pos].ends_with(|c: char| is_digit(c));
+                if !after_digit || !self.peek_char_at(1).is_some_and(&is_digit) {
+                    return Err(format!("Digit separator '_' must be between digits at line {}, column {}",
+                                       self.line, self.column));
+                }
+            } else {
+                break;
+            }
+            self.consume_char();
+            self.column += 1;
+        }
+        Ok(digits)
+    }
 
     fn read_string(&mut self) -> Result<Token, String> {
         self.consume_char(); // consume opening "
@@ -283,6 +496,7 @@ impl<'a> Lexer<'a> {
 
         let start = self.pos;
         let mut string_content = String::new();
+        let mut parts = Vec::new();
         let mut is_escaped = false;
 
         while let Some(c) = self.peek_char() {
@@ -297,6 +511,7 @@ impl<'a> Lexer<'a> {
                     't' => '\t',
                     '\\' => '\\',
                     '"' => '"',
+                    '$' => '$',
                     _ => return Err(format!("Invalid escape sequence '\\{}' at line {}, column {}", 
                                           c, self.line, self.column)),
                 };
@@ -306,7 +521,20 @@ impl<'a> Lexer<'a> {
                 is_escaped = true;
             } else if c == '"' {
                 // End of string
-                return Ok(Token::String(string_content));
+                if parts.is_empty() {
+                    return Ok(Token::String(string_content));
+                }
+                if !string_content.is_empty() {
+                    parts.push(StringPart::Text(string_content));
+                }
+                return Ok(Token::InterpolatedString(parts));
+            } else if c == '$' && self.peek_char() == Some('{') {
+                self.consume_char(); // consume {
+                self.column += 1;
+                if !string_content.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut string_content)));
+                }
+                parts.push(self.read_interpolation()?);
             } else {
                 if c == '\n' {
                     self.line += 1;
@@ -319,6 +547,74 @@ impl<'a> Lexer<'a> {
         Err(format!("Unterminated string literal starting at line {}", self.line))
     }
 
+    // The code of a `${...}` up to its matching `}`, lexed on its own but
+    // keeping its position in the file
+    fn read_interpolation(&mut self) -> Result<StringPart, String> {
+        let (start, line, column) = (self.pos, self.line, self.column);
+        let mut depth = 0;
+        let mut in_string = false; // Inside a string literal within the code
+        let mut is_escaped = false;
+
+        loop {
+            let c = self.peek_char().ok_or(format!(
+                "Unterminated interpolation starting at line {}, column {}", line, column
+            ))?;
+
+            if in_string {
+                if is_escaped {
+                    is_escaped = false;
+                } else if c == '\\' {
+                    is_escaped = true;
+                } else if c == '"' {
+                    in_string = false;
+                }
+            } else {
+                match c {
+                    '"' => in_string = true,
+                    '{' => depth += 1,
+                    '}' if depth == 0 => break,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+            }
+
+            self.consume_char();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        let end = self.pos;
+        self.consume_char(); // consume }
+        self.column += 1;
+
+        let code = &self.input[start..end];
+        if code.trim().is_empty() {
+            return Err(format!("Empty interpolation at line {}, column {}", line, column));
+        }
+
+        let mut lexer = Lexer {
+            input: code,
+            pos: 0,
+            line,
+            column,
+            offset: self.offset + start,
+            spans: Vec::new(),
+            comments: Vec::new(),
+        };
+        let tokens = lexer.tokenize()?;
+        let span = Span {
+            line,
+            column,
+            start: self.offset + start,
+            end: self.offset + end,
+        };
+        Ok(StringPart::Code { tokens, spans: lexer.spans, span })
+    }
+
     fn read_identifier_or_keyword(&mut self) -> Token {
         let start = self.pos;
         let start_col = self.column;
@@ -342,11 +638,19 @@ impl<'a> Lexer<'a> {
             "else" => Token::Else,
             "while" => Token::While,
             "for" => Token::For,
+            "in" => Token::In,
             "print" => Token::Print,
             "true" => Token::Bool(true),
             "false" => Token::Bool(false),
             "main" => Token::Main,
             "import" => Token::Import,
+            "as" => Token::As,
+            "from" => Token::From,
+            "pub" => Token::Pub,
+            "struct" => Token::Struct,
+            "impl" => Token::Impl,
+            "enum" => Token::Enum,
+            "match" => Token::Match,
             "try" => Token::Try,
             "catch" => Token::Catch,
             _ => Token::Identifier(ident.to_string()),
@@ -366,4 +670,14 @@ impl<'a> Lexer<'a> {
             self.pos += c.len_utf8();
         }
     }
-}
\ No newline at end of file
+}
+
+// An integer literal of valid `digits`, as a bigint only when it needs one
+fn integer_token(digits: &str, radix: u32) -> Token {
+    match i64::from_str_radix(digits, radix) {
+        Ok(n) => Token::Number(n),
+        Err(_) => Token::BigInt(
+            BigInt::parse_bytes(digits.as_bytes(), radix).expect("digits are checked while reading"),
+        ),
+    }
+}
@@ -0,0 +1,40 @@
+//! The Kode language as a library, for running Kode code inside Rust programs.
+//!
+//! `Interpreter`, `Parser`, `Value`, the `Io` implementations and the
+//! conversion traits re-exported here are the stable API; the modules hold the
+//! rest of the implementation.
+//!
+//! ```
+//! use kode_compiler::{Interpreter, Value};
+//!
+//! let mut kode = Interpreter::new();
+//! kode.register_fn("greet", |name: String| format!("Hello, {}", name));
+//! kode.set_global("excitement", 3);
+//! kode.eval(r#"
+//!     fn shout(name) {
+//!         let greeting = greet(name);
+//!         for i in 0..excitement {
+//!             greeting = greeting + "!";
+//!         }
+//!         return greeting;
+//!     }
+//! "#)?;
+//!
+//! let greeting: String = kode.call("shout", ("Kode",))?;
+//! assert_eq!(greeting, "Hello, Kode!!!");
+//! assert!(matches!(kode.eval("1 + 2;")?, Value::Number(3)));
+//! # Ok::<(), String>(())
+//! ```
+
+pub mod ast;
+pub mod host;
+pub mod interpreter;
+pub mod io;
+pub mod lexer;
+pub mod modules;
+pub mod parser;
+
+pub use host::{FromValue, IntoArgs, IntoResult, IntoValue, NativeFunction};
+pub use interpreter::{Interpreter, MapKey, Value};
+pub use io::{Io, MemoryIo, StdIo};
+pub use parser::Parser;
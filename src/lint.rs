@@ -0,0 +1,635 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::ast::{
+    ArmBody, BinaryOp, Expression, InterpolationPart, Located, MatchArm, Statement, UnaryOp,
+};
+use crate::lexer::Comment;
+use crate::types::{always_returns, pattern_bindings};
+
+/// Names of the lint rules
+pub const RULES: &[&str] = &[
+    "unused-variable",    // Variables that are never read
+    "unused-parameter",   // Function and closure parameters that are never read
+    "unreachable-code",   // Statements after a `return`
+    "constant-condition", // Conditions of `if`, `while`, `for` and `?:` that never change
+    "empty-catch",        // Empty `catch` blocks, which silently ignore errors
+    "self-assignment",    // Variables, fields and elements assigned to themselves
+    "bool-comparison",    // Comparisons with `true` or `false`
+];
+
+/// How problems found by a rule are reported, set per project in the
+/// `[lint]` table of kode.toml
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// A problem found by the linter
+#[derive(Debug, Clone)]
+pub struct Lint {
+    pub rule: &'static str,
+    pub line: usize,
+    pub message: String,
+}
+
+/// The level of every rule: `warn` unless `config` says otherwise
+pub fn levels(config: &BTreeMap<String, Level>) -> Result<HashMap<&'static str, Level>, String> {
+    if let Some(name) = config.keys().find(|name| !RULES.contains(&name.as_str())) {
+        return Err(format!("Unknown lint rule '{}'", name));
+    }
+    Ok(RULES
+        .iter()
+        .map(|&rule| (rule, config.get(rule).copied().unwrap_or(Level::Warn)))
+        .collect())
+}
+
+/// Lint a parsed program, in source order. Problems in a statement are left
+/// out when the statement or one containing it is marked with a
+/// `// kode:allow(rule, ...)` comment, either at the end of its first line or
+/// on a line of its own before it.
+pub fn lint(program: &[Located], comments: &[Comment]) -> Vec<Lint> {
+    let mut linter = Linter {
+        scopes: vec![HashMap::new()],
+        locals_from: 0,
+        loops: Vec::new(),
+        enclosing: Vec::new(),
+        lines: BTreeSet::new(),
+        found: Vec::new(),
+    };
+
+    // Functions may use globals declared after them
+    for stmt in program {
+        if let Statement::Let {
+            name, is_public, ..
+        } = &stmt.statement
+        {
+            linter.enclosing.push(stmt.line);
+            linter.declare(name, Kind::Variable, *is_public);
+            linter.enclosing.pop();
+        }
+    }
+    linter.statements(program);
+    linter.pop_scope();
+
+    // Line of the statement each `kode:allow` comment applies to -> rules
+    let mut allowed: HashMap<usize, Vec<&str>> = HashMap::new();
+    for comment in comments {
+        let Some(rules) = allowed_rules(&comment.text) else {
+            continue;
+        };
+        let line = if comment.trailing {
+            Some(comment.span.line)
+        } else {
+            linter.lines.range(comment.span.line + 1..).next().copied()
+        };
+        if let Some(line) = line {
+            allowed.entry(line).or_default().extend(rules);
+        }
+    }
+
+    let mut lints: Vec<Lint> = linter
+        .found
+        .into_iter()
+        .filter(|(lint, enclosing)| {
+            !enclosing.iter().any(|line| {
+                allowed
+                    .get(line)
+                    .is_some_and(|rules| rules.contains(&lint.rule))
+            })
+        })
+        .map(|(lint, _)| lint)
+        .collect();
+    lints.sort_by_key(|lint| lint.line);
+    lints
+}
+
+// Rules named by a `// kode:allow(rule, ...)` comment
+fn allowed_rules(text: &str) -> Option<Vec<&str>> {
+    let rest = text
+        .strip_prefix("//")?
+        .trim_start()
+        .strip_prefix("kode:allow(")?;
+    let (rules, _) = rest.split_once(')')?;
+    Some(rules.split(',').map(str::trim).collect())
+}
+
+enum Kind {
+    Variable,
+    Parameter(Option<String>), // Name of the function, None for closures
+}
+
+struct Binding {
+    kind: Kind,
+    used: bool,
+    enclosing: Vec<usize>, // Lines of the statements around the declaration
+}
+
+struct Linter {
+    scopes: Vec<HashMap<String, Binding>>, // Scopes as the interpreter enters them; 0 is the globals
+    locals_from: usize,                    // First scope of the function being linted
+    loops: Vec<HashSet<String>>,           // Names used before any `let` in each enclosing loop
+    enclosing: Vec<usize>,                 // Lines of the statements being linted, innermost last
+    lines: BTreeSet<usize>,                // Lines where statements start
+    found: Vec<(Lint, Vec<usize>)>,        // Problems and the lines of the statements around them
+}
+
+impl Linter {
+    fn report(&mut self, rule: &'static str, message: String) {
+        let line = self.enclosing.last().copied().unwrap_or(1);
+        let lint = Lint {
+            rule,
+            line,
+            message,
+        };
+        self.found.push((lint, self.enclosing.clone()));
+    }
+
+    // Declare `name` in the innermost scope. Names starting with `_` and
+    // exported globals don't need to be used.
+    fn declare(&mut self, name: &str, kind: Kind, used: bool) {
+        let binding = Binding {
+            kind,
+            used: used
+                || name.starts_with('_')
+                || name == "self"
+                || self.loops.iter().any(|pending| pending.contains(name)),
+            enclosing: self.enclosing.clone(),
+        };
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("there is always a global scope");
+        if let Some(previous) = scope.insert(name.to_string(), binding) {
+            self.unused(name, previous);
+        }
+    }
+
+    fn unused(&mut self, name: &str, binding: Binding) {
+        if binding.used {
+            return;
+        }
+        let (rule, message) = match binding.kind {
+            Kind::Variable => (
+                "unused-variable",
+                format!("Variable '{}' is never used", name),
+            ),
+            Kind::Parameter(Some(function)) => (
+                "unused-parameter",
+                format!(
+                    "Parameter '{}' of function '{}' is never used",
+                    name, function
+                ),
+            ),
+            Kind::Parameter(None) => (
+                "unused-parameter",
+                format!("Parameter '{}' of a closure is never used", name),
+            ),
+        };
+        let line = binding.enclosing.last().copied().unwrap_or(1);
+        self.found.push((
+            Lint {
+                rule,
+                line,
+                message,
+            },
+            binding.enclosing,
+        ));
+    }
+
+    fn use_name(&mut self, name: &str) {
+        let locals = (self.locals_from.max(1)..self.scopes.len()).rev();
+        for index in locals.chain([0]) {
+            if let Some(binding) = self.scopes[index].get_mut(name) {
+                binding.used = true;
+                return;
+            }
+        }
+        if let Some(pending) = self.loops.last_mut() {
+            pending.insert(name.to_string());
+        }
+    }
+
+    // Lint a loop. A variable used before the `let` that declares it in the
+    // loop is used by the next iteration.
+    fn in_loop(&mut self, lint: impl FnOnce(&mut Self)) {
+        self.loops.push(HashSet::new());
+        lint(self);
+        let pending = self.loops.pop().unwrap_or_default();
+        if let Some(outer) = self.loops.last_mut() {
+            outer.extend(pending);
+        }
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            let mut bindings: Vec<_> = scope.into_iter().collect();
+            bindings
+                .sort_by(|(a, x), (b, y)| (x.enclosing.last(), a).cmp(&(y.enclosing.last(), b)));
+            for (name, binding) in bindings {
+                self.unused(&name, binding);
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &[Located]) {
+        let mut returned = false;
+        let mut reported = false;
+        for stmt in statements {
+            self.enclosing.push(stmt.line);
+            self.lines.insert(stmt.line);
+            // Only the first unreachable statement of a block is reported
+            if returned && !reported {
+                self.report(
+                    "unreachable-code",
+                    "Unreachable code after 'return'".to_string(),
+                );
+                reported = true;
+            }
+            self.statement(stmt);
+            self.enclosing.pop();
+            returned = returned || always_returns(std::slice::from_ref(stmt));
+        }
+    }
+
+    fn block(&mut self, statements: &[Located]) {
+        self.push_scope();
+        self.statements(statements);
+        self.pop_scope();
+    }
+
+    // A function or method body runs with its parameters in a scope of its own
+    fn function(&mut self, name: Option<String>, params: &[String], body: &[Located]) {
+        let outer = self.locals_from;
+        if name.is_some() {
+            self.locals_from = self.scopes.len();
+        }
+        self.push_scope();
+        for param in params {
+            self.declare(param, Kind::Parameter(name.clone()), false);
+        }
+        self.statements(body);
+        self.pop_scope();
+        self.locals_from = outer;
+    }
+
+    fn condition(&mut self, keyword: &str, condition: &Expression) {
+        if let Some(value) = constant(condition) {
+            self.report(
+                "constant-condition",
+                format!("Condition of '{}' is always {}", keyword, value),
+            );
+        }
+        self.expression(condition);
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let {
+                name,
+                value,
+                is_public,
+                ..
+            } => {
+                self.expression(value);
+                // Top-level variables are declared before the program is linted
+                let declared = self.scopes.len() == 1
+                    && self.scopes[0]
+                        .get(name)
+                        .is_some_and(|b| b.enclosing == self.enclosing);
+                if !declared {
+                    self.declare(name, Kind::Variable, *is_public);
+                }
+            }
+            Statement::Assign { name, value } => {
+                if matches!(value, Expression::Identifier(value, _) if value == name) {
+                    self.report(
+                        "self-assignment",
+                        format!("Variable '{}' is assigned to itself", name),
+                    );
+                }
+                self.expression(value);
+            }
+            Statement::FunctionDef {
+                is_main,
+                name,
+                params,
+                body,
+                ..
+            } => {
+                let name = if *is_main {
+                    "main".to_string()
+                } else {
+                    name.clone()
+                };
+                self.function(Some(name), params, body);
+            }
+            Statement::Impl { name, methods } => {
+                for method in methods {
+                    if let Statement::FunctionDef {
+                        name: method_name,
+                        params,
+                        body,
+                        ..
+                    } = &method.statement
+                    {
+                        self.enclosing.push(method.line);
+                        self.lines.insert(method.line);
+                        self.function(Some(format!("{}.{}", name, method_name)), params, body);
+                        self.enclosing.pop();
+                    }
+                }
+            }
+            Statement::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+            }
+            Statement::Return(expr)
+            | Statement::ExprStmt(expr)
+            | Statement::Print(expr)
+            | Statement::Expression(expr) => self.expression(expr),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition("if", condition);
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Statement::While { condition, body } => {
+                self.in_loop(|linter| {
+                    // `while (true)` is how loops that end with a `return` are written
+                    if *condition == Expression::Bool(true) {
+                        linter.expression(condition);
+                    } else {
+                        linter.condition("while", condition);
+                    }
+                    linter.statements(body);
+                });
+            }
+            Statement::For {
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                self.push_scope();
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                self.in_loop(|linter| {
+                    if let Some(condition) = condition {
+                        linter.condition("for", condition);
+                    }
+                    linter.block(body);
+                    if let Some(update) = update {
+                        linter.statement(update);
+                    }
+                });
+                self.pop_scope();
+            }
+            Statement::ForIn {
+                names,
+                iterable,
+                body,
+            } => {
+                self.expression(iterable);
+                self.push_scope();
+                for name in names {
+                    self.declare(name, Kind::Variable, false);
+                }
+                self.in_loop(|linter| linter.statements(body));
+                self.pop_scope();
+            }
+            Statement::Block(statements) => self.block(statements),
+            Statement::Try { body, catch } => {
+                self.statements(body);
+                if catch.is_empty() {
+                    self.report(
+                        "empty-catch",
+                        "Empty 'catch' block ignores the error".to_string(),
+                    );
+                }
+                self.statements(catch);
+            }
+            Statement::Struct { .. }
+            | Statement::Enum { .. }
+            | Statement::Import { .. }
+            | Statement::ImportFrom { .. } => {}
+        }
+    }
+
+    fn arms(&mut self, arms: &[MatchArm]) {
+        for arm in arms {
+            self.push_scope();
+            let mut bindings = Vec::new();
+            pattern_bindings(&arm.pattern, &mut bindings);
+            for name in bindings {
+                self.declare(&name, Kind::Variable, false);
+            }
+            if let Some(guard) = &arm.guard {
+                self.expression(guard);
+            }
+            match &arm.body {
+                ArmBody::Expression(expr) => self.expression(expr),
+                ArmBody::Block(statements) => self.statements(statements),
+            }
+            self.pop_scope();
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Identifier(name, _) => self.use_name(name),
+            Expression::Binary { left, op, right } => {
+                if matches!(op, BinaryOp::Equal | BinaryOp::NotEqual) {
+                    self.bool_comparison(left, op, right);
+                }
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary { expr, .. } => self.expression(expr),
+            Expression::CompoundAssign { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            Expression::Range { start, end } => {
+                self.expression(start);
+                self.expression(end);
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition("?:", condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expression::Call { callee, arguments } => {
+                self.self_assignment(callee, arguments);
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Array { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+            }
+            Expression::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+            }
+            Expression::Interpolation { parts } => {
+                for part in parts {
+                    if let InterpolationPart::Expression { expr, .. } = part {
+                        self.expression(expr);
+                    }
+                }
+            }
+            Expression::ArrayAccess { array, index } => {
+                self.expression(array);
+                self.expression(index);
+            }
+            Expression::FieldAccess { object, .. } => self.expression(object),
+            Expression::Closure { params, body } => self.function(None, params, body),
+            Expression::Number(_)
+            | Expression::BigInt(_)
+            | Expression::Float(_)
+            | Expression::Bool(_)
+            | Expression::String(_) => {}
+        }
+    }
+
+    fn bool_comparison(&mut self, left: &Expression, op: &BinaryOp, right: &Expression) {
+        let value = match (left, right) {
+            (Expression::Bool(value), _) | (_, Expression::Bool(value)) => *value,
+            _ => return,
+        };
+        let message = if value == (*op == BinaryOp::Equal) {
+            format!(
+                "Comparing with '{}' using '{}' is redundant; use the value itself",
+                value, op
+            )
+        } else {
+            format!(
+                "Comparing with '{}' using '{}'; negate the value with '!' instead",
+                value, op
+            )
+        };
+        self.report("bool-comparison", message);
+    }
+
+    // `a.f = a.f` and `a[i] = a[i]`, which the parser turns into calls
+    fn self_assignment(&mut self, callee: &Expression, arguments: &[Expression]) {
+        let Expression::Identifier(callee, _) = callee else {
+            return;
+        };
+        match (callee.as_str(), arguments) {
+            (
+                "__field_assign",
+                [
+                    object,
+                    Expression::String(field),
+                    Expression::FieldAccess {
+                        object: value,
+                        field: value_field,
+                    },
+                ],
+            ) if value.as_ref() == object && value_field == field => {
+                self.report(
+                    "self-assignment",
+                    format!("Field '{}' is assigned to itself", field),
+                );
+            }
+            (
+                "__array_assign",
+                [
+                    array,
+                    index,
+                    Expression::ArrayAccess {
+                        array: value,
+                        index: value_index,
+                    },
+                ],
+            ) if value.as_ref() == array && value_index.as_ref() == index => {
+                self.report(
+                    "self-assignment",
+                    "Element is assigned to itself".to_string(),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+// The value of a condition that doesn't depend on anything that changes
+fn constant(expr: &Expression) -> Option<bool> {
+    match expr {
+        Expression::Bool(value) => Some(*value),
+        Expression::Unary {
+            op: UnaryOp::Not,
+            expr,
+        } => constant(expr).map(|value| !value),
+        Expression::Binary {
+            left,
+            op: BinaryOp::And,
+            right,
+        } => match (constant(left), constant(right)) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None,
+        },
+        Expression::Binary {
+            left,
+            op: BinaryOp::Or,
+            right,
+        } => match (constant(left), constant(right)) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        },
+        Expression::Binary { left, op, right } => match (left.as_ref(), right.as_ref()) {
+            (Expression::Number(a), Expression::Number(b)) => match op {
+                BinaryOp::Equal => Some(a == b),
+                BinaryOp::NotEqual => Some(a != b),
+                BinaryOp::LessThan => Some(a < b),
+                BinaryOp::GreaterThan => Some(a > b),
+                BinaryOp::LessThanOrEqual => Some(a <= b),
+                BinaryOp::GreaterThanOrEqual => Some(a >= b),
+                _ => None,
+            },
+            (Expression::String(a), Expression::String(b)) => match op {
+                BinaryOp::Equal => Some(a == b),
+                BinaryOp::NotEqual => Some(a != b),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,724 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::checker;
+use crate::interpreter::BUILTINS;
+use crate::lexer::{KEYWORDS, Lexer};
+use crate::modules::{self, ModuleResolver};
+use crate::names;
+use crate::package::Project;
+use crate::parser::Parser;
+use crate::rpc::{read_message, write_message};
+use crate::symbols::{self, Index, Symbol, SymbolKind};
+use crate::types;
+
+// Error codes of responses
+const METHOD_NOT_FOUND: i64 = -32601;
+const REQUEST_FAILED: i64 = -32803;
+
+/// Serve the Language Server Protocol over stdin and stdout until the client
+/// sends `exit`. Returns the exit code, which is 0 if the client asked the
+/// server to shut down first.
+pub fn serve() -> Result<i32, String> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server {
+        documents: HashMap::new(),
+        packages: HashMap::new(),
+        shutdown: false,
+    };
+
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        if method == "exit" {
+            return Ok(if server.shutdown { 0 } else { 1 });
+        }
+
+        match message.get("id") {
+            // Responses to requests of the server, which sends none
+            Some(_) if method.is_empty() => {}
+            Some(id) => {
+                let response = match server.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, error)) => {
+                        json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": error } })
+                    }
+                };
+                write_message(&mut output, &response)?;
+            }
+            None => {
+                for notification in server.notify(method, params) {
+                    write_message(&mut output, &notification)?;
+                }
+            }
+        }
+    }
+    Ok(1)
+}
+
+struct Server {
+    documents: HashMap<String, String>, // Text of the open documents by URI
+    packages: HashMap<PathBuf, Vec<(String, PathBuf)>>, // Dependencies of each package root
+    shutdown: bool,
+}
+
+// A symbol and the document defining it
+#[derive(Clone)]
+struct Target {
+    uri: String,
+    text: String,
+    index: Index,
+    symbol: usize,
+}
+
+impl Server {
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                    "renameProvider": true,
+                },
+                "serverInfo": { "name": "kode", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => Ok(self.definition(&uri, &params["position"])),
+            "textDocument/hover" => Ok(self.hover(&uri, &params["position"])),
+            "textDocument/documentSymbol" => Ok(self.document_symbols(&uri)),
+            "textDocument/completion" => Ok(self.completion(&uri, &params["position"])),
+            "textDocument/rename" => {
+                let new_name = params["newName"].as_str().unwrap_or_default();
+                self.rename(&uri, &params["position"], new_name)
+                    .map_err(|e| (REQUEST_FAILED, e))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unsupported method '{}'", method))),
+        }
+    }
+
+    // Handle a notification, returning the notifications to send back
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // The whole text is sent on each change
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish(&uri, Vec::new())];
+            }
+            _ => None,
+        };
+        let Some(text) = text else {
+            return Vec::new();
+        };
+
+        self.documents.insert(uri.clone(), text.to_string());
+        vec![publish(&uri, diagnostics(&uri, text))]
+    }
+
+    // Text of an open document, or of a file on disk
+    fn text(&self, uri: &str) -> Option<String> {
+        match self.documents.get(uri) {
+            Some(text) => Some(text.clone()),
+            None => fs::read_to_string(uri_to_path(uri)?).ok(),
+        }
+    }
+
+    // The file of `module` imported by the document at `uri`, searched like
+    // the interpreter does: next to the document, in the dependencies of its
+    // package, then in `KODE_PATH`
+    fn module(&mut self, uri: &str, module: &str) -> Option<(String, String, Index)> {
+        let path = uri_to_path(uri)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut resolver = ModuleResolver::new();
+        if let Ok(Some(project)) = Project::find(dir) {
+            let dependencies = self
+                .packages
+                .entry(project.root.clone())
+                .or_insert_with(|| {
+                    project
+                        .resolve_locked()
+                        .map(|resolved| resolved.into_iter().map(|d| (d.name, d.entry)).collect())
+                        .unwrap_or_default()
+                });
+            for (name, entry) in dependencies.iter() {
+                resolver.add_package(name.clone(), entry.clone());
+            }
+        }
+        for path in modules::kode_path() {
+            resolver.add_search_path(path);
+        }
+
+        let uri = path_to_uri(&resolver.resolve(module, &[dir]).ok()?);
+        let text = self.text(&uri)?;
+        let index = symbols::index(&text).ok()?;
+        Some((uri, text, index))
+    }
+
+    // The definition of the name at `position` of the document at `uri`
+    fn target(&mut self, uri: &str, position: &Value) -> Option<Target> {
+        let text = self.text(uri)?;
+        let index = symbols::index(&text).ok()?;
+        let offset = offset(&text, position);
+
+        if let Some(reference) = index.reference_at(offset) {
+            return self.resolve(
+                Target {
+                    uri: uri.to_string(),
+                    text,
+                    index,
+                    symbol: 0,
+                },
+                reference,
+            );
+        }
+        let symbol = index
+            .symbols
+            .iter()
+            .position(|s| s.span.start <= offset && offset <= s.span.end)?;
+        Some(self.follow(Target {
+            uri: uri.to_string(),
+            text,
+            index,
+            symbol,
+        }))
+    }
+
+    // The definition of reference `reference` of `document`, looking in the
+    // modules it imports for names it doesn't define
+    fn resolve(&mut self, document: Target, reference: usize) -> Option<Target> {
+        let found = &document.index.references[reference];
+        let name = found.name.clone();
+        if let Some(symbol) = found.symbol {
+            return Some(self.follow(Target { symbol, ..document }));
+        }
+
+        if let Some(object) = found.object {
+            if let Some(module) = module_of(&document.index, reference) {
+                let (uri, text, index) = self.module(&document.uri, &module)?;
+                let symbol = index.global(&name)?;
+                return Some(Target {
+                    uri,
+                    text,
+                    index,
+                    symbol,
+                });
+            }
+            let object = self.resolve(document.clone(), object)?;
+            let symbol = &object.index.symbols[object.symbol];
+            return match symbol.kind {
+                SymbolKind::Struct | SymbolKind::Enum => {
+                    let symbol = object
+                        .index
+                        .members(object.symbol)
+                        .find(|&m| object.index.symbols[m].name == name)?;
+                    Some(Target { symbol, ..object })
+                }
+                _ => None,
+            };
+        }
+
+        // Names of imported modules can be used without the module name
+        let modules: Vec<String> = document
+            .index
+            .symbols
+            .iter()
+            .filter(|s| s.kind == SymbolKind::Module)
+            .filter_map(|s| s.module.clone())
+            .collect();
+        for module in modules {
+            if let Some((uri, text, index)) = self.module(&document.uri, &module)
+                && let Some(symbol) = index.global(&name)
+            {
+                return Some(Target {
+                    uri,
+                    text,
+                    index,
+                    symbol,
+                });
+            }
+        }
+        None
+    }
+
+    // The definition behind a name of `from module import name`
+    fn follow(&mut self, target: Target) -> Target {
+        let symbol = &target.index.symbols[target.symbol];
+        if symbol.kind != SymbolKind::Imported {
+            return target;
+        }
+        let name = symbol.name.clone();
+        let found = symbol
+            .module
+            .clone()
+            .and_then(|module| self.module(&target.uri, &module))
+            .and_then(|(uri, text, index)| {
+                index.global(&name).map(|symbol| Target {
+                    uri,
+                    text,
+                    index,
+                    symbol,
+                })
+            });
+        found.unwrap_or(target)
+    }
+
+    fn definition(&mut self, uri: &str, position: &Value) -> Value {
+        let Some(target) = self.target(uri, position) else {
+            return Value::Null;
+        };
+        let symbol = &target.index.symbols[target.symbol];
+        if symbol.kind == SymbolKind::Module {
+            // The module's file
+            if let Some((uri, _, _)) = symbol
+                .module
+                .clone()
+                .and_then(|module| self.module(&target.uri, &module))
+            {
+                return json!({ "uri": uri, "range": range(&target.text, 0, 0) });
+            }
+        }
+        json!({ "uri": target.uri, "range": range(&target.text, symbol.span.start, symbol.span.end) })
+    }
+
+    fn hover(&mut self, uri: &str, position: &Value) -> Value {
+        let contents = match self.target(uri, position) {
+            Some(target) => {
+                let symbol = &target.index.symbols[target.symbol];
+                let detail = match symbol.kind {
+                    SymbolKind::Variable
+                        if !symbol.detail.starts_with("let")
+                            && !symbol.detail.starts_with("for") =>
+                    {
+                        format!("let {}", symbol.detail)
+                    }
+                    _ => symbol.detail.clone(),
+                };
+                format!("```kode\n{}\n```", detail)
+            }
+            None => {
+                let name = self.text(uri).and_then(|text| {
+                    let index = symbols::index(&text).ok()?;
+                    let reference = index.reference_at(offset(&text, position))?;
+                    Some(index.references[reference].name.clone())
+                });
+                match name {
+                    Some(name) if BUILTINS.contains(&name.as_str()) => {
+                        format!("Built-in function `{}`", name)
+                    }
+                    _ => return Value::Null,
+                }
+            }
+        };
+        json!({ "contents": { "kind": "markdown", "value": contents } })
+    }
+
+    fn document_symbols(&mut self, uri: &str) -> Value {
+        let Some(text) = self.text(uri) else {
+            return Value::Null;
+        };
+        let Ok(index) = symbols::index(&text) else {
+            return json!([]);
+        };
+
+        let symbol = |i: usize, children: Vec<Value>| {
+            let symbol = &index.symbols[i];
+            json!({
+                "name": symbol.name,
+                "detail": symbol.detail,
+                "kind": document_symbol_kind(symbol.kind),
+                "range": range(&text, symbol.range.0, symbol.range.1),
+                "selectionRange": range(&text, symbol.span.start, symbol.span.end),
+                "children": children,
+            })
+        };
+        let top_level = (0..index.symbols.len()).filter(|&i| {
+            let s = &index.symbols[i];
+            (s.scope == 0 && s.parent.is_none())
+                && (!s.kind.is_member() || s.kind == SymbolKind::Method)
+        });
+        let symbols: Vec<Value> = top_level
+            .map(|i| {
+                symbol(
+                    i,
+                    index
+                        .members(i)
+                        .map(|member| symbol(member, Vec::new()))
+                        .collect(),
+                )
+            })
+            .collect();
+        json!(symbols)
+    }
+
+    fn completion(&mut self, uri: &str, position: &Value) -> Value {
+        let Some(text) = self.text(uri) else {
+            return Value::Null;
+        };
+        let Ok(index) = symbols::index(&text) else {
+            return json!([]);
+        };
+        let offset = offset(&text, position);
+
+        let mut items = Vec::new();
+        let word_start = text[..offset]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        if text[..word_start].ends_with('.') {
+            // Members of the object before the `.`
+            let dot = word_start - 1;
+            let object = index.references.iter().position(|r| r.span.end == dot);
+            let document = Target {
+                uri: uri.to_string(),
+                text: text.clone(),
+                index: index.clone(),
+                symbol: 0,
+            };
+            let object = object.and_then(|object| self.resolve(document, object));
+            match object.and_then(|object| self.members(object)) {
+                Some(members) => items = members,
+                None => {
+                    // Any field or method, as the type of the object isn't known
+                    let mut seen = Vec::new();
+                    for member in index.symbols.iter().filter(|s| s.kind.is_member()) {
+                        if !seen.contains(&&member.name) {
+                            seen.push(&member.name);
+                            items.push(completion_item(member));
+                        }
+                    }
+                }
+            }
+            return json!(items);
+        }
+
+        let mut seen: Vec<String> = Vec::new();
+        for symbol in index.visible(offset).into_iter().map(|i| &index.symbols[i]) {
+            if !seen.contains(&symbol.name) {
+                seen.push(symbol.name.clone());
+                items.push(completion_item(symbol));
+            }
+        }
+        for builtin in BUILTINS {
+            items.push(json!({ "label": builtin, "kind": 3, "detail": "built-in function" }));
+        }
+        for keyword in KEYWORDS {
+            items.push(json!({ "label": keyword, "kind": 14 }));
+        }
+        json!(items)
+    }
+
+    // Completions after `object.`, or `None` if the type of the object isn't known
+    fn members(&mut self, object: Target) -> Option<Vec<Value>> {
+        let symbol = &object.index.symbols[object.symbol];
+        let members: Vec<usize> = match symbol.kind {
+            SymbolKind::Module => {
+                let (_, _, module) = self.module(&object.uri, symbol.module.as_deref()?)?;
+                let globals = module
+                    .symbols
+                    .iter()
+                    .filter(|s| s.scope == 0 && !s.kind.is_member());
+                return Some(globals.map(completion_item).collect());
+            }
+            SymbolKind::Struct | SymbolKind::Enum => object.index.members(object.symbol).collect(),
+            _ => object.index.members(symbol.instance_of?).collect(),
+        };
+        Some(
+            members
+                .into_iter()
+                .map(|member| completion_item(&object.index.symbols[member]))
+                .collect(),
+        )
+    }
+
+    fn rename(&mut self, uri: &str, position: &Value, new_name: &str) -> Result<Value, String> {
+        let valid = new_name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && new_name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            && !KEYWORDS.contains(&new_name);
+        if !valid {
+            return Err(format!("'{}' is not a valid name", new_name));
+        }
+
+        let text = self
+            .text(uri)
+            .ok_or_else(|| format!("Can't read {}", uri))?;
+        let index = symbols::index(&text)?;
+        let symbol = index
+            .symbol_at(offset(&text, position))
+            .ok_or_else(|| "No name defined in this file here".to_string())?;
+        let found = &index.symbols[symbol];
+        if found.kind == SymbolKind::Imported {
+            return Err(format!(
+                "'{}' is defined in module {}",
+                found.name,
+                found.module.clone().unwrap_or_default()
+            ));
+        }
+        if found.name == "self" {
+            return Err("'self' can't be renamed".to_string());
+        }
+
+        let spans = std::iter::once(found.span).chain(
+            index
+                .references
+                .iter()
+                .filter(|r| r.symbol == Some(symbol))
+                .map(|r| r.span),
+        );
+        let edits: Vec<Value> = spans
+            .map(|span| json!({ "range": range(&text, span.start, span.end), "newText": new_name }))
+            .collect();
+        Ok(json!({ "changes": { uri: edits } }))
+    }
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+// Problems of a document, as `kode check` reports them
+fn diagnostics(uri: &str, text: &str) -> Vec<Value> {
+    const ERROR: u8 = 1;
+    const WARNING: u8 = 2;
+    let diagnostic = |range: Value, severity: u8, message: &str| json!({ "range": range, "severity": severity, "source": "kode", "message": message });
+
+    let mut lexer = Lexer::new(text);
+    if let Err(error) = lexer.tokenize() {
+        let (line, column) = lexer.get_position();
+        return vec![diagnostic(point(text, line, column), ERROR, &error)];
+    }
+    let name = uri_to_path(uri).map_or(uri.to_string(), |path| path.to_string_lossy().into_owned());
+    let mut parser = match Parser::new(&name, text) {
+        Ok(parser) => parser,
+        Err(error) => return vec![diagnostic(point(text, 1, 1), ERROR, &error)],
+    };
+    let mut ast = match parser.parse() {
+        Ok(ast) => ast,
+        Err(error) => {
+            let (line, column) = parser.position();
+            return vec![diagnostic(point(text, line, column), ERROR, &error)];
+        }
+    };
+
+    let names = names::resolve(&mut ast);
+    let errors = names
+        .errors
+        .iter()
+        .chain(&types::check(&ast))
+        .map(|d| (d.clone(), ERROR))
+        .collect::<Vec<_>>();
+    let warnings = names
+        .warnings
+        .iter()
+        .chain(&checker::check(&ast))
+        .map(|d| (d.clone(), WARNING))
+        .collect::<Vec<_>>();
+    errors
+        .into_iter()
+        .chain(warnings)
+        .map(|(found, severity)| diagnostic(line_range(text, found.line), severity, &found.message))
+        .collect()
+}
+
+// The import path of the module whose global `reference` names, when it ends
+// a chain such as `util.helper.name` that starts at an imported module.
+// `import util.helper;` binds `util`, so the chain spells out the whole path;
+// after `import util.helper as h;` it is just the alias.
+fn module_of(index: &Index, reference: usize) -> Option<String> {
+    let mut segments = Vec::new();
+    let mut current = &index.references[reference];
+    let root = loop {
+        match (current.symbol, current.object) {
+            (Some(symbol), _) => break &index.symbols[symbol],
+            (None, Some(object)) => current = &index.references[object],
+            (None, None) => return None,
+        }
+        segments.push(current.name.as_str());
+    };
+    if root.kind != SymbolKind::Module {
+        return None;
+    }
+    segments.reverse();
+    let path = segments.join(".");
+
+    // `import util.a; import util.b;` both bind `util`
+    index
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.kind == SymbolKind::Module && symbol.name == root.name)
+        .find_map(|symbol| {
+            let module = symbol.module.clone()?;
+            let written = if symbol.detail.contains(" as ") {
+                &symbol.name
+            } else {
+                &module
+            };
+            (*written == path).then_some(module)
+        })
+}
+
+// The position at a 1-based line and column, where a lexer or parser error is
+fn point(text: &str, line: usize, column: usize) -> Value {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let offset = text[start..]
+        .char_indices()
+        .take_while(|&(_, c)| c != '\n')
+        .nth(column.saturating_sub(1))
+        .map_or_else(
+            || start + text[start..].find('\n').unwrap_or(text.len() - start),
+            |(i, _)| start + i,
+        );
+    range(text, offset, offset)
+}
+
+// The code on a 1-based line, without its indentation
+fn line_range(text: &str, line: usize) -> Value {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let content = text[start..].split('\n').next().unwrap_or_default();
+    let indent = content.len() - content.trim_start().len();
+    range(text, start + indent, start + content.trim_end().len())
+}
+
+// Byte offset of an LSP position, whose character counts UTF-16 code units
+fn offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+    let start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+
+    let mut units = 0;
+    for (i, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+// The LSP position of a byte offset
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": before.matches('\n').count(), "character": character })
+}
+
+fn range(text: &str, start: usize, end: usize) -> Value {
+    json!({ "start": position(text, start), "end": position(text, end) })
+}
+
+fn completion_item(symbol: &Symbol) -> Value {
+    // Kinds of completion items defined by the protocol
+    let kind = match symbol.kind {
+        SymbolKind::Function => 3,
+        SymbolKind::Method => 2,
+        SymbolKind::Variable | SymbolKind::Parameter => 6,
+        SymbolKind::Struct => 22,
+        SymbolKind::Field => 5,
+        SymbolKind::Enum => 13,
+        SymbolKind::Variant => 20,
+        SymbolKind::Module => 9,
+        SymbolKind::Imported => 18,
+    };
+    json!({ "label": symbol.name, "kind": kind, "detail": symbol.detail })
+}
+
+// Kinds of document symbols defined by the protocol
+fn document_symbol_kind(kind: SymbolKind) -> u8 {
+    match kind {
+        SymbolKind::Function => 12,
+        SymbolKind::Method => 6,
+        SymbolKind::Variable | SymbolKind::Parameter | SymbolKind::Imported => 13,
+        SymbolKind::Struct => 23,
+        SymbolKind::Field => 8,
+        SymbolKind::Enum => 10,
+        SymbolKind::Variant => 22,
+        SymbolKind::Module => 2,
+    }
+}
+
+// The path of a `file:` URI
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // `file:///C:/dir` on Windows
+    let path = match path.strip_prefix('/') {
+        Some(rest) if rest.get(1..2) == Some(":") => rest.to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+// The `file:` URI of a path
+fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
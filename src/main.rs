@@ -1,37 +1,32 @@
-mod ast;
-mod lexer;
-mod parser;
-mod interpreter;
 mod codegen;  // New module for native code generation
+mod cli;
+mod checker;
+mod types;
+mod names;
+mod lint;
+mod format;
+mod package;
+mod symbols;
+mod rpc;
+mod lsp;
+mod debug;
 
-use std::env;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::ExitCode;
 use std::time::Instant;
 
+use clap::{CommandFactory, Parser as _};
+
+use kode_compiler::{ast, interpreter, io, lexer, modules, parser};
+
 use parser::Parser;
 use interpreter::Interpreter;
-use codegen::CodeGenerator;  // New import
-
-const VERSION: &str = "0.2.0";
+use cli::{Cli, Command};
+use package::Project;
 
-fn print_help() {
-    println!("Kode Programming Language v{}", VERSION);
-    println!("Usage: kode [COMMAND] [OPTIONS]");
-    println!("\nCommands:");
-    println!("  run <file.kode>      Run a Kode program");
-    println!("  build <file.kode>    Compile a Kode program to a .kdc file");
-    println!("  repl                 Start an interactive REPL session");
-    println!("  version              Show compiler version");
-    println!("  help                 Show this help message");
-    println!("\nOptions:");
-    println!("  --verbose            Enable verbose output");
-    println!("  --optimize           Enable optimization (build command)");
-    println!("  --time               Show execution time");
-    println!("  --no-run             Build only, don't run (build command)");
-}
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 fn print_version() {
     println!("Kode Programming Language v{}", VERSION);
@@ -51,50 +46,302 @@ fn validate_file_path(file_path: &str) -> Result<PathBuf, String> {
     Ok(path.to_path_buf())
 }
 
-fn run_file(file_path: &str, verbose: bool, time_execution: bool) -> Result<(), String> {
+// Settings shared by every way of running a program
+struct RunOptions {
+    program_args: Vec<String>,
+    lib_paths: Vec<PathBuf>,
+    verbose: bool,
+    time_execution: bool,
+}
+
+// Interpreter for a program whose imports resolve relative to `base_dir`,
+// then the dependencies of the package containing it, then the `--lib-path`
+// directories, then `KODE_PATH`
+fn new_interpreter(base_dir: &Path, options: &RunOptions) -> Result<Interpreter, String> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_base_path(base_dir);
+    interpreter.set_program_args(options.program_args.clone());
+
+    if let Some(project) = Project::find(base_dir)? {
+        for dependency in project.resolve_locked()? {
+            if options.verbose {
+                println!("Using {} v{} ({})", dependency.name, dependency.version, dependency.source);
+            }
+            interpreter.add_package(dependency.name, dependency.entry);
+        }
+    }
+
+    for path in options.lib_paths.iter().cloned().chain(modules::kode_path()) {
+        interpreter.add_search_path(path);
+    }
+    Ok(interpreter)
+}
+
+// Entry file of the package in the current directory
+fn package_entry() -> Result<String, String> {
+    match Project::find(Path::new("."))? {
+        Some(project) => Ok(project.entry().to_string_lossy().into_owned()),
+        None => Err(format!("Error: No input file specified and no {} found", package::MANIFEST_FILE)),
+    }
+}
+
+fn init_package(path: &Path, name: Option<String>) -> Result<(), String> {
+    let project = package::init(path, name)?;
+    println!("Created package '{}' in {}", project.manifest.package.name, project.root.display());
+    Ok(())
+}
+
+fn add_dependency(name: &str, path: Option<String>, git: Option<String>, rev: Option<String>, version: Option<String>) -> Result<(), String> {
+    let mut project = Project::find(Path::new("."))?
+        .ok_or_else(|| format!("Error: No {} found in this directory or its parents", package::MANIFEST_FILE))?;
+
+    let quote = |s: String| toml::Value::String(s).to_string();
+    let spec = match (path, git, version) {
+        (Some(path), _, _) => format!("{{ path = {} }}", quote(path)),
+        (None, Some(git), _) => match rev {
+            Some(rev) => format!("{{ git = {}, rev = {} }}", quote(git), quote(rev)),
+            None => format!("{{ git = {} }}", quote(git)),
+        },
+        (None, None, version) => quote(version.unwrap_or_else(|| "*".to_string())),
+    };
+
+    let resolved = project.add_dependency(name, &spec)?;
+    if let Some(dependency) = resolved.iter().find(|d| d.name == name) {
+        println!("Added {} v{} ({})", dependency.name, dependency.version, dependency.source);
+    }
+    Ok(())
+}
+
+fn install_dependencies() -> Result<(), String> {
+    let project = Project::find(Path::new("."))?
+        .ok_or_else(|| format!("Error: No {} found in this directory or its parents", package::MANIFEST_FILE))?;
+
+    for dependency in project.resolve()? {
+        println!("Using {} v{} ({})", dependency.name, dependency.version, dependency.source);
+    }
+    Ok(())
+}
+
+fn run_file(file_path: &str, options: &RunOptions) -> Result<i32, String> {
     let path = validate_file_path(file_path)?;
     
-    if verbose {
+    if options.verbose {
         println!("Running file: {}", file_path);
     }
 
+    let source_code = fs::read_to_string(&path)
+        .map_err(|e| format!("Error reading file {}: {}", file_path, e))?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    run_source(file_path, &source_code, base_dir, options)
+}
+
+// Run source code given with `-e` or piped through stdin
+fn run_snippet(source_name: &str, source_code: &str, options: &RunOptions) -> Result<i32, String> {
+    if options.verbose {
+        println!("Running {}", source_name);
+    }
+
+    run_source(source_name, source_code, Path::new("."), options)
+}
+
+// Resolve names and print the warnings and errors found before running;
+// errors stop the program from running. Each is printed as
+// `file:line: error: message`, like `kode lint` prints its findings.
+fn check_program(file_path: &str, ast: &mut [ast::Located]) -> Result<(), String> {
+    let names = names::resolve(ast);
+    for warning in names.warnings.iter().chain(&checker::check(ast)) {
+        eprintln!("{}:{}: warning: {}", file_path, warning.line, warning.message);
+    }
+    for error in &names.errors {
+        eprintln!("{}:{}: error: {}", file_path, error.line, error.message);
+    }
+
+    let type_errors = types::check(ast);
+    for error in &type_errors {
+        eprintln!("{}:{}: error: {}", file_path, error.line, error.message);
+    }
+    match names.errors.len() + type_errors.len() {
+        0 => Ok(()),
+        1 => Err("Found 1 error".to_string()),
+        n => Err(format!("Found {} errors", n)),
+    }
+}
+
+fn check_file(file_path: &str) -> Result<i32, String> {
+    let path = validate_file_path(file_path)?;
     let source_code = fs::read_to_string(&path)
         .map_err(|e| format!("Error reading file {}: {}", file_path, e))?;
 
     let mut parser = Parser::new(file_path, &source_code)
         .map_err(|e| format!("Parser initialization error: {}", e))?;
+    let mut ast = parser.parse()
+        .map_err(|e| format!("Parse error: {}", e))?;
 
-    let start_parse = Instant::now();
+    check_program(file_path, &mut ast)?;
+    println!("No errors in {}", file_path);
+    Ok(0)
+}
+
+// Lint a file with the rule levels of the package containing it
+fn lint_file(file_path: &str) -> Result<i32, String> {
+    let path = validate_file_path(file_path)?;
+    let source_code = fs::read_to_string(&path)
+        .map_err(|e| format!("Error reading file {}: {}", file_path, e))?;
+
+    let mut parser = Parser::new(file_path, &source_code)
+        .map_err(|e| format!("Parser initialization error: {}", e))?;
     let ast = parser.parse()
         .map_err(|e| format!("Parse error: {}", e))?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let levels = match Project::find(base_dir)? {
+        Some(project) => lint::levels(&project.manifest.lint)
+            .map_err(|e| format!("Error: {} in {}", e, project.root.join(package::MANIFEST_FILE).display()))?,
+        None => lint::levels(&Default::default())?,
+    };
+
+    let (mut errors, mut warnings) = (0, 0);
+    for found in lint::lint(&ast, parser.comments()) {
+        let kind = match levels[found.rule] {
+            lint::Level::Allow => continue,
+            lint::Level::Warn => {
+                warnings += 1;
+                "warning"
+            }
+            lint::Level::Deny => {
+                errors += 1;
+                "error"
+            }
+        };
+        println!("{}:{}: {}: {} [{}]", file_path, found.line, kind, found.message, found.rule);
+    }
+
+    let plural = |n: usize, noun: &str| if n == 1 { format!("1 {}", noun) } else { format!("{} {}s", n, noun) };
+    match (errors, warnings) {
+        (0, 0) => println!("No problems in {}", file_path),
+        (0, warnings) => println!("Found {}", plural(warnings, "warning")),
+        (errors, 0) => return Err(format!("Found {}", plural(errors, "error"))),
+        (errors, warnings) => {
+            return Err(format!("Found {} and {}", plural(errors, "error"), plural(warnings, "warning")));
+        }
+    }
+    Ok(0)
+}
+
+// Format files in place, or with `check` only list those that aren't
+// formatted. `-` formats stdin to stdout.
+fn format_files(files: Vec<PathBuf>, check: bool) -> Result<i32, String> {
+    let files = if files.is_empty() {
+        Project::find(Path::new("."))?
+            .ok_or_else(|| format!("Error: No input file specified and no {} found", package::MANIFEST_FILE))?
+            .source_files()?
+            .into_iter()
+            .map(|file| match std::env::current_dir() {
+                Ok(dir) => file.strip_prefix(&dir).map(Path::to_path_buf).unwrap_or(file),
+                Err(_) => file,
+            })
+            .collect()
+    } else {
+        files
+    };
+
+    let mut unformatted = 0;
+    for file in files {
+        let file_path = file.to_string_lossy().into_owned();
+        if file_path == "-" {
+            let mut source_code = String::new();
+            std::io::stdin().read_to_string(&mut source_code)
+                .map_err(|e| format!("Error reading stdin: {}", e))?;
+            let formatted = format::format("<stdin>", &source_code)
+                .map_err(|e| format!("Error formatting <stdin>: {}", e))?;
+            if !check {
+                print!("{}", formatted);
+            } else if formatted != source_code {
+                println!("Would reformat <stdin>");
+                unformatted += 1;
+            }
+            continue;
+        }
+
+        let path = validate_file_path(&file_path)?;
+        let source_code = fs::read_to_string(&path)
+            .map_err(|e| format!("Error reading file {}: {}", file_path, e))?;
+        let formatted = format::format(&file_path, &source_code)
+            .map_err(|e| format!("Error formatting {}: {}", file_path, e))?;
+        if formatted == source_code {
+            continue;
+        }
+        if check {
+            println!("Would reformat {}", file_path);
+            unformatted += 1;
+        } else {
+            fs::write(&path, formatted)
+                .map_err(|e| format!("Error writing file {}: {}", file_path, e))?;
+            println!("Formatted {}", file_path);
+        }
+    }
+
+    match unformatted {
+        0 => Ok(0),
+        1 => Err("1 file is not formatted".to_string()),
+        n => Err(format!("{} files are not formatted", n)),
+    }
+}
+
+fn run_source(source_name: &str, source_code: &str, base_dir: &Path, options: &RunOptions) -> Result<i32, String> {
+    let mut parser = Parser::new(source_name, source_code)
+        .map_err(|e| format!("Parser initialization error: {}", e))?;
+
+    let start_parse = Instant::now();
+    let mut ast = parser.parse()
+        .map_err(|e| format!("Parse error: {}", e))?;
     let parse_duration = start_parse.elapsed();
 
-    if verbose {
+    if options.verbose {
         println!("Successfully parsed AST (took {:?})", parse_duration);
     }
+    check_program(source_name, &mut ast)?;
 
-    let mut interpreter = Interpreter::new();
-    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
-    interpreter.set_base_path(base_dir);
+    let mut interpreter = new_interpreter(base_dir, options)?;
 
     let start_execution = Instant::now();
-    interpreter.run(ast)
+    let result = interpreter.run(ast)
         .map_err(|e| format!("Runtime error: {}", e))?;
     let execution_duration = start_execution.elapsed();
 
-    if verbose || time_execution {
+    if options.verbose || options.time_execution {
         println!("Program executed successfully in {:?}", execution_duration);
-        if verbose {
+        if options.verbose {
             println!("Total time: {:?}", parse_duration + execution_duration);
         }
-    } else if verbose {
+    } else if options.verbose {
         println!("Program executed successfully");
     }
 
-    Ok(())
+    Ok(interpreter::exit_code(&result).into())
 }
 
-fn build_file(file_path: &str, verbose: bool, _optimize: bool, no_run: bool) -> Result<(), String> {
+// Run a program under the debug adapter, which talks to the editor over
+// stdin and stdout; problems found before it runs go to stderr
+fn debug_file(file_path: &str, options: &RunOptions) -> Result<i32, String> {
+    let path = validate_file_path(file_path)?;
+    let source_code = fs::read_to_string(&path)
+        .map_err(|e| format!("Error reading file {}: {}", file_path, e))?;
+
+    let mut parser = Parser::new(file_path, &source_code)
+        .map_err(|e| format!("Parser initialization error: {}", e))?;
+    let mut ast = parser.parse()
+        .map_err(|e| format!("Parse error: {}", e))?;
+    check_program(file_path, &mut ast)?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let interpreter = new_interpreter(base_dir, options)?;
+    debug::serve(interpreter, ast, &path)
+}
+
+fn build_file(file_path: &str, options: &RunOptions, optimize: bool, native: bool, no_run: bool) -> Result<i32, String> {
+    let verbose = options.verbose;
     let path = validate_file_path(file_path)?;
     
     if verbose {
@@ -109,13 +356,18 @@ fn build_file(file_path: &str, verbose: bool, _optimize: bool, no_run: bool) ->
         .map_err(|e| format!("Parser initialization error: {}", e))?;
 
     let start_parse = Instant::now();
-    let ast = parser.parse()
+    let mut ast = parser.parse()
         .map_err(|e| format!("Parse error: {}", e))?;
     let parse_duration = start_parse.elapsed();
 
     if verbose {
         println!("Successfully parsed AST (took {:?})", parse_duration);
     }
+    check_program(file_path, &mut ast)?;
+
+    if native {
+        return build_native(&path, &ast, optimize, no_run);
+    }
 
     // Create .kdc file
     let kdc_path = path.with_extension("kdc");
@@ -139,29 +391,48 @@ fn build_file(file_path: &str, verbose: bool, _optimize: bool, no_run: bool) ->
     // Run if --no-run is not specified
     if !no_run {
         println!("Running the compiled file...");
-        run_file(&kdc_path.to_string_lossy(), verbose, false)?;
+        return run_bytecode_file(&kdc_path.to_string_lossy(), options);
     }
 
-    Ok(())
+    Ok(0)
+}
+
+// Compile to an executable next to the source file, named after it
+fn build_native(path: &Path, ast: &[ast::Located], optimize: bool, no_run: bool) -> Result<i32, String> {
+    let executable = path.with_extension("");
+    codegen::compile_native(ast, optimize, &executable)?;
+    println!("Build successful: Native executable generated at {}", executable.display());
+
+    if no_run {
+        return Ok(0);
+    }
+    println!("Running the compiled file...");
+    // A bare file name would be looked up in PATH
+    let executable = if executable.is_relative() { Path::new(".").join(&executable) } else { executable };
+    let status = std::process::Command::new(&executable)
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", executable.display(), e))?;
+    Ok(status.code().unwrap_or(1))
 }
 
 // Add this function to run .kdc files
-fn run_bytecode_file(file_path: &str, verbose: bool) -> Result<(), String> {
+fn run_bytecode_file(file_path: &str, options: &RunOptions) -> Result<i32, String> {
     let bytecode = fs::read(file_path)
         .map_err(|e| format!("Error reading .kdc file: {}", e))?;
     
-    let ast: Vec<ast::Statement> = bincode::deserialize(&bytecode)
+    let ast: Vec<ast::Located> = bincode::deserialize(&bytecode)
         .map_err(|e| format!("Error deserializing bytecode: {}", e))?;
     
     let start_execution = Instant::now();
-    let mut interpreter = Interpreter::new();
-    interpreter.run(ast)?;
+    let base_dir = Path::new(file_path).parent().unwrap_or_else(|| Path::new("."));
+    let mut interpreter = new_interpreter(base_dir, options)?;
+    let result = interpreter.run(ast)?;
     
-    if verbose {
+    if options.verbose {
         println!("Program executed successfully in {:?}", start_execution.elapsed());
     }
     
-    Ok(())
+    Ok(interpreter::exit_code(&result).into())
 }
 
 fn repl() -> Result<(), String> {
@@ -173,10 +444,10 @@ fn repl() -> Result<(), String> {
     
     loop {
         print!("> ");
-        io::stdout().flush().map_err(|e| e.to_string())?;
+        std::io::stdout().flush().map_err(|e| e.to_string())?;
         
         let mut input = String::new();
-        io::stdin().read_line(&mut input).map_err(|e| e.to_string())?;
+        std::io::stdin().read_line(&mut input).map_err(|e| e.to_string())?;
         
         let input = input.trim();
         if input.is_empty() {
@@ -202,7 +473,7 @@ fn repl() -> Result<(), String> {
                 
                 match parser.parse() {
                     Ok(ast) => {
-                        if let Err(e) = interpreter.run(ast) {
+                        if let Err(e) = interpreter.run_script(&ast) {
                             println!("Runtime error: {}", e);
                         }
                     }
@@ -215,72 +486,100 @@ fn repl() -> Result<(), String> {
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    
-    if args.len() < 2 {
-        print_help();
-        return Err("Not enough arguments".into());
-    }
-
-    let command = &args[1];
-    let mut verbose = false;
-    let mut optimize = false;
-    let mut time_execution = false;
-    let mut no_run = false;
-    
-    // Process options
-    for arg in &args[2..] {
-        match arg.as_str() {
-            "--verbose" => verbose = true,
-            "--optimize" => optimize = true,
-            "--time" => time_execution = true,
-            "--no-run" => no_run = true,
-            _ => {}
-        }
-    }
-
-    match command.as_str() {
-        "run" => {
-    if args.len() < 3 {
-        return Err("Error: No input file specified".into());
-    }
-
-    let file_path = &args[2];
-
-    if file_path.ends_with(".kdc") {
-        run_bytecode_file(file_path, verbose)
-            .map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
-    } else if file_path.ends_with(".kode") {
-        run_file(file_path, verbose, time_execution)
-            .map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
-    } else {
-        return Err("Unsupported file type. Only .kode and .kdc are allowed.".into());
-    }
-}
-
-        "build" => {
-            if args.len() < 3 {
-                return Err("Error: No input file specified".into());
-            } else {
-                build_file(&args[2], verbose, optimize, no_run)
-                    .map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
+fn run_command(command: Command) -> Result<i32, String> {
+    match command {
+        Command::Run { file, eval, verbose, time, lib_path, args } => {
+            let options = RunOptions {
+                program_args: args,
+                lib_paths: lib_path,
+                verbose,
+                time_execution: time,
+            };
+
+            match (file, eval) {
+                (_, Some(source)) => run_snippet("<eval>", &source, &options),
+                (Some(file), None) => {
+                    let file_path = file.to_string_lossy();
+
+                    if file_path == "-" {
+                        let mut source = String::new();
+                        std::io::stdin().read_to_string(&mut source)
+                            .map_err(|e| format!("Error reading stdin: {}", e))?;
+                        run_snippet("<stdin>", &source, &options)
+                    } else if file_path.ends_with(".kdc") {
+                        run_bytecode_file(&file_path, &options)
+                    } else if file_path.ends_with(".kode") {
+                        run_file(&file_path, &options)
+                    } else {
+                        Err("Unsupported file type. Only .kode and .kdc are allowed.".into())
+                    }
+                }
+                (None, None) => run_file(&package_entry()?, &options),
             }
         }
-        "repl" => {
-            repl().map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
+        Command::Build { file, verbose, optimize, native, no_run, lib_path } => {
+            let options = RunOptions {
+                program_args: Vec::new(),
+                lib_paths: lib_path,
+                verbose,
+                time_execution: false,
+            };
+            let file_path = match file {
+                Some(file) => file.to_string_lossy().into_owned(),
+                None => package_entry()?,
+            };
+            build_file(&file_path, &options, optimize, native, no_run)
         }
-        "version" => {
-            print_version();
+        Command::Check { file } => {
+            let file_path = match file {
+                Some(file) => file.to_string_lossy().into_owned(),
+                None => package_entry()?,
+            };
+            check_file(&file_path)
         }
-        "help" => {
-            print_help();
+        Command::Lint { file } => {
+            let file_path = match file {
+                Some(file) => file.to_string_lossy().into_owned(),
+                None => package_entry()?,
+            };
+            lint_file(&file_path)
         }
-        _ => {
-            print_help();
-            return Err(format!("Unknown command: {}", command).into());
+        Command::Fmt { files, check } => format_files(files, check),
+        Command::Lsp => lsp::serve(),
+        Command::Debug { file, lib_path, args } => {
+            let options = RunOptions {
+                program_args: args,
+                lib_paths: lib_path,
+                verbose: false,
+                time_execution: false,
+            };
+            debug_file(&file.to_string_lossy(), &options)
         }
-    };
+        Command::Init { path, name } => init_package(&path, name).map(|()| 0),
+        Command::Add { name, path, git, rev, version } => {
+            add_dependency(&name, path, git, rev, version).map(|()| 0)
+        }
+        Command::Install => install_dependencies().map(|()| 0),
+        Command::Repl => repl().map(|()| 0),
+        Command::Version => {
+            print_version();
+            Ok(0)
+        }
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "kode", &mut std::io::stdout());
+            Ok(0)
+        }
+    }
+}
 
-    Ok(())
-}
\ No newline at end of file
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run_command(cli.command) {
+        Ok(code) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
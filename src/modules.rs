@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding extra module directories, separated like `PATH`
+pub const KODE_PATH: &str = "KODE_PATH";
+
+/// Directories listed in `KODE_PATH`
+pub fn kode_path() -> Vec<PathBuf> {
+    env::var_os(KODE_PATH)
+        .map(|paths| {
+            env::split_paths(&paths)
+                .filter(|p| !p.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Finds the file behind an import path such as `util.strings`
+#[derive(Debug, Clone, Default)]
+pub struct ModuleResolver {
+    search_paths: Vec<PathBuf>,
+    packages: HashMap<String, PathBuf>, // Package name -> entry file
+}
+
+impl ModuleResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a directory to search after the program's own directories
+    pub fn add_search_path(&mut self, path: PathBuf) {
+        self.search_paths.push(path);
+    }
+
+    /// Make a resolved package importable: `import name;` loads its entry
+    /// file and `import name.sub;` loads `sub.kode` next to the entry
+    pub fn add_package(&mut self, name: String, entry: PathBuf) {
+        self.packages.insert(name, entry);
+    }
+
+    /// Resolve `module` to a canonical file path. `local_dirs` (the importing
+    /// file's directory, then the program's) are searched first, then the
+    /// packages, then the search paths in order.
+    pub fn resolve(&self, module: &str, local_dirs: &[&Path]) -> Result<PathBuf, String> {
+        let relative = module_file(module);
+        let mut tried: Vec<PathBuf> = Vec::new();
+
+        for dir in local_dirs {
+            let candidate = dir.join(&relative);
+            if candidate.is_file() {
+                return canonical(module, &candidate);
+            }
+            if !tried.contains(&candidate) {
+                tried.push(candidate);
+            }
+        }
+
+        let (package, rest) = module.split_once('.').unwrap_or((module, ""));
+        if let Some(entry) = self.packages.get(package) {
+            let candidate = if rest.is_empty() {
+                entry.clone()
+            } else {
+                entry
+                    .parent()
+                    .unwrap_or_else(|| Path::new("."))
+                    .join(module_file(rest))
+            };
+            if candidate.is_file() {
+                return canonical(module, &candidate);
+            }
+            tried.push(candidate);
+        }
+
+        for dir in &self.search_paths {
+            let candidate = dir.join(&relative);
+            if tried.contains(&candidate) {
+                continue;
+            }
+            if candidate.is_file() {
+                return canonical(module, &candidate);
+            }
+            tried.push(candidate);
+        }
+
+        let tried: Vec<String> = tried.iter().map(|p| format!("  {}", p.display())).collect();
+        Err(format!(
+            "Module '{}' not found. Searched:\n{}",
+            module,
+            tried.join("\n")
+        ))
+    }
+}
+
+fn canonical(module: &str, path: &Path) -> Result<PathBuf, String> {
+    path.canonicalize()
+        .map_err(|e| format!("Error resolving module '{}': {}", module, e))
+}
+
+// `util.strings` -> `util/strings.kode`
+fn module_file(module: &str) -> PathBuf {
+    let mut path: PathBuf = module.split('.').collect();
+    path.set_extension("kode");
+    path
+}
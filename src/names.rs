@@ -0,0 +1,584 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::{ArmBody, Expression, InterpolationPart, Located, MatchArm, Statement};
+use crate::interpreter::BUILTINS;
+use crate::types::pattern_bindings;
+
+/// A problem found in a program before running it, with the line of the
+/// statement it is in
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Problems found while resolving names. Errors stop the program from running.
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Resolve the names of a program: report undefined variables and functions,
+/// functions defined twice, calls with the wrong number of arguments,
+/// variables used before their `let` and variables that shadow others.
+///
+/// Each identifier that names a variable is annotated with the index of the
+/// scope that defines it in `Interpreter::variables`, which mirrors the scopes
+/// the interpreter enters: one for the globals, one per function call,
+/// closure call, block, `for` loop, `for`-`in` iteration and `match` arm.
+/// `if`, `while` and `try` bodies run in the scope around them.
+pub fn resolve(program: &mut [Located]) -> Diagnostics {
+    let mut resolver = Resolver {
+        functions: HashMap::new(),
+        prefixes: HashSet::new(),
+        imported: HashSet::new(),
+        has_imports: false,
+        globals: HashSet::new(),
+        scopes: vec![HashSet::new()],
+        locals_from: 0,
+        declared: HashSet::new(),
+        later: declared_names(program),
+        function: None,
+        line: 1,
+        silent: 0,
+        diagnostics: Diagnostics {
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        },
+    };
+
+    let mut methods = HashSet::new();
+    for stmt in program.iter() {
+        resolver.line = stmt.line;
+        match &stmt.statement {
+            Statement::FunctionDef {
+                is_main,
+                name,
+                params,
+                ..
+            } => {
+                let name = if *is_main { "main" } else { name };
+                if resolver
+                    .functions
+                    .insert(name.to_string(), params.len())
+                    .is_some()
+                {
+                    resolver.error(format!("Function '{}' is defined more than once", name));
+                }
+            }
+            Statement::Impl {
+                name,
+                methods: defs,
+            } => {
+                for def in defs {
+                    if let Statement::FunctionDef { name: method, .. } = &def.statement {
+                        let method = format!("{}.{}", name, method);
+                        if !methods.insert(method.clone()) {
+                            resolver
+                                .error(format!("Method '{}' is defined more than once", method));
+                        }
+                    }
+                }
+            }
+            Statement::Struct { name, .. } | Statement::Enum { name, .. } => {
+                resolver.prefixes.insert(name.clone());
+            }
+            Statement::Import { module, alias } => {
+                // Functions of imported modules can be called without the alias
+                resolver.has_imports = true;
+                let alias = alias.as_ref().unwrap_or(module);
+                resolver
+                    .prefixes
+                    .insert(alias.split('.').next().unwrap_or_default().to_string());
+            }
+            Statement::ImportFrom { names, .. } => resolver.imported.extend(names.iter().cloned()),
+            _ => {}
+        }
+    }
+    global_names(program, &mut resolver.globals);
+    resolver.statements(program);
+
+    resolver.diagnostics
+}
+
+struct Resolver {
+    functions: HashMap<String, usize>, // Top-level functions -> number of parameters
+    prefixes: HashSet<String>,         // Structs, enums and imported modules, used before a '.'
+    imported: HashSet<String>,         // Names from `from module import ...`
+    has_imports: bool,
+    globals: HashSet<String>,     // Variables the top-level code declares
+    scopes: Vec<HashSet<String>>, // Variables declared so far, indexed like `Interpreter::variables`
+    locals_from: usize,           // First scope of the current function's own variables
+    declared: HashSet<String>,    // Variables the current function has declared so far
+    later: HashSet<String>,       // Variables the current function declares anywhere
+    function: Option<String>,     // Function being resolved, for messages
+    line: usize,                  // Line of the statement being resolved
+    silent: usize,                // Nonzero while collecting declarations without reporting
+    diagnostics: Diagnostics,
+}
+
+impl Resolver {
+    fn location(&self) -> String {
+        match &self.function {
+            Some(function) => format!(" in function '{}'", function),
+            None => String::new(),
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        if self.silent == 0 {
+            let message = format!("{}{}", message, self.location());
+            self.diagnostics.errors.push(Diagnostic {
+                line: self.line,
+                message,
+            });
+        }
+    }
+
+    fn warning(&mut self, message: String) {
+        if self.silent == 0 {
+            let message = format!("{}{}", message, self.location());
+            self.diagnostics.warnings.push(Diagnostic {
+                line: self.line,
+                message,
+            });
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        let innermost = self.scopes.len() - 1;
+        let outer = self
+            .scopes
+            .get(self.locals_from..innermost)
+            .unwrap_or_default();
+        if outer.iter().any(|scope| scope.contains(name)) {
+            self.warning(format!(
+                "Variable '{}' shadows a variable of an enclosing scope",
+                name
+            ));
+        }
+        self.scopes[innermost].insert(name.to_string());
+        self.declared.insert(name.to_string());
+    }
+
+    // The index of the scope defining variable `name`, reporting it if there is none
+    fn variable(&mut self, name: &str) -> Option<usize> {
+        if let Some(index) = self.scopes.iter().rposition(|scope| scope.contains(name)) {
+            return Some(index);
+        }
+        if self.imported.contains(name) {
+            return None;
+        }
+
+        if self.declared.contains(name) {
+            self.error(format!(
+                "Variable '{}' is used outside the scope that declares it",
+                name
+            ));
+        } else if self.later.contains(name) {
+            self.error(format!("Variable '{}' is used before its 'let'", name));
+        } else {
+            self.error(format!("Undefined variable '{}'", name));
+        }
+        None
+    }
+
+    // Resolve the statements in a new scope
+    fn block(&mut self, statements: &mut [Located]) {
+        self.scopes.push(HashSet::new());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &mut [Located]) {
+        let outer = self.line;
+        for stmt in statements {
+            self.line = stmt.line;
+            self.statement(stmt);
+        }
+        self.line = outer;
+    }
+
+    // Variables a loop body declares are still there when the next iteration
+    // starts, so they are collected before anything in the loop is reported
+    fn predeclare(&mut self, body: &mut [Located], update: Option<&mut Statement>) {
+        self.silent += 1;
+        self.statements(body);
+        if let Some(update) = update {
+            self.statement(update);
+        }
+        self.silent -= 1;
+    }
+
+    fn statement(&mut self, stmt: &mut Statement) {
+        match stmt {
+            Statement::Let { name, value, .. } => {
+                self.expression(value);
+                self.declare(name);
+            }
+            Statement::Assign { name, value } => {
+                self.expression(value);
+                self.variable(name);
+            }
+            Statement::FunctionDef {
+                is_main,
+                name,
+                params,
+                body,
+                ..
+            } => {
+                let name = if *is_main {
+                    "main".to_string()
+                } else {
+                    name.clone()
+                };
+                self.function_body(name, params, body);
+            }
+            Statement::Impl { name, methods } => {
+                for method in methods {
+                    self.line = method.line;
+                    if let Statement::FunctionDef {
+                        name: method,
+                        params,
+                        body,
+                        ..
+                    } = &mut method.statement
+                    {
+                        self.function_body(format!("{}.{}", name, method), params, body);
+                    }
+                }
+            }
+            Statement::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+            }
+            Statement::Return(expr)
+            | Statement::ExprStmt(expr)
+            | Statement::Print(expr)
+            | Statement::Expression(expr) => self.expression(expr),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Statement::While { condition, body } => {
+                self.predeclare(body, None);
+                self.expression(condition);
+                self.statements(body);
+            }
+            Statement::For {
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                self.scopes.push(HashSet::new());
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                self.predeclare(body, update.as_deref_mut());
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                self.statements(body);
+                if let Some(update) = update {
+                    self.statement(update);
+                }
+                self.scopes.pop();
+            }
+            Statement::ForIn {
+                names,
+                iterable,
+                body,
+            } => {
+                self.expression(iterable);
+                self.scopes.push(HashSet::new());
+                for name in names.iter() {
+                    self.declare(name);
+                }
+                self.statements(body);
+                self.scopes.pop();
+            }
+            Statement::Block(statements) => self.block(statements),
+            Statement::Try { body, catch } => {
+                self.statements(body);
+                self.statements(catch);
+            }
+            Statement::Struct { .. }
+            | Statement::Enum { .. }
+            | Statement::Import { .. }
+            | Statement::ImportFrom { .. } => {}
+        }
+    }
+
+    // A function sees the globals and its parameters, never the variables of
+    // the code around it
+    fn function_body(&mut self, name: String, params: &[String], body: &mut [Located]) {
+        let outer_function = self.function.replace(name);
+        let outer_scopes =
+            std::mem::replace(&mut self.scopes, vec![self.globals.clone(), HashSet::new()]);
+        let outer_locals_from = std::mem::replace(&mut self.locals_from, 1);
+        let outer_declared = std::mem::take(&mut self.declared);
+        let outer_later = std::mem::replace(&mut self.later, declared_names(body));
+
+        for param in params {
+            self.declare(param);
+        }
+        self.statements(body);
+
+        self.function = outer_function;
+        self.scopes = outer_scopes;
+        self.locals_from = outer_locals_from;
+        self.declared = outer_declared;
+        self.later = outer_later;
+    }
+
+    fn arms(&mut self, arms: &mut [MatchArm]) {
+        for arm in arms {
+            self.scopes.push(HashSet::new());
+            let mut bindings = Vec::new();
+            pattern_bindings(&arm.pattern, &mut bindings);
+            for binding in &bindings {
+                self.declare(binding);
+            }
+            if let Some(guard) = &mut arm.guard {
+                self.expression(guard);
+            }
+            match &mut arm.body {
+                ArmBody::Expression(expr) => self.expression(expr),
+                ArmBody::Block(statements) => self.statements(statements),
+            }
+            self.scopes.pop();
+        }
+    }
+
+    fn expression(&mut self, expr: &mut Expression) {
+        match expr {
+            Expression::Identifier(name, scope) => *scope = self.variable(name),
+            Expression::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary { expr, .. } => self.expression(expr),
+            Expression::CompoundAssign { target, value, .. } => {
+                self.expression(target);
+                self.expression(value);
+            }
+            Expression::Range { start, end } => {
+                self.expression(start);
+                self.expression(end);
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expression::Call { callee, arguments } => {
+                match callee.as_mut() {
+                    Expression::Identifier(name, scope) => {
+                        *scope = self.call(name, arguments.len())
+                    }
+                    Expression::FieldAccess { object, .. } => self.prefix(object),
+                    callee => self.expression(callee),
+                }
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Array { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expression::Map { entries } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+            }
+            Expression::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+            }
+            Expression::Interpolation { parts } => {
+                for part in parts {
+                    if let InterpolationPart::Expression { expr, .. } = part {
+                        self.expression(expr);
+                    }
+                }
+            }
+            Expression::ArrayAccess { array, index } => {
+                self.expression(array);
+                self.expression(index);
+            }
+            Expression::FieldAccess { object, .. } => self.prefix(object),
+            // A closure sees the scopes it was created in, plus one for its parameters
+            Expression::Closure { params, body } => {
+                self.scopes.push(HashSet::new());
+                for param in params.iter() {
+                    self.declare(param);
+                }
+                self.statements(body);
+                self.scopes.pop();
+            }
+            Expression::Number(_)
+            | Expression::BigInt(_)
+            | Expression::Float(_)
+            | Expression::Bool(_)
+            | Expression::String(_) => {}
+        }
+    }
+
+    // The object of `object.field`, which may also name a struct, an enum or a
+    // module. Structs and enums of imported modules can be used unqualified.
+    fn prefix(&mut self, object: &mut Expression) {
+        match object {
+            Expression::Identifier(name, scope) => {
+                let defined = self
+                    .scopes
+                    .iter()
+                    .any(|scope| scope.contains(name.as_str()));
+                if defined || !(self.prefixes.contains(name.as_str()) || self.has_imports) {
+                    *scope = self.variable(name);
+                }
+            }
+            Expression::FieldAccess { object, .. } => self.prefix(object),
+            object => self.expression(object),
+        }
+    }
+
+    // A call by name goes to a variable holding a closure if one is in scope,
+    // and to a function otherwise. Returns the scope of that variable.
+    fn call(&mut self, name: &str, args: usize) -> Option<usize> {
+        // Assignments to elements and fields
+        if name == "__array_assign" || name == "__field_assign" {
+            return None;
+        }
+        if self.scopes.iter().any(|scope| scope.contains(name)) {
+            return self.variable(name);
+        }
+
+        match self.functions.get(name) {
+            Some(&params) if params != args => {
+                self.error(format!(
+                    "Function '{}' expects {} args, got {}",
+                    name, params, args
+                ));
+            }
+            Some(_) => {}
+            None if BUILTINS.contains(&name)
+                || self.imported.contains(name)
+                || self.has_imports => {}
+            None => self.error(format!("Undefined function '{}'", name)),
+        }
+        None
+    }
+}
+
+// Variables declared in the global scope: by top-level `let`s, including
+// those inside `if`, `while` and `try`, which run in the scope around them
+fn global_names(statements: &[Located], names: &mut HashSet<String>) {
+    for stmt in statements {
+        match &stmt.statement {
+            Statement::Let { name, .. } => {
+                names.insert(name.clone());
+            }
+            Statement::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                global_names(then_branch, names);
+                if let Some(else_branch) = else_branch {
+                    global_names(else_branch, names);
+                }
+            }
+            Statement::While { body, .. } => global_names(body, names),
+            Statement::Try { body, catch } => {
+                global_names(body, names);
+                global_names(catch, names);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Every variable declared by `statements` in any scope, not counting nested
+// functions and closures
+fn declared_names(statements: &[Located]) -> HashSet<String> {
+    fn collect(statements: &[Located], names: &mut HashSet<String>) {
+        for stmt in statements {
+            match &stmt.statement {
+                Statement::Let { name, .. } => {
+                    names.insert(name.clone());
+                }
+                Statement::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    collect(then_branch, names);
+                    if let Some(else_branch) = else_branch {
+                        collect(else_branch, names);
+                    }
+                }
+                Statement::While { body, .. } | Statement::Block(body) => collect(body, names),
+                Statement::For { init, body, .. } => {
+                    if let Some(Statement::Let { name, .. }) = init.as_deref() {
+                        names.insert(name.clone());
+                    }
+                    collect(body, names);
+                }
+                Statement::ForIn {
+                    names: loop_names,
+                    body,
+                    ..
+                } => {
+                    names.extend(loop_names.iter().cloned());
+                    collect(body, names);
+                }
+                Statement::Try { body, catch } => {
+                    collect(body, names);
+                    collect(catch, names);
+                }
+                Statement::Match { arms, .. } => {
+                    for arm in arms {
+                        let mut bindings = Vec::new();
+                        pattern_bindings(&arm.pattern, &mut bindings);
+                        names.extend(bindings);
+                        if let ArmBody::Block(body) = &arm.body {
+                            collect(body, names);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    collect(statements, &mut names);
+    names
+}
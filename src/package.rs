@@ -0,0 +1,812 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lint::Level;
+
+/// File name of a package manifest
+pub const MANIFEST_FILE: &str = "kode.toml";
+/// File name of the lockfile written next to the manifest
+pub const LOCK_FILE: &str = "kode.lock";
+/// Environment variable overriding the local registry directory
+pub const KODE_REGISTRY: &str = "KODE_REGISTRY";
+
+const DEFAULT_ENTRY: &str = "src/main.kode";
+
+/// Contents of `kode.toml`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub package: PackageInfo,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(default)]
+    pub registry: Option<RegistryConfig>,
+    #[serde(default)]
+    pub lint: BTreeMap<String, Level>, // Rule name -> level
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version: String,
+    #[serde(default = "default_entry")]
+    pub entry: String,
+}
+
+fn default_entry() -> String {
+    DEFAULT_ENTRY.to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryConfig {
+    pub path: String,
+}
+
+/// A dependency is either a registry version requirement (`json = "1.2"`)
+/// or a table naming where to find it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Dependency {
+    Version(String),
+    Detailed(DependencyDetail),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DependencyDetail {
+    pub version: Option<String>,
+    pub path: Option<String>,
+    pub git: Option<String>,
+    pub rev: Option<String>,
+}
+
+/// A package found by the manifest lookup
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// Find `kode.toml` in `dir` or its nearest ancestor
+    pub fn find(dir: &Path) -> Result<Option<Project>, String> {
+        // `Path::parent` of a bare file name is empty
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("Error reading directory {}: {}", dir.display(), e))?;
+
+        for ancestor in dir.ancestors() {
+            if ancestor.join(MANIFEST_FILE).is_file() {
+                return Project::load(ancestor).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn load(root: &Path) -> Result<Project, String> {
+        Ok(Project {
+            root: root.to_path_buf(),
+            manifest: read_manifest(root)?,
+        })
+    }
+
+    pub fn entry(&self) -> PathBuf {
+        self.root.join(&self.manifest.package.entry)
+    }
+
+    /// Every .kode file of the package, skipping hidden directories such as
+    /// the `.kode` checkout of git dependencies
+    pub fn source_files(&self) -> Result<Vec<PathBuf>, String> {
+        fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+            let entries = fs::read_dir(dir)
+                .map_err(|e| format!("Error reading directory {}: {}", dir.display(), e))?;
+            for entry in entries {
+                let path = entry
+                    .map_err(|e| format!("Error reading directory {}: {}", dir.display(), e))?
+                    .path();
+                let hidden = path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                if hidden {
+                    continue;
+                }
+                if path.is_dir() {
+                    walk(&path, files)?;
+                } else if path.extension().is_some_and(|ext| ext == "kode") {
+                    files.push(path);
+                }
+            }
+            Ok(())
+        }
+
+        let mut files = Vec::new();
+        walk(&self.root, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// Resolve all dependencies, reusing the choices recorded in `kode.lock`
+    /// and rewriting it when they change. Git dependencies are cloned as needed.
+    pub fn resolve(&self) -> Result<Vec<ResolvedPackage>, String> {
+        let lock_path = self.root.join(LOCK_FILE);
+        let lock = self.read_lock()?.unwrap_or_default();
+
+        let resolved = Resolver::new(self, &lock, Mode::Update).resolve()?;
+
+        let new_lock = Lockfile::of(&resolved);
+        if new_lock != lock || !lock_path.is_file() {
+            let text = toml::to_string(&new_lock)
+                .map_err(|e| format!("Failed to serialize lockfile: {}", e))?;
+            fs::write(
+                &lock_path,
+                format!(
+                    "# This file is generated by kode. Do not edit it by hand.\n\n{}",
+                    text
+                ),
+            )
+            .map_err(|e| format!("Failed to write {}: {}", lock_path.display(), e))?;
+        }
+
+        Ok(resolved)
+    }
+
+    /// The dependencies recorded in `kode.lock`, for running the package:
+    /// nothing is fetched and the lock is not written. A lock that is missing
+    /// or no longer matches the manifests is an error until `kode install`.
+    pub fn resolve_locked(&self) -> Result<Vec<ResolvedPackage>, String> {
+        let lock = match self.read_lock()? {
+            Some(lock) => lock,
+            None if self.manifest.dependencies.is_empty() => return Ok(Vec::new()),
+            None => return Err(format!("{} is missing; run `kode install`", LOCK_FILE)),
+        };
+
+        let resolved = Resolver::new(self, &lock, Mode::Locked).resolve()?;
+        if Lockfile::of(&resolved) != lock {
+            return Err(stale_lock());
+        }
+        Ok(resolved)
+    }
+
+    fn read_lock(&self) -> Result<Option<Lockfile>, String> {
+        let path = self.root.join(LOCK_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        toml::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Add a dependency line to the manifest's `[dependencies]` table,
+    /// keeping the rest of the file untouched. The manifest is only written
+    /// once the new dependency resolves.
+    pub fn add_dependency(
+        &mut self,
+        name: &str,
+        spec: &str,
+    ) -> Result<Vec<ResolvedPackage>, String> {
+        if !is_valid_name(name) {
+            return Err(invalid_dependency_name(name));
+        }
+        if self.manifest.dependencies.contains_key(name) {
+            return Err(format!(
+                "'{}' is already a dependency of '{}'",
+                name, self.manifest.package.name
+            ));
+        }
+
+        let path = self.root.join(MANIFEST_FILE);
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        let line = format!("{} = {}", name, spec);
+
+        let mut lines: Vec<&str> = text.lines().collect();
+        match lines.iter().position(|l| l.trim() == "[dependencies]") {
+            Some(header) => {
+                // Insert after the last entry of the table
+                let mut end = header + 1;
+                while end < lines.len() && !lines[end].trim_start().starts_with('[') {
+                    end += 1;
+                }
+                while end > header + 1 && lines[end - 1].trim().is_empty() {
+                    end -= 1;
+                }
+                lines.insert(end, &line);
+            }
+            None => {
+                if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    lines.push("");
+                }
+                lines.push("[dependencies]");
+                lines.push(&line);
+            }
+        }
+
+        let text = lines.join("\n") + "\n";
+        let manifest =
+            parse_manifest(&text).map_err(|e| format!("Invalid dependency '{}': {}", line, e))?;
+        let previous = std::mem::replace(&mut self.manifest, manifest);
+        let resolved = match self.resolve() {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                self.manifest = previous;
+                return Err(e);
+            }
+        };
+
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(resolved)
+    }
+}
+
+fn read_manifest(root: &Path) -> Result<Manifest, String> {
+    let path = root.join(MANIFEST_FILE);
+    let text = fs::read_to_string(&path)
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    parse_manifest(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+}
+
+// Dependency names become directory names and git URLs and revisions are
+// passed to git, which would read one starting with `-` as an option
+fn parse_manifest(text: &str) -> Result<Manifest, String> {
+    let manifest: Manifest = toml::from_str(text).map_err(|e| e.to_string())?;
+    for (name, dependency) in &manifest.dependencies {
+        if !is_valid_name(name) {
+            return Err(invalid_dependency_name(name));
+        }
+        if let Dependency::Detailed(detail) = dependency
+            && let Some(value) = [&detail.git, &detail.rev]
+                .into_iter()
+                .flatten()
+                .find(|v| v.starts_with('-'))
+        {
+            return Err(format!(
+                "Dependency '{}' has '{}', which must not start with '-'",
+                name, value
+            ));
+        }
+    }
+    Ok(manifest)
+}
+
+fn invalid_dependency_name(name: &str) -> String {
+    format!(
+        "Invalid dependency name '{}': use letters, digits and '_'",
+        name
+    )
+}
+
+fn stale_lock() -> String {
+    format!("{} is out of date; run `kode install`", LOCK_FILE)
+}
+
+/// Create a new package in `dir`: a manifest and a hello world entry file
+pub fn init(dir: &Path, name: Option<String>) -> Result<Project, String> {
+    if dir.join(MANIFEST_FILE).exists() {
+        return Err(format!(
+            "{} already exists in {}",
+            MANIFEST_FILE,
+            dir.display()
+        ));
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let name = match name {
+        Some(name) => name,
+        None => dir
+            .canonicalize()
+            .ok()
+            .and_then(|d| d.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .ok_or_else(|| "Cannot derive a package name; pass --name".to_string())?,
+    };
+    if !is_valid_name(&name) {
+        return Err(format!(
+            "Invalid package name '{}': use letters, digits and '_'",
+            name
+        ));
+    }
+
+    let manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nentry = \"{}\"\n\n[dependencies]\n",
+        name, DEFAULT_ENTRY
+    );
+    fs::write(dir.join(MANIFEST_FILE), manifest)
+        .map_err(|e| format!("Failed to write {}: {}", MANIFEST_FILE, e))?;
+
+    let entry = dir.join(DEFAULT_ENTRY);
+    if !entry.exists() {
+        if let Some(parent) = entry.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&entry, "fn main() {\n    print \"Hello, World!\";\n}\n")
+            .map_err(|e| format!("Failed to write {}: {}", entry.display(), e))?;
+    }
+
+    Project::load(dir)
+}
+
+// Package names double as the first segment of import paths
+fn is_valid_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A dependency pinned to a concrete version and location
+#[derive(Debug, Clone)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Source,
+    pub root: PathBuf,
+    pub entry: PathBuf,
+}
+
+impl ResolvedPackage {
+    fn lock_entry(&self) -> LockEntry {
+        LockEntry {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            source: self.source.to_string(),
+        }
+    }
+}
+
+/// Where a dependency comes from, as recorded in `kode.lock`. Paths on disk
+/// are relative to the project root, so the lock is the same on every machine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Path(String),
+    Git { url: String, commit: String },
+    Registry,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Path(path) => write!(f, "path+{}", path),
+            Source::Git { url, commit } => write!(f, "git+{}#{}", url, commit),
+            Source::Registry => write!(f, "registry"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockEntry>,
+}
+
+impl Lockfile {
+    fn of(resolved: &[ResolvedPackage]) -> Lockfile {
+        Lockfile {
+            package: resolved.iter().map(ResolvedPackage::lock_entry).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LockEntry {
+    name: String,
+    version: String,
+    source: String,
+}
+
+// Whether resolving may clone git dependencies and choose versions the lock
+// doesn't have, or must find everything as locked
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Update,
+    Locked,
+}
+
+struct Resolver<'a> {
+    project: &'a Project,
+    root: PathBuf, // Canonical project root, which recorded paths are relative to
+    locked: HashMap<&'a str, &'a LockEntry>,
+    registry: PathBuf,
+    mode: Mode,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(project: &'a Project, lock: &'a Lockfile, mode: Mode) -> Self {
+        let registry = match (&project.manifest.registry, env::var_os(KODE_REGISTRY)) {
+            (Some(config), _) => project.root.join(&config.path),
+            (None, Some(path)) => PathBuf::from(path),
+            (None, None) => env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default()
+                .join(".kode")
+                .join("registry"),
+        };
+
+        Resolver {
+            project,
+            root: project
+                .root
+                .canonicalize()
+                .unwrap_or_else(|_| project.root.clone()),
+            locked: lock
+                .package
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry))
+                .collect(),
+            registry,
+            mode,
+        }
+    }
+
+    // Breadth-first over the dependency graph. Every package name resolves
+    // to exactly one version; two different requirements for it are an error.
+    fn resolve(&self) -> Result<Vec<ResolvedPackage>, String> {
+        let mut resolved: BTreeMap<String, ResolvedPackage> = BTreeMap::new();
+        let mut queue: VecDeque<(String, Dependency, PathBuf, String)> = self
+            .project
+            .manifest
+            .dependencies
+            .iter()
+            .map(|(name, dep)| {
+                (
+                    name.clone(),
+                    dep.clone(),
+                    self.project.root.clone(),
+                    self.project.manifest.package.name.clone(),
+                )
+            })
+            .collect();
+
+        while let Some((name, dependency, declared_in, required_by)) = queue.pop_front() {
+            let package = self
+                .resolve_one(&name, &dependency, &declared_in)
+                .map_err(|e| {
+                    format!(
+                        "Failed to resolve dependency '{}' of '{}': {}",
+                        name, required_by, e
+                    )
+                })?;
+
+            if let Some(existing) = resolved.get(&name) {
+                if existing.source != package.source || existing.version != package.version {
+                    return Err(format!(
+                        "Conflicting versions of '{}': {} from {} and {} from {}",
+                        name, existing.version, existing.source, package.version, package.source
+                    ));
+                }
+                continue;
+            }
+
+            let manifest = read_manifest(&package.root)?;
+            for (dep_name, dep) in manifest.dependencies {
+                queue.push_back((dep_name, dep, package.root.clone(), name.clone()));
+            }
+            resolved.insert(name, package);
+        }
+
+        Ok(resolved.into_values().collect())
+    }
+
+    fn resolve_one(
+        &self,
+        name: &str,
+        dependency: &Dependency,
+        declared_in: &Path,
+    ) -> Result<ResolvedPackage, String> {
+        let (source, root) = match dependency {
+            Dependency::Detailed(DependencyDetail {
+                path: Some(path), ..
+            }) => {
+                let root = declared_in
+                    .join(path)
+                    .canonicalize()
+                    .map_err(|e| format!("Cannot read path '{}': {}", path, e))?;
+                (Source::Path(relative_path(&root, &self.root)), root)
+            }
+            Dependency::Detailed(DependencyDetail {
+                git: Some(url),
+                rev,
+                ..
+            }) => self.checkout_git(name, url, rev.as_deref(), declared_in)?,
+            Dependency::Version(requirement)
+            | Dependency::Detailed(DependencyDetail {
+                version: Some(requirement),
+                ..
+            }) => self.find_in_registry(name, requirement)?,
+            Dependency::Detailed(_) => {
+                return Err("Expected a version, 'path' or 'git'".to_string());
+            }
+        };
+
+        let manifest = read_manifest(&root)?;
+        if manifest.package.name != name {
+            return Err(format!(
+                "Package at {} is named '{}'",
+                root.display(),
+                manifest.package.name
+            ));
+        }
+        if let Some(requirement) = version_requirement(dependency)
+            && !version_matches(requirement, &manifest.package.version)?
+        {
+            return Err(format!(
+                "Version {} does not match '{}'",
+                manifest.package.version, requirement
+            ));
+        }
+
+        Ok(ResolvedPackage {
+            name: name.to_string(),
+            version: manifest.package.version.clone(),
+            entry: root.join(&manifest.package.entry),
+            source,
+            root,
+        })
+    }
+
+    // Check out a git repository at the locked commit, or at `rev` (default
+    // `HEAD`) when the lockfile has no matching entry. Relative URLs name
+    // repositories on disk relative to the declaring package, and are
+    // recorded relative to the project root.
+    fn checkout_git(
+        &self,
+        name: &str,
+        url: &str,
+        rev: Option<&str>,
+        declared_in: &Path,
+    ) -> Result<(Source, PathBuf), String> {
+        let (clone_url, url) = if !url.contains("://") && Path::new(url).is_relative() {
+            let path = declared_in
+                .join(url)
+                .canonicalize()
+                .map_err(|e| format!("Cannot read repository '{}': {}", url, e))?;
+            (
+                path.to_string_lossy().into_owned(),
+                relative_path(&path, &self.root),
+            )
+        } else {
+            (url.to_string(), url.to_string())
+        };
+
+        let locked_commit = self
+            .locked
+            .get(name)
+            .and_then(|entry| entry.source.strip_prefix("git+"))
+            .and_then(|source| source.rsplit_once('#'))
+            .filter(|(locked_url, commit)| {
+                *locked_url == url && commit.chars().all(|c| c.is_ascii_hexdigit())
+            })
+            .map(|(_, commit)| commit.to_string());
+
+        let git_dir = self.project.root.join(".kode").join("git");
+        let checkout_for =
+            |commit: &str| git_dir.join(format!("{}-{}", name, &commit[..commit.len().min(12)]));
+
+        if let Some(commit) = &locked_commit {
+            let checkout = checkout_for(commit);
+            if checkout.join(MANIFEST_FILE).is_file() {
+                return Ok((
+                    Source::Git {
+                        url,
+                        commit: commit.clone(),
+                    },
+                    checkout,
+                ));
+            }
+        }
+
+        if self.mode == Mode::Locked {
+            return match locked_commit {
+                Some(commit) => Err(format!(
+                    "Commit {} is not checked out; run `kode install`",
+                    commit
+                )),
+                None => Err(stale_lock()),
+            };
+        }
+
+        // Clone into a staging directory, then move it to its commit's place
+        let staging = git_dir.join(format!("{}-staging", name));
+        let _ = fs::remove_dir_all(&staging);
+        git(
+            None,
+            &[
+                "clone",
+                "--quiet",
+                "--",
+                &clone_url,
+                &staging.to_string_lossy(),
+            ],
+        )?;
+
+        // A fresh clone only has a local branch for the default branch; the
+        // others are `origin/` branches
+        let target = locked_commit.as_deref().or(rev).unwrap_or("HEAD");
+        let commit = [target.to_string(), format!("origin/{}", target)]
+            .iter()
+            .find_map(|rev| {
+                git(
+                    Some(&staging),
+                    &[
+                        "rev-parse",
+                        "--verify",
+                        "--quiet",
+                        &format!("{}^{{commit}}", rev),
+                    ],
+                )
+                .ok()
+            })
+            .ok_or_else(|| format!("Revision '{}' not found", target))?;
+        git(
+            Some(&staging),
+            &["checkout", "--quiet", "--detach", &commit],
+        )?;
+
+        let checkout = checkout_for(&commit);
+        if checkout.exists() {
+            let _ = fs::remove_dir_all(&staging);
+        } else {
+            fs::rename(&staging, &checkout)
+                .map_err(|e| format!("Failed to move checkout to {}: {}", checkout.display(), e))?;
+        }
+
+        Ok((Source::Git { url, commit }, checkout))
+    }
+
+    // Registry layout: `<registry>/<name>/<version>/kode.toml`. The locked
+    // version wins while it still satisfies the requirement; otherwise the
+    // highest matching version is chosen.
+    fn find_in_registry(&self, name: &str, requirement: &str) -> Result<(Source, PathBuf), String> {
+        let package_dir = self.registry.join(name);
+
+        if let Some(entry) = self.locked.get(name)
+            && entry.source == Source::Registry.to_string()
+            && version_matches(requirement, &entry.version)?
+        {
+            let root = package_dir.join(&entry.version);
+            if root.join(MANIFEST_FILE).is_file() {
+                return Ok((Source::Registry, root));
+            }
+        }
+        if self.mode == Mode::Locked {
+            return Err(stale_lock());
+        }
+
+        let entries = fs::read_dir(&package_dir)
+            .map_err(|_| format!("Not found in registry {}", self.registry.display()))?;
+        let mut best: Option<(Version, PathBuf)> = None;
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(version) = Version::parse(&file_name.to_string_lossy()) else {
+                continue;
+            };
+            if version_matches(requirement, &version.to_string())?
+                && best.as_ref().is_none_or(|(current, _)| version > *current)
+            {
+                best = Some((version, entry.path()));
+            }
+        }
+
+        best.map(|(_, root)| (Source::Registry, root))
+            .ok_or_else(|| {
+                format!(
+                    "No version matching '{}' in registry {}",
+                    requirement,
+                    self.registry.display()
+                )
+            })
+    }
+}
+
+fn version_requirement(dependency: &Dependency) -> Option<&str> {
+    match dependency {
+        Dependency::Version(requirement) => Some(requirement),
+        Dependency::Detailed(detail) => detail.version.as_deref(),
+    }
+}
+
+// `path` relative to `base`, both canonical, with `/` separators
+fn relative_path(path: &Path, base: &Path) -> String {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        // On different drives there is no relative path
+        return path
+            .iter()
+            .collect::<PathBuf>()
+            .to_string_lossy()
+            .into_owned();
+    }
+
+    let parts: Vec<String> = base[common..]
+        .iter()
+        .map(|_| "..".to_string())
+        .chain(
+            path[common..]
+                .iter()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect();
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+fn git(dir: Option<&Path>, args: &[&str]) -> Result<String, String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Version(u64, u64, u64);
+
+impl Version {
+    // Accepts `1`, `1.2` and `1.2.3`; missing parts are zero
+    fn parse(text: &str) -> Option<Version> {
+        let mut parts = text.split('.').map(|part| part.parse::<u64>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+        let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Version(major, minor, patch))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+// Requirements: `*` matches anything, `=1.2.3` matches exactly, and `1.2` or
+// `^1.2` matches compatible versions (same major, or same minor below 1.0)
+fn version_matches(requirement: &str, version: &str) -> Result<bool, String> {
+    let requirement = requirement.trim();
+    let version =
+        Version::parse(version).ok_or_else(|| format!("Invalid version '{}'", version))?;
+    if requirement == "*" {
+        return Ok(true);
+    }
+
+    let invalid = || format!("Invalid version requirement '{}'", requirement);
+    if let Some(exact) = requirement.strip_prefix('=') {
+        return Ok(Version::parse(exact.trim()).ok_or_else(invalid)? == version);
+    }
+
+    let minimum = Version::parse(requirement.trim_start_matches('^')).ok_or_else(invalid)?;
+    let compatible = if minimum.0 > 0 {
+        version.0 == minimum.0
+    } else {
+        version.0 == 0 && version.1 == minimum.1
+    };
+    Ok(compatible && version >= minimum)
+}
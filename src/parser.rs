@@ -1,13 +1,17 @@
 use std::path::Path;
-use crate::ast::{Statement, Expression, BinaryOp, UnaryOp};
-use crate::lexer::{Lexer, Token};
+use crate::ast::{Located, Statement, Expression, BinaryOp, UnaryOp, Variant, MatchArm, ArmBody, Pattern, InterpolationPart, Span, Type};
+use crate::lexer::{Comment, Lexer, Token, StringPart};
+use num_bigint::BigInt;
 
 pub struct Parser<'a> {
     tokens: Vec<Token>,
+    spans: Vec<Span>, // Where each token is
+    comments: Vec<Comment>,
     current: usize,
     file_path: String,
     source_code: &'a str,
     file_prefix: String, // Store file prefix for function definitions
+    allow_struct_literal: bool, // False where `Name {` opens a block instead
 }
 
 impl<'a> Parser<'a> {
@@ -23,37 +27,62 @@ impl<'a> Parser<'a> {
         
         Ok(Self {
             tokens,
+            spans: lexer.spans().to_vec(),
+            comments: lexer.comments().to_vec(),
             current: 0,
             file_path: file_path.to_string(),
             source_code,
             file_prefix: file_stem.to_string(),
+            allow_struct_literal: true,
         })
     }
     
+    /// Comments of the source code, which are not part of the AST
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+    
     // Parse a complete program
-    pub fn parse(&mut self) -> Result<Vec<Statement>, String> {
+    pub fn parse(&mut self) -> Result<Vec<Located>, String> {
         let mut statements = Vec::new();
         
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            let line = self.line();
+            statements.push(Located { line, statement: self.top_level_declaration()? });
         }
         
         Ok(statements)
     }
 
+    /// Parse source that holds a single expression, such as the condition of
+    /// a breakpoint
+    pub fn parse_expression(&mut self) -> Result<Expression, String> {
+        let expr = self.expression()?;
+        if !self.is_at_end() {
+            let (line, column) = self.position();
+            return Err(format!("Unexpected {:?} after expression at line {}, column {}", self.peek(), line, column));
+        }
+        Ok(expr)
+    }
+
     // Used for parsing imported modules
-    pub fn parse_module(&mut self) -> Result<Vec<Statement>, String> {
+    pub fn parse_module(&mut self) -> Result<Vec<Located>, String> {
         let mut statements = Vec::new();
         
         while !self.is_at_end() {
-            // Only include function definitions and imports in modules
+            // Modules only contain declarations; their `let` bindings run once at import time
             match self.peek() {
-                Token::Fn | Token::Import => {
-                    statements.push(self.declaration()?);
+                Token::Fn | Token::Let | Token::Pub | Token::Import | Token::From
+                | Token::Struct | Token::Impl | Token::Enum => {
+                    let line = self.line();
+                    statements.push(Located { line, statement: self.top_level_declaration()? });
                 },
-                _ => {
-                    // Skip other top-level statements in modules
-                    self.advance();
+                token => {
+                    let (line, column) = self.position();
+                    return Err(format!(
+                        "Only 'fn', 'let', 'struct', 'impl', 'enum' and 'import' declarations are allowed at the top level of a module, found {:?} at line {}, column {}",
+                        token, line, column
+                    ));
                 }
             }
         }
@@ -61,20 +90,121 @@ impl<'a> Parser<'a> {
         Ok(statements)
     }
     
+    // A declaration outside any block, which may be marked `pub`
+    fn top_level_declaration(&mut self) -> Result<Statement, String> {
+        if self.match_token(&[Token::Pub]) {
+            return if self.match_token(&[Token::Fn]) {
+                self.function_definition(true)
+            } else if self.match_token(&[Token::Let]) {
+                self.let_declaration(true)
+            } else {
+                let (line, column) = self.position();
+                Err(format!("Expected 'fn' or 'let' after 'pub' at line {}, column {}", line, column))
+            };
+        }
+        
+        self.declaration()
+    }
+    
     fn declaration(&mut self) -> Result<Statement, String> {
+        if self.check(&Token::Pub) {
+            let (line, column) = self.position();
+            return Err(format!("'pub' is only allowed on top-level declarations at line {}, column {}", line, column));
+        }
+        
         if self.match_token(&[Token::Let]) {
-            return self.let_declaration();
+            return self.let_declaration(false);
         } else if self.match_token(&[Token::Fn]) {
-            return self.function_definition();
+            return self.function_definition(false);
+        } else if self.match_token(&[Token::Struct]) {
+            return self.struct_declaration();
+        } else if self.match_token(&[Token::Impl]) {
+            return self.impl_declaration();
+        } else if self.match_token(&[Token::Enum]) {
+            return self.enum_declaration();
         } else if self.match_token(&[Token::Import]) {
             return self.import_declaration();
+        } else if self.match_token(&[Token::From]) {
+            return self.import_from_declaration();
         }
         
         self.statement()
     }
     
-    fn let_declaration(&mut self) -> Result<Statement, String> {
+    fn struct_declaration(&mut self) -> Result<Statement, String> {
+        let name = self.consume_identifier("Expected struct name after 'struct'")?;
+        self.consume(&Token::LBrace, "Expected '{' after struct name")?;
+        
+        let mut fields = Vec::new();
+        while !self.check(&Token::RBrace) {
+            let field = self.consume_identifier("Expected field name")?;
+            if fields.contains(&field) {
+                return Err(format!("Duplicate field '{}' in struct '{}'", field, name));
+            }
+            fields.push(field);
+            
+            if !self.match_token(&[Token::Comma]) {
+                break;
+            }
+        }
+        
+        self.consume(&Token::RBrace, "Expected '}' after struct fields")?;
+        
+        Ok(Statement::Struct { name, fields })
+    }
+    
+    fn enum_declaration(&mut self) -> Result<Statement, String> {
+        let name = self.consume_identifier("Expected enum name after 'enum'")?;
+        self.consume(&Token::LBrace, "Expected '{' after enum name")?;
+        
+        let mut variants: Vec<Variant> = Vec::new();
+        while !self.check(&Token::RBrace) {
+            let variant = self.consume_identifier("Expected variant name")?;
+            if variants.iter().any(|v| v.name == variant) {
+                return Err(format!("Duplicate variant '{}' in enum '{}'", variant, name));
+            }
+            
+            let mut fields = Vec::new();
+            if self.match_token(&[Token::LParen]) {
+                if !self.check(&Token::RParen) {
+                    fields.push(self.consume_identifier("Expected field name")?);
+                    while self.match_token(&[Token::Comma]) {
+                        fields.push(self.consume_identifier("Expected field name")?);
+                    }
+                }
+                self.consume(&Token::RParen, "Expected ')' after variant fields")?;
+            }
+            variants.push(Variant { name: variant, fields });
+            
+            if !self.match_token(&[Token::Comma]) {
+                break;
+            }
+        }
+        
+        self.consume(&Token::RBrace, "Expected '}' after enum variants")?;
+        
+        Ok(Statement::Enum { name, variants })
+    }
+    
+    fn impl_declaration(&mut self) -> Result<Statement, String> {
+        let name = self.consume_identifier("Expected struct name after 'impl'")?;
+        self.consume(&Token::LBrace, "Expected '{' after impl name")?;
+        
+        let mut methods = Vec::new();
+        while !self.check(&Token::RBrace) && !self.is_at_end() {
+            let line = self.line();
+            self.consume(&Token::Fn, "Expected 'fn' in impl block")?;
+            methods.push(Located { line, statement: self.function_definition(false)? });
+        }
+        
+        self.consume(&Token::RBrace, "Expected '}' after impl block")?;
+        
+        Ok(Statement::Impl { name, methods })
+    }
+    
+    fn let_declaration(&mut self, is_public: bool) -> Result<Statement, String> {
         let name = self.consume_identifier("Expected variable name after 'let'")?;
+        let ty = if self.match_token(&[Token::Colon]) { Some(self.parse_type()?) } else { None };
         
         let value = if self.match_token(&[Token::Equal]) {
             self.expression()?
@@ -86,11 +216,13 @@ impl<'a> Parser<'a> {
         
         Ok(Statement::Let { 
             name, 
-            value 
+            ty,
+            value,
+            is_public,
         })
     }
     
-    fn function_definition(&mut self) -> Result<Statement, String> {
+    fn function_definition(&mut self, is_public: bool) -> Result<Statement, String> {
         // Check if this is a main function definition
         let is_main = self.match_token(&[Token::Main]);
         
@@ -103,17 +235,20 @@ impl<'a> Parser<'a> {
         self.consume(&Token::LParen, "Expected '(' after function name")?;
         
         let mut params = Vec::new();
+        let mut param_types = Vec::new();
         if !self.check(&Token::RParen) {
-            // Parse first parameter
-            params.push(self.consume_identifier("Expected parameter name")?);
-            
-            // Parse any additional parameters
-            while self.match_token(&[Token::Comma]) {
+            loop {
                 params.push(self.consume_identifier("Expected parameter name")?);
+                param_types.push(if self.match_token(&[Token::Colon]) { Some(self.parse_type()?) } else { None });
+                
+                if !self.match_token(&[Token::Comma]) {
+                    break;
+                }
             }
         }
         
         self.consume(&Token::RParen, "Expected ')' after parameters")?;
+        let return_type = if self.match_token(&[Token::Arrow]) { Some(self.parse_type()?) } else { None };
         self.consume(&Token::LBrace, "Expected '{' before function body")?;
         
         let body = self.block()?;
@@ -121,17 +256,100 @@ impl<'a> Parser<'a> {
         Ok(Statement::FunctionDef {
             file_prefix: self.file_prefix.clone(),
             is_main,
+            is_public,
             name,
             params,
+            param_types,
+            return_type,
             body,
         })
     }
     
+    // A type annotation: `int`, `float`, `bool`, `string`, `void`, `any`,
+    // `range`, `[T]`, `{K: V}`, `fn(T, U) -> R` or a struct or enum name
+    fn parse_type(&mut self) -> Result<Type, String> {
+        if self.match_token(&[Token::LBracket]) {
+            let element = self.parse_type()?;
+            self.consume(&Token::RBracket, "Expected ']' after array element type")?;
+            return Ok(Type::Array(Box::new(element)));
+        }
+        
+        if self.match_token(&[Token::LBrace]) {
+            let key = self.parse_type()?;
+            self.consume(&Token::Colon, "Expected ':' after map key type")?;
+            let value = self.parse_type()?;
+            self.consume(&Token::RBrace, "Expected '}' after map value type")?;
+            return Ok(Type::Map(Box::new(key), Box::new(value)));
+        }
+        
+        if self.match_token(&[Token::Fn]) {
+            self.consume(&Token::LParen, "Expected '(' after 'fn' in type")?;
+            let mut params = Vec::new();
+            if !self.check(&Token::RParen) {
+                params.push(self.parse_type()?);
+                while self.match_token(&[Token::Comma]) {
+                    params.push(self.parse_type()?);
+                }
+            }
+            self.consume(&Token::RParen, "Expected ')' after parameter types")?;
+            let return_type = if self.match_token(&[Token::Arrow]) { self.parse_type()? } else { Type::Void };
+            return Ok(Type::Function { params, return_type: Box::new(return_type) });
+        }
+        
+        let mut name = self.consume_identifier("Expected a type")?;
+        while self.match_token(&[Token::Dot]) {
+            name = format!("{}.{}", name, self.consume_identifier("Expected a type name after '.'")?);
+        }
+        Ok(match name.as_str() {
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            "void" => Type::Void,
+            "any" => Type::Any,
+            "range" => Type::Range,
+            _ => Type::Named(name),
+        })
+    }
+    
     fn import_declaration(&mut self) -> Result<Statement, String> {
-        let module_name = self.consume_identifier("Expected module name after 'import'")?;
+        let module = self.module_path("Expected module name after 'import'")?;
+        
+        let alias = if self.match_token(&[Token::As]) {
+            Some(self.consume_identifier("Expected alias after 'as'")?)
+        } else {
+            None
+        };
+        
         self.consume(&Token::Semicolon, "Expected ';' after import statement")?;
         
-        Ok(Statement::Import(module_name))
+        Ok(Statement::Import { module, alias })
+    }
+    
+    fn import_from_declaration(&mut self) -> Result<Statement, String> {
+        let module = self.module_path("Expected module name after 'from'")?;
+        self.consume(&Token::Import, "Expected 'import' after module name")?;
+        
+        let mut names = vec![self.consume_identifier("Expected function name after 'import'")?];
+        while self.match_token(&[Token::Comma]) {
+            names.push(self.consume_identifier("Expected function name after ','")?);
+        }
+        
+        self.consume(&Token::Semicolon, "Expected ';' after import statement")?;
+        
+        Ok(Statement::ImportFrom { module, names })
+    }
+    
+    // Dotted module path such as `util.strings`
+    fn module_path(&mut self, message: &str) -> Result<String, String> {
+        let mut path = self.consume_identifier(message)?;
+        
+        while self.match_token(&[Token::Dot]) {
+            path.push('.');
+            path.push_str(&self.consume_identifier("Expected module name after '.'")?);
+        }
+        
+        Ok(path)
     }
     
     fn statement(&mut self) -> Result<Statement, String> {
@@ -149,6 +367,9 @@ impl<'a> Parser<'a> {
             return self.return_statement();
         } else if self.match_token(&[Token::Try]) {
             return self.try_statement();
+        } else if self.match_token(&[Token::Match]) {
+            let (subject, arms) = self.match_body(true)?;
+            return Ok(Statement::Match { subject, arms });
         }
         
         self.expression_statement()
@@ -174,11 +395,12 @@ impl<'a> Parser<'a> {
         Ok(Statement::Return(value))
     }
     
-    fn block(&mut self) -> Result<Vec<Statement>, String> {
+    fn block(&mut self) -> Result<Vec<Located>, String> {
         let mut statements = Vec::new();
         
         while !self.check(&Token::RBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?);
+            let line = self.line();
+            statements.push(Located { line, statement: self.declaration()? });
         }
         
         self.consume(&Token::RBrace, "Expected '}' after block")?;
@@ -195,10 +417,11 @@ impl<'a> Parser<'a> {
         let then_branch = self.block()?;
         
         let else_branch = if self.match_token(&[Token::Else]) {
+            let line = self.line();
             if self.match_token(&[Token::If]) {
                 // Handle 'else if' as a nested if in the else branch
                 let else_if_stmt = self.if_statement()?;
-                Some(vec![else_if_stmt])
+                Some(vec![Located { line, statement: else_if_stmt }])
             } else {
                 self.consume(&Token::LBrace, "Expected '{' before else body")?;
                 Some(self.block()?)
@@ -229,13 +452,17 @@ impl<'a> Parser<'a> {
     }
     
     fn for_statement(&mut self) -> Result<Statement, String> {
+        if let Some(names) = self.for_in_names() {
+            return self.for_in_statement(names);
+        }
+        
         self.consume(&Token::LParen, "Expected '(' after 'for'")?;
         
         // Initializer
         let init = if self.match_token(&[Token::Semicolon]) {
             None
         } else if self.match_token(&[Token::Let]) {
-            Some(Box::new(self.let_declaration()?))
+            Some(Box::new(self.let_declaration(false)?))
         } else {
             Some(Box::new(self.expression_statement()?))
         };
@@ -250,7 +477,7 @@ impl<'a> Parser<'a> {
         
         // Increment
         let update = if !self.check(&Token::RParen) {
-            let expr = self.expression()?;
+            let expr = self.update_expression()?;
             Some(Box::new(Statement::Expression(expr)))
         } else {
             None
@@ -268,6 +495,39 @@ impl<'a> Parser<'a> {
         })
     }
     
+    // The loop variables of `for x in`, `for k, v in` or `for (k, v) in`,
+    // consuming them and `in`. None for a C-style `for`.
+    fn for_in_names(&mut self) -> Option<Vec<String>> {
+        let (names, length) = match self.tokens.get(self.current..) {
+            Some([Token::Identifier(x), Token::In, ..]) => (vec![x.clone()], 2),
+            Some([Token::Identifier(k), Token::Comma, Token::Identifier(v), Token::In, ..]) => {
+                (vec![k.clone(), v.clone()], 4)
+            }
+            Some([
+                Token::LParen,
+                Token::Identifier(k),
+                Token::Comma,
+                Token::Identifier(v),
+                Token::RParen,
+                Token::In,
+                ..
+            ]) => (vec![k.clone(), v.clone()], 6),
+            _ => return None,
+        };
+        self.current += length;
+        Some(names)
+    }
+    
+    fn for_in_statement(&mut self, names: Vec<String>) -> Result<Statement, String> {
+        // `for x in items { ... }` must not read `items { ... }` as a struct literal
+        let iterable = self.with_struct_literals(false, Self::expression)?;
+        
+        self.consume(&Token::LBrace, "Expected '{' before for body")?;
+        let body = self.block()?;
+        
+        Ok(Statement::ForIn { names, iterable, body })
+    }
+    
     fn try_statement(&mut self) -> Result<Statement, String> {
         self.consume(&Token::LBrace, "Expected '{' after 'try'")?;
         let try_body = self.block()?;
@@ -283,7 +543,7 @@ impl<'a> Parser<'a> {
     }
     
     fn expression_statement(&mut self) -> Result<Statement, String> {
-        let expr = self.expression()?;
+        let expr = self.update_expression()?;
         
         // Handle assignment expressions
         if let Expression::Binary { 
@@ -291,7 +551,7 @@ impl<'a> Parser<'a> {
             op: BinaryOp::Equal, 
             right 
         } = expr.clone() {
-            if let Expression::Identifier(name) = *box_left {
+            if let Expression::Identifier(name, _) = *box_left {
                 self.consume(&Token::Semicolon, "Expected ';' after assignment")?;
                 return Ok(Statement::Assign { name, value: *right });
             }
@@ -301,19 +561,53 @@ impl<'a> Parser<'a> {
         Ok(Statement::Expression(expr))
     }
     
+    // An expression, or `target++` / `target--`, which only appear as statements
+    fn update_expression(&mut self) -> Result<Expression, String> {
+        let expr = self.expression()?;
+        
+        if self.match_token(&[Token::PlusPlus, Token::MinusMinus]) {
+            let op = match self.previous() {
+                Token::PlusPlus => BinaryOp::Add,
+                _ => BinaryOp::Subtract,
+            };
+            return compound_assignment(expr, op, Expression::Number(1));
+        }
+        
+        Ok(expr)
+    }
+    
     // Expression parsing methods
     fn expression(&mut self) -> Result<Expression, String> {
         self.assignment()
     }
     
     fn assignment(&mut self) -> Result<Expression, String> {
-        let expr = self.logic_or()?;
+        let expr = self.conditional()?;
+        
+        if self.match_token(&[
+            Token::PlusEqual,
+            Token::MinusEqual,
+            Token::StarEqual,
+            Token::SlashEqual,
+            Token::PercentEqual,
+        ]) {
+            let op = match self.previous() {
+                Token::PlusEqual => BinaryOp::Add,
+                Token::MinusEqual => BinaryOp::Subtract,
+                Token::StarEqual => BinaryOp::Multiply,
+                Token::SlashEqual => BinaryOp::Divide,
+                Token::PercentEqual => BinaryOp::Modulo,
+                _ => unreachable!(),
+            };
+            let value = self.assignment()?;
+            return compound_assignment(expr, op, value);
+        }
         
         if self.match_token(&[Token::Equal]) {
-            if let Expression::Identifier(name) = expr {
+            if let Expression::Identifier(name, _) = expr {
                 let value = Box::new(self.assignment()?);
                 return Ok(Expression::Binary { 
-                    left: Box::new(Expression::Identifier(name)), 
+                    left: Box::new(Expression::Identifier(name, None)), 
                     op: BinaryOp::Equal, 
                     right: value 
                 });
@@ -321,9 +615,15 @@ impl<'a> Parser<'a> {
                 let value = Box::new(self.assignment()?);
                 // Instead of using ArrayAssign, use a function call pattern
                 return Ok(Expression::Call {
-                    callee: Box::new(Expression::Identifier("__array_assign".to_string())),
+                    callee: Box::new(Expression::Identifier("__array_assign".to_string(), None)),
                     arguments: vec![*array, *index, *value],
                 });
+            } else if let Expression::FieldAccess { object, field } = expr {
+                let value = Box::new(self.assignment()?);
+                return Ok(Expression::Call {
+                    callee: Box::new(Expression::Identifier("__field_assign".to_string(), None)),
+                    arguments: vec![*object, Expression::String(field), *value],
+                });
             }
             
             return Err("Invalid assignment target".to_string());
@@ -332,6 +632,39 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
     
+    // `condition ? a : b`, right-associative so `a ? b : c ? d : e` chains
+    fn conditional(&mut self) -> Result<Expression, String> {
+        let condition = self.range()?;
+        
+        if self.match_token(&[Token::Question]) {
+            let then_branch = self.expression()?;
+            self.consume(&Token::Colon, "Expected ':' in conditional expression")?;
+            let else_branch = self.conditional()?;
+            return Ok(Expression::Conditional {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            });
+        }
+        
+        Ok(condition)
+    }
+    
+    // `start..end` binds looser than any other binary operator
+    fn range(&mut self) -> Result<Expression, String> {
+        let start = self.logic_or()?;
+        
+        if self.match_token(&[Token::DotDot]) {
+            let end = self.logic_or()?;
+            return Ok(Expression::Range {
+                start: Box::new(start),
+                end: Box::new(end),
+            });
+        }
+        
+        Ok(start)
+    }
+    
     fn logic_or(&mut self) -> Result<Expression, String> {
         let mut expr = self.logic_and()?;
         
@@ -384,7 +717,7 @@ impl<'a> Parser<'a> {
     }
     
     fn comparison(&mut self) -> Result<Expression, String> {
-        let mut expr = self.term()?;
+        let mut expr = self.bit_or()?;
         
         while self.match_token(&[
             Token::LessThan, 
@@ -400,7 +733,7 @@ impl<'a> Parser<'a> {
                 _ => unreachable!(),
             };
             
-            let right = self.term()?;
+            let right = self.bit_or()?;
             expr = Expression::Binary {
                 left: Box::new(expr),
                 op,
@@ -411,6 +744,48 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
     
+    // Bitwise operators bind tighter than comparisons: `|`, then `^`, then
+    // `&`, then shifts
+    fn bit_or(&mut self) -> Result<Expression, String> {
+        self.binary_level(&[(Token::BitOr, BinaryOp::BitOr)], Self::bit_xor)
+    }
+    
+    fn bit_xor(&mut self) -> Result<Expression, String> {
+        self.binary_level(&[(Token::BitXor, BinaryOp::BitXor)], Self::bit_and)
+    }
+    
+    fn bit_and(&mut self) -> Result<Expression, String> {
+        self.binary_level(&[(Token::BitAnd, BinaryOp::BitAnd)], Self::shift)
+    }
+    
+    fn shift(&mut self) -> Result<Expression, String> {
+        self.binary_level(
+            &[(Token::ShiftLeft, BinaryOp::ShiftLeft), (Token::ShiftRight, BinaryOp::ShiftRight)],
+            Self::term,
+        )
+    }
+    
+    // Left-associative operators of one precedence level over `operand`
+    fn binary_level(
+        &mut self,
+        operators: &[(Token, BinaryOp)],
+        operand: fn(&mut Self) -> Result<Expression, String>,
+    ) -> Result<Expression, String> {
+        let mut expr = operand(self)?;
+        
+        while let Some((_, op)) = operators.iter().find(|(token, _)| self.check(token)) {
+            self.advance();
+            let right = operand(self)?;
+            expr = Expression::Binary {
+                left: Box::new(expr),
+                op: op.clone(),
+                right: Box::new(right),
+            };
+        }
+        
+        Ok(expr)
+    }
+    
     fn term(&mut self) -> Result<Expression, String> {
         let mut expr = self.factor()?;
         
@@ -455,10 +830,11 @@ impl<'a> Parser<'a> {
     }
     
     fn unary(&mut self) -> Result<Expression, String> {
-        if self.match_token(&[Token::Minus, Token::Not]) {
+        if self.match_token(&[Token::Minus, Token::Not, Token::BitNot]) {
             let op = match self.previous() {
                 Token::Minus => UnaryOp::Negate,
                 Token::Not => UnaryOp::Not,
+                Token::BitNot => UnaryOp::BitNot,
                 _ => unreachable!(),
             };
             
@@ -479,12 +855,18 @@ impl<'a> Parser<'a> {
             if self.match_token(&[Token::LParen]) {
                 expr = self.finish_call(expr)?;
             } else if self.match_token(&[Token::LBracket]) {
-                let index = self.expression()?;
+                let index = self.with_struct_literals(true, Self::expression)?;
                 self.consume(&Token::RBracket, "Expected ']' after array index")?;
                 expr = Expression::ArrayAccess {
                     array: Box::new(expr),
                     index: Box::new(index),
                 };
+            } else if self.match_token(&[Token::Dot]) {
+                let field = self.consume_identifier("Expected name after '.'")?;
+                expr = Expression::FieldAccess {
+                    object: Box::new(expr),
+                    field,
+                };
             } else {
                 break;
             }
@@ -501,14 +883,14 @@ impl<'a> Parser<'a> {
             if arguments.len() >= 255 {
                 return Err("Cannot have more than 255 arguments".to_string());
             }
-            arguments.push(self.expression()?);
+            arguments.push(self.with_struct_literals(true, Self::expression)?);
             
             // Parse any additional arguments
             while self.match_token(&[Token::Comma]) {
                 if arguments.len() >= 255 {
                     return Err("Cannot have more than 255 arguments".to_string());
                 }
-                arguments.push(self.expression()?);
+                arguments.push(self.with_struct_literals(true, Self::expression)?);
             }
         }
         
@@ -527,6 +909,12 @@ impl<'a> Parser<'a> {
             }
         }
         
+        if self.match_token(&[Token::BigInt(BigInt::default())])
+            && let Token::BigInt(n) = self.previous()
+        {
+            return Ok(Expression::BigInt(n.clone()));
+        }
+        
         if self.match_token(&[Token::Float(0.0)]) {
             if let Token::Float(f) = self.previous() {
                 return Ok(Expression::Float(*f));
@@ -545,22 +933,41 @@ impl<'a> Parser<'a> {
             }
         }
         
+        if self.match_token(&[Token::InterpolatedString(Vec::new())])
+            && let Token::InterpolatedString(parts) = self.previous()
+        {
+            return self.interpolation(parts.clone());
+        }
+        
         if self.match_token(&[Token::LBracket]) {
             return self.array_literal();
         }
         
+        if self.match_token(&[Token::LBrace]) {
+            return self.map_literal();
+        }
+        
         if self.match_token(&[Token::Identifier("".to_string())]) {
             if let Token::Identifier(name) = self.previous() {
-                return Ok(Expression::Identifier(name.clone()));
+                let name = name.clone();
+                if self.allow_struct_literal && self.at_struct_literal() {
+                    return self.struct_literal(name);
+                }
+                return Ok(Expression::Identifier(name, None));
             }
         }
         
         if self.match_token(&[Token::LParen]) {
-            let expr = self.expression()?;
+            let expr = self.with_struct_literals(true, Self::expression)?;
             self.consume(&Token::RParen, "Expected ')' after expression")?;
             return Ok(expr);
         }
         
+        if self.match_token(&[Token::Match]) {
+            let (subject, arms) = self.match_body(false)?;
+            return Ok(Expression::Match { subject: Box::new(subject), arms });
+        }
+        
         // Parse closure/lambda expressions
         if self.match_token(&[Token::Fn]) {
             self.consume(&Token::LParen, "Expected '(' after 'fn' in closure")?;
@@ -595,11 +1002,11 @@ impl<'a> Parser<'a> {
         
         if !self.check(&Token::RBracket) {
             // Parse first element
-            elements.push(self.expression()?);
+            elements.push(self.with_struct_literals(true, Self::expression)?);
             
             // Parse any additional elements
             while self.match_token(&[Token::Comma]) {
-                elements.push(self.expression()?);
+                elements.push(self.with_struct_literals(true, Self::expression)?);
             }
         }
         
@@ -610,6 +1017,245 @@ impl<'a> Parser<'a> {
         })
     }
     
+    // Each `${...}` of an interpolated string is parsed as a full expression
+    fn interpolation(&mut self, parts: Vec<StringPart>) -> Result<Expression, String> {
+        let mut result = Vec::new();
+        
+        for part in parts {
+            match part {
+                StringPart::Text(text) => result.push(InterpolationPart::Text(text)),
+                StringPart::Code { tokens, spans, span } => {
+                    let mut parser = Parser {
+                        tokens,
+                        spans,
+                        comments: Vec::new(),
+                        current: 0,
+                        file_path: self.file_path.clone(),
+                        source_code: self.source_code,
+                        file_prefix: self.file_prefix.clone(),
+                        allow_struct_literal: true,
+                    };
+                    let expr = parser.expression()
+                        .map_err(|e| format!("{} in interpolation at line {}, column {}", e, span.line, span.column))?;
+                    if !parser.is_at_end() {
+                        return Err(format!(
+                            "Unexpected {:?} in interpolation at line {}, column {}",
+                            parser.peek(), span.line, span.column
+                        ));
+                    }
+                    result.push(InterpolationPart::Expression { expr, span });
+                }
+            }
+        }
+        
+        Ok(Expression::Interpolation { parts: result })
+    }
+    
+    fn map_literal(&mut self) -> Result<Expression, String> {
+        let mut entries = Vec::new();
+        
+        if !self.check(&Token::RBrace) {
+            loop {
+                let key = self.with_struct_literals(true, Self::expression)?;
+                self.consume(&Token::Colon, "Expected ':' after map key")?;
+                let value = self.with_struct_literals(true, Self::expression)?;
+                entries.push((key, value));
+                
+                if !self.match_token(&[Token::Comma]) {
+                    break;
+                }
+            }
+        }
+        
+        self.consume(&Token::RBrace, "Expected '}' after map entries")?;
+        
+        Ok(Expression::Map {
+            entries,
+        })
+    }
+    
+    // `Name {` starts a struct literal only when followed by `}` or `field:`,
+    // so blocks after an identifier are left alone
+    fn at_struct_literal(&self) -> bool {
+        matches!(
+            self.tokens.get(self.current..self.current + 3),
+            Some([Token::LBrace, Token::RBrace, _] | [Token::LBrace, Token::Identifier(_), Token::Colon])
+        )
+    }
+    
+    // Parse with struct literals allowed or not. Inside parentheses, brackets
+    // and braces they are allowed again, as the closing token ends the expression
+    fn with_struct_literals<T>(&mut self, allow: bool, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        let previous = std::mem::replace(&mut self.allow_struct_literal, allow);
+        let result = parse(self);
+        self.allow_struct_literal = previous;
+        result
+    }
+    
+    fn struct_literal(&mut self, name: String) -> Result<Expression, String> {
+        self.consume(&Token::LBrace, "Expected '{' after struct name")?;
+        
+        let mut fields: Vec<(String, Expression)> = Vec::new();
+        while !self.check(&Token::RBrace) {
+            let field = self.consume_identifier("Expected field name")?;
+            if fields.iter().any(|(f, _)| *f == field) {
+                return Err(format!("Field '{}' specified more than once in '{}' literal", field, name));
+            }
+            self.consume(&Token::Colon, "Expected ':' after field name")?;
+            fields.push((field, self.with_struct_literals(true, Self::expression)?));
+            
+            if !self.match_token(&[Token::Comma]) {
+                break;
+            }
+        }
+        
+        self.consume(&Token::RBrace, "Expected '}' after struct fields")?;
+        
+        Ok(Expression::StructLiteral { name, fields })
+    }
+    
+    // `subject { arm, ... }` after `match`. Only a `match` statement may have
+    // block arms, which can `return` from the enclosing function.
+    fn match_body(&mut self, is_statement: bool) -> Result<(Expression, Vec<MatchArm>), String> {
+        // `match point {` must not read `point {` as a struct literal
+        let subject = self.with_struct_literals(false, Self::expression)?;
+        
+        self.consume(&Token::LBrace, "Expected '{' after match subject")?;
+        
+        let mut arms = Vec::new();
+        while !self.check(&Token::RBrace) && !self.is_at_end() {
+            let pattern = self.pattern()?;
+            let guard = if self.match_token(&[Token::If]) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            self.consume(&Token::FatArrow, "Expected '=>' after match pattern")?;
+            
+            let body = if self.match_token(&[Token::LBrace]) {
+                if !is_statement {
+                    let (line, column) = self.position();
+                    return Err(format!(
+                        "Block arms are only allowed when 'match' is used as a statement at line {}, column {}",
+                        line, column
+                    ));
+                }
+                ArmBody::Block(self.block()?)
+            } else {
+                ArmBody::Expression(self.expression()?)
+            };
+            
+            // Commas separate arms; they are optional after a block
+            let is_block = matches!(body, ArmBody::Block(_));
+            arms.push(MatchArm { pattern, guard, body });
+            if !self.match_token(&[Token::Comma]) && !is_block {
+                break;
+            }
+        }
+        
+        self.consume(&Token::RBrace, "Expected '}' after match arms")?;
+        
+        Ok((subject, arms))
+    }
+    
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        if self.match_token(&[
+            Token::Number(0),
+            Token::BigInt(BigInt::default()),
+            Token::Float(0.0),
+            Token::String(String::new()),
+            Token::Bool(false),
+        ]) {
+            let literal = match self.previous() {
+                Token::Number(n) => Expression::Number(*n),
+                Token::BigInt(n) => Expression::BigInt(n.clone()),
+                Token::Float(f) => Expression::Float(*f),
+                Token::String(s) => Expression::String(s.clone()),
+                Token::Bool(b) => Expression::Bool(*b),
+                _ => unreachable!(),
+            };
+            return Ok(Pattern::Literal(literal));
+        }
+        
+        if self.match_token(&[Token::Minus]) {
+            return match self.advance().clone() {
+                Token::Number(n) => Ok(Pattern::Literal(Expression::Number(-n))),
+                // `-9223372036854775808` is the only negated bigint that fits in 64 bits
+                Token::BigInt(n) => Ok(Pattern::Literal(match i64::try_from(-&n) {
+                    Ok(n) => Expression::Number(n),
+                    Err(_) => Expression::BigInt(-n),
+                })),
+                Token::Float(f) => Ok(Pattern::Literal(Expression::Float(-f))),
+                _ => Err("Expected a number after '-' in pattern".to_string()),
+            };
+        }
+        
+        if self.match_token(&[Token::LBracket]) {
+            let mut elements = Vec::new();
+            let mut rest = None;
+            while !self.check(&Token::RBracket) {
+                if self.match_token(&[Token::DotDot]) {
+                    let name = if self.check(&Token::Identifier(String::new())) {
+                        Some(self.consume_identifier("Expected name after '..'")?)
+                    } else {
+                        None
+                    };
+                    rest = Some(name);
+                    break;
+                }
+                elements.push(self.pattern()?);
+                if !self.match_token(&[Token::Comma]) {
+                    break;
+                }
+            }
+            self.consume(&Token::RBracket, "Expected ']' after array pattern (a '..' rest must come last)")?;
+            return Ok(Pattern::Array { elements, rest });
+        }
+        
+        let name = self.consume_identifier("Expected pattern")?;
+        
+        if name == "_" {
+            return Ok(Pattern::Wildcard);
+        }
+        
+        // `Enum.Variant` or `Enum.Variant(patterns)`
+        if self.match_token(&[Token::Dot]) {
+            let variant = self.consume_identifier("Expected variant name after '.'")?;
+            let mut fields = Vec::new();
+            if self.match_token(&[Token::LParen]) {
+                if !self.check(&Token::RParen) {
+                    fields.push(self.pattern()?);
+                    while self.match_token(&[Token::Comma]) {
+                        fields.push(self.pattern()?);
+                    }
+                }
+                self.consume(&Token::RParen, "Expected ')' after variant patterns")?;
+            }
+            return Ok(Pattern::Enum { name, variant, fields });
+        }
+        
+        // `Struct { field, field: pattern }`
+        if self.match_token(&[Token::LBrace]) {
+            let mut fields = Vec::new();
+            while !self.check(&Token::RBrace) {
+                let field = self.consume_identifier("Expected field name in struct pattern")?;
+                let pattern = if self.match_token(&[Token::Colon]) {
+                    self.pattern()?
+                } else {
+                    Pattern::Binding(field.clone())
+                };
+                fields.push((field, pattern));
+                if !self.match_token(&[Token::Comma]) {
+                    break;
+                }
+            }
+            self.consume(&Token::RBrace, "Expected '}' after struct pattern")?;
+            return Ok(Pattern::Struct { name, fields });
+        }
+        
+        Ok(Pattern::Binding(name))
+    }
+    
     // Helper methods for token handling
     fn match_token(&mut self, tokens: &[Token]) -> bool {
         for token in tokens {
@@ -628,6 +1274,7 @@ impl<'a> Parser<'a> {
         } else {
             match (token, &self.tokens[self.current]) {
                 (Token::Number(_), Token::Number(_)) => true,
+                (Token::BigInt(_), Token::BigInt(_)) => true,
                 (Token::Float(_), Token::Float(_)) => true,
                 (Token::Bool(_), Token::Bool(_)) => true,
                 (Token::String(_), Token::String(_)) => true,
@@ -657,11 +1304,22 @@ impl<'a> Parser<'a> {
         &self.tokens[self.current - 1]
     }
     
+    /// Line and column of the current token, which is where parsing stopped
+    /// if `parse` failed
+    pub fn position(&self) -> (usize, usize) {
+        self.spans.get(self.current).or(self.spans.last()).map_or((1, 1), |span| (span.line, span.column))
+    }
+
+    // Line of the current token
+    fn line(&self) -> usize {
+        self.position().0
+    }
+    
     fn consume(&mut self, token: &Token, message: &str) -> Result<&Token, String> {
         if self.check(token) {
             Ok(self.advance())
         } else {
-            let (line, column) = Lexer::get_position_for_error(&self.tokens, self.current, self.source_code);
+            let (line, column) = self.position();
             Err(format!("{} at line {}, column {}", message, line, column))
         }
     }
@@ -673,21 +1331,21 @@ impl<'a> Parser<'a> {
             }
         }
         
-        let (line, column) = Lexer::get_position_for_error(&self.tokens, self.current, self.source_code);
+        let (line, column) = self.position();
         Err(format!("{} at line {}, column {}", message, line, column))
     }
 }
 
-// Helper method added to Lexer for error reporting
-impl<'a> Lexer<'a> {
-    pub fn get_position_for_error(tokens: &[Token], current: usize, source_code: &str) -> (usize, usize) {
-        if current >= tokens.len() {
-            // If at the end, count lines and columns manually
-            let lines: Vec<&str> = source_code.lines().collect();
-            return (lines.len(), lines.last().map_or(0, |s| s.len() + 1));
+// `target op= value`, checking that the target can be assigned to
+fn compound_assignment(target: Expression, op: BinaryOp, value: Expression) -> Result<Expression, String> {
+    match target {
+        Expression::Identifier(..) | Expression::ArrayAccess { .. } | Expression::FieldAccess { .. } => {
+            Ok(Expression::CompoundAssign {
+                target: Box::new(target),
+                op,
+                value: Box::new(value),
+            })
         }
-        
-        // Return a default position if not available
-        (1, 1)
+        _ => Err("Invalid assignment target".to_string()),
     }
-}
\ No newline at end of file
+}
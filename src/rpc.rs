@@ -0,0 +1,52 @@
+use std::io::{BufRead, Write};
+
+use serde_json::Value;
+
+/// Read a message of the base protocol of the language server and the debug
+/// adapter: `Content-Length` and other headers, a blank line, then a JSON
+/// body of that many bytes. Returns `None` at the end of the input.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, String> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        let read = input
+            .read_line(&mut line)
+            .map_err(|e| format!("Error reading message: {}", e))?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("Content-Length")
+        {
+            let value = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| format!("Invalid Content-Length '{}': {}", value.trim(), e))?;
+            length = Some(value);
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or_default()];
+    input
+        .read_exact(&mut body)
+        .map_err(|e| format!("Error reading message: {}", e))?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| format!("Invalid message: {}", e))
+}
+
+/// Write a message of the base protocol
+pub fn write_message(output: &mut impl Write, message: &Value) -> Result<(), String> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|()| output.flush())
+        .map_err(|e| format!("Error writing message: {}", e))
+}
@@ -0,0 +1,882 @@
+use std::collections::HashMap;
+
+use crate::ast::Span;
+use crate::lexer::{Lexer, StringPart, Token, takes_expression};
+
+/// What a name defined in a program stands for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Variable,
+    Parameter,
+    Struct,
+    Field,
+    Enum,
+    Variant,
+    Module,   // `import module` or `import module as alias`
+    Imported, // A name of `from module import name`
+}
+
+impl SymbolKind {
+    /// Whether symbols of this kind are only used after a `.`
+    pub fn is_member(self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Field | SymbolKind::Variant | SymbolKind::Method
+        )
+    }
+}
+
+/// A name defined in a program
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,                 // The name where it is defined
+    pub range: (usize, usize),      // Byte range of the whole definition
+    pub detail: String,             // The declaration, such as `fn area(w: int, h: int) -> int`
+    pub scope: usize,               // Index in `Index::scopes` of the scope defining it
+    pub visible_from: usize,        // Byte offset from which code of that scope sees it
+    pub parent: Option<usize>,      // Struct or enum of a field, variant or method
+    pub module: Option<String>,     // Import path of a module or imported name
+    pub instance_of: Option<usize>, // Struct of `self` in a method, or of a variable when known
+}
+
+/// A use of a name
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub span: Span,
+    pub symbol: Option<usize>, // Definition in this program, if there is one
+    pub object: Option<usize>, // Reference to the object of `object.name`
+}
+
+/// A part of a program whose variables are only visible inside it: the
+/// program itself, a function or closure, a `for` loop, a `match` arm or a
+/// block. The bodies of `if`, `while` and `try` belong to the scope around them.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub parent: Option<usize>,
+    pub range: (usize, usize), // Byte range
+    pub function: bool,        // Body of a named function, which only sees the globals around it
+}
+
+/// The names a program defines and uses, with their positions
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+    pub scopes: Vec<Scope>,
+}
+
+impl Index {
+    /// The symbol defined or used at byte `offset`
+    pub fn symbol_at(&self, offset: usize) -> Option<usize> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        self.symbols
+            .iter()
+            .position(|symbol| contains(&symbol.span))
+            .or_else(|| {
+                self.references
+                    .iter()
+                    .find(|r| contains(&r.span))
+                    .and_then(|r| r.symbol)
+            })
+    }
+
+    /// The reference at byte `offset`
+    pub fn reference_at(&self, offset: usize) -> Option<usize> {
+        self.references
+            .iter()
+            .position(|r| r.span.start <= offset && offset <= r.span.end)
+    }
+
+    /// Fields, variants and methods of a struct or enum
+    pub fn members(&self, parent: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.symbols.len()).filter(move |&i| self.symbols[i].parent == Some(parent))
+    }
+
+    /// A top-level function, struct, enum or variable
+    pub fn global(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .position(|symbol| symbol.scope == 0 && symbol.name == name && !symbol.kind.is_member())
+    }
+
+    /// Symbols visible at byte `offset`, innermost first
+    pub fn visible(&self, offset: usize) -> Vec<usize> {
+        let innermost = (0..self.scopes.len())
+            .filter(|&i| self.scopes[i].range.0 <= offset && offset <= self.scopes[i].range.1)
+            .max_by_key(|&i| self.scopes[i].range.0)
+            .unwrap_or(0);
+
+        let mut visible = Vec::new();
+        let mut scope = Some(innermost);
+        let mut in_function = false;
+        while let Some(current) = scope {
+            for (i, symbol) in self.symbols.iter().enumerate() {
+                let seen = symbol.visible_from <= offset || (current == 0 && in_function);
+                if symbol.scope == current && !symbol.kind.is_member() && seen {
+                    visible.push(i);
+                }
+            }
+            if self.scopes[current].function {
+                in_function = true;
+                scope = if current == 0 { None } else { Some(0) };
+            } else {
+                scope = self.scopes[current].parent;
+            }
+        }
+        visible
+    }
+}
+
+/// Index the names of a program. Only the tokens are used, so programs with
+/// parse errors are indexed as well as they can be.
+pub fn index(source: &str) -> Result<Index, String> {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize()?;
+    let mut flat = Vec::new();
+    flatten(&tokens, lexer.spans(), &mut flat);
+
+    let mut closers = vec![None; flat.len()];
+    let mut open = Vec::new();
+    for (i, (token, _)) in flat.iter().enumerate() {
+        match token {
+            Token::LParen | Token::LBrace | Token::LBracket => open.push(i),
+            Token::RParen | Token::RBrace | Token::RBracket => {
+                if let Some(start) = open.pop() {
+                    closers[start] = Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut indexer = Indexer {
+        source,
+        handled: vec![false; flat.len()],
+        tokens: flat,
+        closers,
+        index: Index {
+            symbols: Vec::new(),
+            references: Vec::new(),
+            scopes: vec![Scope {
+                parent: None,
+                range: (0, source.len()),
+                function: false,
+            }],
+        },
+        names: vec![HashMap::new()],
+        ends: vec![usize::MAX],
+        pending: HashMap::new(),
+        stack: vec![0],
+        bodies: Vec::new(),
+        structs: Vec::new(),
+        reference_at: HashMap::new(),
+    };
+    indexer.declarations();
+    indexer.walk();
+    Ok(indexer.index)
+}
+
+// The tokens of a program with the code of interpolated strings spliced in,
+// each `${...}` in parentheses
+fn flatten(tokens: &[Token], spans: &[Span], flat: &mut Vec<(Token, Span)>) {
+    for (token, span) in tokens.iter().zip(spans) {
+        match token {
+            Token::EOF => {}
+            Token::InterpolatedString(parts) => {
+                for part in parts {
+                    if let StringPart::Code {
+                        tokens,
+                        spans,
+                        span,
+                    } = part
+                    {
+                        flat.push((Token::LParen, *span));
+                        flatten(tokens, spans, flat);
+                        flat.push((Token::RParen, *span));
+                    }
+                }
+            }
+            token => flat.push((token.clone(), *span)),
+        }
+    }
+}
+
+struct Indexer<'a> {
+    source: &'a str,
+    tokens: Vec<(Token, Span)>,
+    closers: Vec<Option<usize>>, // Matching closing token of each opening one
+    index: Index,
+    names: Vec<HashMap<String, Vec<usize>>>, // Symbols of each scope by name
+    ends: Vec<usize>,                        // Last token of each scope
+    pending: HashMap<usize, Vec<usize>>,     // Scopes entered at a token
+    stack: Vec<usize>,                       // Scopes entered so far
+    handled: Vec<bool>,                      // Tokens that define a name
+    bodies: Vec<usize>, // `{` of bodies that follow a name, which aren't struct literals
+    structs: Vec<Option<usize>>, // Struct of each `{` open while walking, for field names
+    reference_at: HashMap<usize, usize>, // Reference made by a token
+}
+
+impl<'a> Indexer<'a> {
+    fn token(&self, i: usize) -> &Token {
+        self.tokens.get(i).map_or(&Token::EOF, |(token, _)| token)
+    }
+
+    fn span(&self, i: usize) -> Span {
+        self.tokens[i].1
+    }
+
+    fn name(&self, i: usize) -> Option<String> {
+        match self.token(i) {
+            Token::Identifier(name) => Some(name.clone()),
+            Token::Main => Some("main".to_string()),
+            _ => None,
+        }
+    }
+
+    // Source from the start of token `first` to the end of token `last`,
+    // on one line
+    fn text(&self, first: usize, last: usize) -> String {
+        let text = &self.source[self.span(first).start..self.span(last).end];
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    // The token after a bracketed group, or after token `i` if it opens none
+    fn skip(&self, i: usize) -> usize {
+        self.closers
+            .get(i)
+            .copied()
+            .flatten()
+            .map_or(i + 1, |close| close + 1)
+    }
+
+    fn new_scope(&mut self, start: usize, end: usize, function: bool) -> usize {
+        let range = (
+            self.span(start).start,
+            self.span(end.min(self.tokens.len() - 1)).end,
+        );
+        self.index.scopes.push(Scope {
+            parent: None,
+            range,
+            function,
+        });
+        self.names.push(HashMap::new());
+        self.ends.push(end);
+        self.pending
+            .entry(start)
+            .or_default()
+            .push(self.index.scopes.len() - 1);
+        self.index.scopes.len() - 1
+    }
+
+    // Define the name at token `i` in `scope`, visible from there on
+    fn define(
+        &mut self,
+        i: usize,
+        kind: SymbolKind,
+        scope: usize,
+        range: (usize, usize),
+        detail: String,
+    ) -> usize {
+        let symbol = self.define_member(i, kind, range, detail, None);
+        self.index.symbols[symbol].scope = scope;
+        let name = self.index.symbols[symbol].name.clone();
+        self.names[scope].entry(name).or_default().push(symbol);
+        symbol
+    }
+
+    // Define the field, variant or method at token `i`
+    fn define_member(
+        &mut self,
+        i: usize,
+        kind: SymbolKind,
+        range: (usize, usize),
+        detail: String,
+        parent: Option<usize>,
+    ) -> usize {
+        self.handled[i] = true;
+        self.index.symbols.push(Symbol {
+            name: self.name(i).unwrap_or_default(),
+            kind,
+            span: self.span(i),
+            range,
+            detail,
+            scope: 0,
+            visible_from: self.span(i).start,
+            parent,
+            module: None,
+            instance_of: None,
+        });
+        self.index.symbols.len() - 1
+    }
+
+    // The byte range of tokens `first` to `last`
+    fn range(&self, first: usize, last: usize) -> (usize, usize) {
+        (
+            self.span(first).start,
+            self.span(last.min(self.tokens.len() - 1)).end,
+        )
+    }
+
+    // The `{` of a body starting at or after token `i`, skipping the
+    // expression or type before it
+    fn body(&self, mut i: usize) -> Option<usize> {
+        while i < self.tokens.len() {
+            match self.token(i) {
+                Token::LBrace if i == 0 || !takes_expression(self.token(i - 1)) => return Some(i),
+                Token::LParen | Token::LBracket | Token::LBrace => i = self.skip(i),
+                Token::Semicolon | Token::RBrace | Token::RParen | Token::EOF => return None,
+                _ => i += 1,
+            }
+        }
+        None
+    }
+
+    // The token after the type starting at token `i`
+    fn skip_type(&self, mut i: usize) -> usize {
+        match self.token(i) {
+            Token::Identifier(_) => {
+                i += 1;
+                while *self.token(i) == Token::Dot
+                    && matches!(self.token(i + 1), Token::Identifier(_))
+                {
+                    i += 2;
+                }
+                i
+            }
+            Token::LBracket | Token::LBrace => self.skip(i),
+            Token::Fn => {
+                let i = self.skip(i + 1);
+                if *self.token(i) == Token::Arrow {
+                    self.skip_type(i + 1)
+                } else {
+                    i
+                }
+            }
+            _ => i,
+        }
+    }
+
+    // Top-level declarations, which are visible before their definition:
+    // functions, structs, enums, methods and imports
+    fn declarations(&mut self) {
+        let mut i = 0;
+        while i < self.tokens.len() {
+            let start = i;
+            if *self.token(i) == Token::Pub {
+                i += 1;
+            }
+            match self.token(i) {
+                Token::Fn if self.name(i + 1).is_some() => {
+                    let body = self.function(start, i + 1, SymbolKind::Function, None);
+                    i = body.map_or(i + 2, |body| self.skip(body));
+                }
+                Token::Struct | Token::Enum if self.name(i + 1).is_some() => {
+                    i = self.type_declaration(i)
+                }
+                Token::Impl if self.name(i + 1).is_some() => i = self.impl_block(i),
+                Token::Import | Token::From => i = self.import(i),
+                Token::LParen | Token::LBracket | Token::LBrace => i = self.skip(i),
+                _ => i += 1,
+            }
+        }
+    }
+
+    // A named function or method, with the name at token `name`. Returns the
+    // `{` of its body.
+    fn function(
+        &mut self,
+        start: usize,
+        name: usize,
+        kind: SymbolKind,
+        parent: Option<usize>,
+    ) -> Option<usize> {
+        let body = self.body(name + 1)?;
+        let end = self.closers[body].unwrap_or(self.tokens.len() - 1);
+        let detail = self.text(start, body - 1);
+        let range = self.range(start, end);
+        let symbol = match kind {
+            SymbolKind::Method => self.define_member(name, kind, range, detail, parent),
+            _ => self.define(name, kind, 0, range, detail),
+        };
+        self.index.symbols[symbol].visible_from = 0;
+        self.bodies.push(body);
+        Some(body)
+    }
+
+    // `struct Name { fields }` or `enum Name { variants }` at token `i`.
+    // Returns the token after it.
+    fn type_declaration(&mut self, i: usize) -> usize {
+        let (kind, member_kind) = if *self.token(i) == Token::Struct {
+            (SymbolKind::Struct, SymbolKind::Field)
+        } else {
+            (SymbolKind::Enum, SymbolKind::Variant)
+        };
+        let start = if i > 0 && *self.token(i - 1) == Token::Pub {
+            i - 1
+        } else {
+            i
+        };
+        if *self.token(i + 2) != Token::LBrace {
+            return i + 2;
+        }
+        let close = self.closers[i + 2].unwrap_or(self.tokens.len() - 1);
+        let detail = self.text(start, close);
+        let range = self.range(start, close);
+        let name = self.name(i + 1).unwrap_or_default();
+        let symbol = self.define(i + 1, kind, 0, range, detail);
+        self.index.symbols[symbol].visible_from = 0;
+        self.bodies.push(i + 2);
+
+        let mut j = i + 3;
+        while j < close {
+            if matches!(self.token(j), Token::Identifier(_))
+                && matches!(self.token(j - 1), Token::LBrace | Token::Comma)
+            {
+                let last = if *self.token(j + 1) == Token::LParen {
+                    self.skip(j + 1) - 1
+                } else {
+                    j
+                };
+                let detail = format!("{}.{}", name, self.text(j, last));
+                let range = self.range(j, last);
+                self.define_member(j, member_kind, range, detail, Some(symbol));
+                // The names of a variant's values name nothing
+                for k in j + 1..=last {
+                    self.handled[k] = true;
+                }
+                j = last + 1;
+            } else {
+                j = self.skip(j);
+            }
+        }
+        close + 1
+    }
+
+    // `impl Name { methods }` at token `i`. Returns the token after it.
+    fn impl_block(&mut self, i: usize) -> usize {
+        let name = self.name(i + 1).unwrap_or_default();
+        let Some(open) = (*self.token(i + 2) == Token::LBrace).then_some(i + 2) else {
+            return i + 2;
+        };
+        let close = self.closers[open].unwrap_or(self.tokens.len() - 1);
+        self.bodies.push(open);
+        let parent = self
+            .index
+            .symbols
+            .iter()
+            .position(|s| s.kind == SymbolKind::Struct && s.name == name);
+
+        let mut j = open + 1;
+        while j < close {
+            if *self.token(j) == Token::Fn && self.name(j + 1).is_some() {
+                let body = self.function(j, j + 1, SymbolKind::Method, parent);
+                if parent.is_none() {
+                    // Methods of a struct from another module
+                    let method = self.index.symbols.len() - 1;
+                    self.index.symbols[method].detail =
+                        format!("impl {}: {}", name, self.index.symbols[method].detail);
+                }
+                j = body.map_or(j + 2, |body| self.skip(body));
+            } else {
+                j = self.skip(j);
+            }
+        }
+        close + 1
+    }
+
+    // `import module [as alias];` or `from module import names;` at token `i`.
+    // Returns the token after it.
+    fn import(&mut self, i: usize) -> usize {
+        let mut j = i + 1;
+        let first = j;
+        while matches!(self.token(j), Token::Identifier(_) | Token::Dot) {
+            self.handled[j] = true;
+            j += 1;
+        }
+        if j == first {
+            return j;
+        }
+        let module = self.text(first, j - 1).replace(' ', "");
+        // Up to the `;`, or the last token that can be part of the import
+        // while it's being typed
+        let mut end = j;
+        while end + 1 < self.tokens.len()
+            && matches!(
+                self.token(end),
+                Token::Identifier(_) | Token::Dot | Token::Comma | Token::As | Token::Import
+            )
+        {
+            end += 1;
+        }
+        if *self.token(end) != Token::Semicolon {
+            end -= 1;
+        }
+        let range = self.range(i, end);
+        let detail = self.text(
+            i,
+            if *self.token(end) == Token::Semicolon {
+                end - 1
+            } else {
+                end
+            },
+        );
+
+        if *self.token(i) == Token::Import {
+            let name = if *self.token(j) == Token::As && self.name(j + 1).is_some() {
+                j + 1
+            } else {
+                first
+            };
+            let symbol = self.define(name, SymbolKind::Module, 0, range, detail);
+            self.index.symbols[symbol].visible_from = 0;
+            self.index.symbols[symbol].module = Some(module);
+        } else if *self.token(j) == Token::Import {
+            for k in j + 1..=end {
+                if let Some(name) = self.name(k) {
+                    let symbol = self.define(
+                        k,
+                        SymbolKind::Imported,
+                        0,
+                        range,
+                        format!("from {} import {}", module, name),
+                    );
+                    self.index.symbols[symbol].visible_from = 0;
+                    self.index.symbols[symbol].module = Some(module.clone());
+                }
+            }
+        }
+        end + 1
+    }
+
+    // Scopes, local names and references, in source order
+    fn walk(&mut self) {
+        for i in 0..self.tokens.len() {
+            let statement_start = i == 0
+                || matches!(
+                    self.token(i - 1),
+                    Token::Semicolon | Token::LBrace | Token::RBrace
+                );
+            if *self.token(i) == Token::LBrace && statement_start && !self.bodies.contains(&i) {
+                // A block
+                if let Some(close) = self.closers[i] {
+                    self.new_scope(i, close, false);
+                }
+            }
+            if let Some(scopes) = self.pending.remove(&i) {
+                for scope in scopes {
+                    self.index.scopes[scope].parent = self.stack.last().copied();
+                    self.stack.push(scope);
+                }
+            }
+
+            match self.token(i).clone() {
+                Token::Let => self.let_declaration(i),
+                Token::Fn => self.function_scope(i),
+                Token::For => self.for_loop(i),
+                Token::Match => self.match_arms(i),
+                Token::LBrace => {
+                    let literal = i > 0
+                        && matches!(self.token(i - 1), Token::Identifier(_))
+                        && !self.bodies.contains(&i);
+                    let structure = if literal {
+                        self.reference_at
+                            .get(&(i - 1))
+                            .and_then(|&r| self.index.references[r].symbol)
+                            .filter(|&s| self.index.symbols[s].kind == SymbolKind::Struct)
+                    } else {
+                        None
+                    };
+                    self.structs.push(structure);
+                }
+                Token::RBrace => {
+                    self.structs.pop();
+                }
+                Token::Identifier(_) if !self.handled[i] => self.reference(i),
+                _ => {}
+            }
+
+            while self.stack.len() > 1 && self.ends[*self.stack.last().unwrap()] <= i {
+                self.stack.pop();
+            }
+        }
+    }
+
+    fn let_declaration(&mut self, i: usize) {
+        if self.name(i + 1).is_none() || self.handled[i + 1] {
+            return;
+        }
+        let start = if i > 0 && *self.token(i - 1) == Token::Pub {
+            i - 1
+        } else {
+            i
+        };
+        let mut end = i + 2;
+        while end < self.tokens.len()
+            && !matches!(self.token(end), Token::Semicolon | Token::RBrace)
+        {
+            end = self.skip(end);
+        }
+        let value = (i + 2..end)
+            .find(|&k| *self.token(k) == Token::Equal)
+            .unwrap_or(end);
+        let detail = self.text(start, value - 1);
+        let range = self.range(start, end);
+        // The variable is visible after its `let`, not in its own value
+        let visible_from = self.span(end.min(self.tokens.len() - 1)).end;
+        let scope = *self.stack.last().unwrap();
+        let symbol = self.define(i + 1, SymbolKind::Variable, scope, range, detail);
+        self.index.symbols[symbol].visible_from = visible_from;
+
+        // The struct of `let x: Struct` or `let x = Struct { ... }`
+        let type_name = match (self.token(i + 2), self.token(value + 2)) {
+            (Token::Colon, _) => self.name(i + 3),
+            (_, Token::LBrace) if *self.token(value) == Token::Equal => self.name(value + 1),
+            _ => None,
+        };
+        let instance_of = type_name
+            .and_then(|name| self.index.global(&name))
+            .filter(|&s| self.index.symbols[s].kind == SymbolKind::Struct);
+        self.index.symbols[symbol].instance_of = instance_of;
+    }
+
+    // The scope of a function or closure at token `i`, holding its parameters
+    fn function_scope(&mut self, i: usize) {
+        let named = self.name(i + 1).is_some();
+        let paren = if named { i + 2 } else { i + 1 };
+        if *self.token(paren) != Token::LParen {
+            return;
+        }
+        let close = self.skip(paren) - 1;
+        let mut after = close + 1;
+        if *self.token(after) == Token::Arrow {
+            after = self.skip_type(after + 1);
+        }
+        // A function type such as `fn(int) -> int` has no body
+        if *self.token(after) != Token::LBrace {
+            return;
+        }
+        let end = self.closers[after].unwrap_or(self.tokens.len() - 1);
+        self.bodies.push(after);
+
+        if named && !self.handled[i + 1] {
+            // A function defined inside another one
+            let detail = self.text(i, after - 1);
+            let range = self.range(i, end);
+            let scope = *self.stack.last().unwrap();
+            self.define(i + 1, SymbolKind::Function, scope, range, detail);
+        }
+        let scope = self.new_scope(paren, end, named);
+        let method_of = self
+            .index
+            .symbols
+            .iter()
+            .find(|s| s.kind == SymbolKind::Method && s.span == self.tokens[i + 1].1)
+            .and_then(|s| s.parent);
+        let mut j = paren + 1;
+        while j < close {
+            if matches!(self.token(j), Token::Identifier(_))
+                && matches!(self.token(j - 1), Token::LParen | Token::Comma)
+            {
+                let last = if *self.token(j + 1) == Token::Colon {
+                    self.skip_type(j + 2) - 1
+                } else {
+                    j
+                };
+                let detail = self.text(j, last);
+                let range = self.range(j, last);
+                let symbol = self.define(j, SymbolKind::Parameter, scope, range, detail);
+                if self.index.symbols[symbol].name == "self" {
+                    self.index.symbols[symbol].instance_of = method_of;
+                }
+                j = last + 1;
+            } else {
+                j = self.skip(j);
+            }
+        }
+    }
+
+    // `for (init; condition; update) { }` or `for names in iterable { }`
+    fn for_loop(&mut self, i: usize) {
+        if *self.token(i + 1) == Token::LParen {
+            let Some(body) = self.body(self.skip(i + 1)) else {
+                return;
+            };
+            let end = self.closers[body].unwrap_or(self.tokens.len() - 1);
+            self.new_scope(i + 1, end, false);
+            return;
+        }
+
+        let Some(in_token) = (i + 1..self.tokens.len())
+            .find(|&k| !matches!(self.token(k), Token::Identifier(_) | Token::Comma))
+        else {
+            return;
+        };
+        if *self.token(in_token) != Token::In {
+            return;
+        }
+        let Some(body) = self.body(in_token + 1) else {
+            return;
+        };
+        let end = self.closers[body].unwrap_or(self.tokens.len() - 1);
+        self.bodies.push(body);
+        let scope = self.new_scope(body, end, false);
+        for k in i + 1..in_token {
+            if self.name(k).is_some() {
+                let detail = format!("for {}", self.name(k).unwrap_or_default());
+                let range = self.range(k, k);
+                self.define(k, SymbolKind::Variable, scope, range, detail);
+            }
+        }
+    }
+
+    // A scope for each arm of the `match` at token `i`, holding the names its
+    // pattern binds
+    fn match_arms(&mut self, i: usize) {
+        let Some(open) = self.body(i + 1) else { return };
+        let Some(close) = self.closers[open] else {
+            return;
+        };
+        self.bodies.push(open);
+
+        let mut start = open + 1;
+        while start < close {
+            // The pattern and guard end at `=>`
+            let mut arrow = start;
+            while arrow < close && *self.token(arrow) != Token::FatArrow {
+                arrow = self.skip(arrow);
+            }
+            if arrow >= close {
+                return;
+            }
+            let guard = (start..arrow)
+                .find(|&k| *self.token(k) == Token::If)
+                .unwrap_or(arrow);
+
+            let (last, next) = if *self.token(arrow + 1) == Token::LBrace {
+                let end = self.skip(arrow + 1) - 1;
+                self.bodies.push(arrow + 1);
+                let next = if *self.token(end + 1) == Token::Comma {
+                    end + 2
+                } else {
+                    end + 1
+                };
+                (end, next)
+            } else {
+                let mut k = arrow + 1;
+                while k < close && *self.token(k) != Token::Comma {
+                    k = self.skip(k);
+                }
+                (k - 1, k + 1)
+            };
+
+            let scope = self.new_scope(start, last, false);
+            for k in start..guard {
+                let binding = matches!(self.token(k), Token::Identifier(name) if name != "_")
+                    && !matches!(
+                        self.token(k + 1),
+                        Token::LParen | Token::Dot | Token::LBrace | Token::Colon
+                    )
+                    && (k == 0 || *self.token(k - 1) != Token::Dot);
+                if binding {
+                    let detail = self.name(k).unwrap_or_default();
+                    let range = self.range(k, k);
+                    self.define(k, SymbolKind::Variable, scope, range, detail);
+                }
+            }
+            start = next;
+        }
+    }
+
+    // A use of a name at token `i`
+    fn reference(&mut self, i: usize) {
+        let name = self.name(i).unwrap_or_default();
+        let span = self.span(i);
+        let member = i >= 2 && *self.token(i - 1) == Token::Dot;
+        let object = if member {
+            self.reference_at.get(&(i - 2)).copied()
+        } else {
+            None
+        };
+
+        let symbol = if member {
+            match object.map(|r| {
+                self.index.references[r]
+                    .symbol
+                    .map(|s| (s, &self.index.symbols[s]))
+            }) {
+                // Members of other modules are looked up by the caller
+                Some(None) => None,
+                Some(Some((_, object)))
+                    if matches!(object.kind, SymbolKind::Module | SymbolKind::Imported) =>
+                {
+                    None
+                }
+                Some(Some((s, object)))
+                    if matches!(object.kind, SymbolKind::Struct | SymbolKind::Enum) =>
+                {
+                    self.member(&name, Some(s))
+                }
+                Some(Some((_, object))) if object.instance_of.is_some() => {
+                    self.member(&name, object.instance_of)
+                }
+                // The type of other objects isn't known
+                _ => self.member(&name, None),
+            }
+        } else if *self.token(i + 1) == Token::Colon
+            && i > 0
+            && matches!(self.token(i - 1), Token::LBrace | Token::Comma)
+        {
+            // A field of a struct literal or pattern
+            match self.structs.last().copied().flatten() {
+                Some(parent) => self.member(&name, Some(parent)),
+                None => self.lookup(&name, span.start),
+            }
+        } else {
+            self.lookup(&name, span.start)
+        };
+
+        self.index.references.push(Reference {
+            name,
+            span,
+            symbol,
+            object,
+        });
+        self.reference_at.insert(i, self.index.references.len() - 1);
+    }
+
+    // A field, variant or method called `name`: of `parent` if it is known,
+    // otherwise of any struct or enum
+    fn member(&self, name: &str, parent: Option<usize>) -> Option<usize> {
+        self.index.symbols.iter().position(|symbol| {
+            symbol.name == name
+                && symbol.kind.is_member()
+                && (parent.is_none() || symbol.parent == parent)
+        })
+    }
+
+    // The definition `name` refers to at byte `offset`: the innermost one,
+    // skipping the locals around a function
+    fn lookup(&self, name: &str, offset: usize) -> Option<usize> {
+        let mut in_function = false;
+        for &scope in self.stack.iter().rev() {
+            if in_function && scope != 0 {
+                continue;
+            }
+            if let Some(symbols) = self.names[scope].get(name) {
+                let found = symbols.iter().rev().find(|&&s| {
+                    self.index.symbols[s].visible_from <= offset || (scope == 0 && in_function)
+                });
+                if let Some(&symbol) = found {
+                    return Some(symbol);
+                }
+            }
+            in_function |= self.index.scopes[scope].function;
+        }
+        None
+    }
+}
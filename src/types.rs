@@ -0,0 +1,872 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    ArmBody, BinaryOp, Expression, InterpolationPart, Located, MatchArm, Pattern, Statement, Type,
+    UnaryOp,
+};
+use crate::names::Diagnostic;
+
+/// Check the type annotations of a program and infer the types of
+/// unannotated locals. Code without annotations is only reported when it is
+/// sure to fail at run time, such as `1 + true`.
+pub fn check(program: &[Located]) -> Vec<Diagnostic> {
+    let mut checker = TypeChecker {
+        types: HashSet::new(),
+        functions: HashMap::new(),
+        scopes: vec![HashMap::new()],
+        function: None,
+        return_type: None,
+        line: 1,
+        errors: Vec::new(),
+    };
+
+    for stmt in program {
+        match &stmt.statement {
+            Statement::Struct { name, .. } | Statement::Enum { name, .. } => {
+                checker.types.insert(name.clone());
+            }
+            _ => {}
+        }
+    }
+    // Functions can be called before they are defined
+    for stmt in program {
+        if let Statement::FunctionDef {
+            name,
+            is_main: false,
+            param_types,
+            return_type,
+            ..
+        } = &stmt.statement
+        {
+            checker
+                .functions
+                .insert(name.clone(), signature(param_types, return_type.as_ref()));
+        }
+    }
+    checker.statements(program);
+
+    checker.errors
+}
+
+/// The `Type::Function` of a function with these annotations. Parameters and
+/// results without one are `any`.
+pub fn signature(param_types: &[Option<Type>], return_type: Option<&Type>) -> Type {
+    Type::Function {
+        params: param_types
+            .iter()
+            .map(|ty| ty.clone().unwrap_or(Type::Any))
+            .collect(),
+        return_type: Box::new(return_type.cloned().unwrap_or(Type::Any)),
+    }
+}
+
+/// The type of `left op right`, or `None` if the operator can't be applied
+/// to operands of these types
+pub fn binary_type(left: &Type, op: &BinaryOp, right: &Type) -> Option<Type> {
+    use BinaryOp::*;
+
+    match (left, right) {
+        (Type::Any, _) | (_, Type::Any) => Some(match op {
+            Equal | NotEqual | LessThan | GreaterThan | LessThanOrEqual | GreaterThanOrEqual
+            | And | Or => Type::Bool,
+            _ => Type::Any,
+        }),
+        (Type::Int, Type::Int) => match op {
+            Add | Subtract | Multiply | Divide | Modulo | BitAnd | BitOr | BitXor | ShiftLeft
+            | ShiftRight => Some(Type::Int),
+            Equal | NotEqual | LessThan | GreaterThan | LessThanOrEqual | GreaterThanOrEqual => {
+                Some(Type::Bool)
+            }
+            And | Or => None,
+        },
+        (Type::Int | Type::Float, Type::Int | Type::Float) => match op {
+            Add | Subtract | Multiply | Divide | Modulo => Some(Type::Float),
+            Equal | NotEqual | LessThan | GreaterThan | LessThanOrEqual | GreaterThanOrEqual => {
+                Some(Type::Bool)
+            }
+            _ => None,
+        },
+        (Type::Bool, Type::Bool) => match op {
+            And | Or | BitAnd | BitOr | BitXor | Equal | NotEqual => Some(Type::Bool),
+            _ => None,
+        },
+        (Type::String, Type::String) => match op {
+            Add => Some(Type::String),
+            Equal | NotEqual => Some(Type::Bool),
+            _ => None,
+        },
+        (Type::String, Type::Int | Type::Float) | (Type::Int | Type::Float, Type::String) => {
+            match op {
+                Add => Some(Type::String),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// A variable in scope. An annotated variable keeps its type; an inferred one
+// becomes `any` once it is assigned a value of another type.
+struct Binding {
+    ty: Type,
+    annotated: bool,
+}
+
+struct TypeChecker {
+    types: HashSet<String>,           // Structs and enums declared in the program
+    functions: HashMap<String, Type>, // Top-level functions -> Type::Function
+    scopes: Vec<HashMap<String, Binding>>,
+    function: Option<String>,  // Function being checked, for messages
+    return_type: Option<Type>, // Declared return type of that function
+    line: usize,               // Line of the statement being checked
+    errors: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    fn error(&mut self, message: String) {
+        let location = match &self.function {
+            Some(function) => format!(" in function '{}'", function),
+            None => String::new(),
+        };
+        self.errors.push(Diagnostic {
+            line: self.line,
+            message: format!("{}{}", message, location),
+        });
+    }
+
+    fn declare(&mut self, name: &str, ty: Type, annotated: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Binding { ty, annotated });
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // Check the statements in a new scope
+    fn block(&mut self, statements: &[Located]) {
+        self.scopes.push(HashMap::new());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &[Located]) {
+        let outer = self.line;
+        for stmt in statements {
+            self.line = stmt.line;
+            self.statement(stmt);
+        }
+        self.line = outer;
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let {
+                name, ty, value, ..
+            } => match ty {
+                Some(ty) => {
+                    let ty = self.check_annotation(ty);
+                    let actual = self.expected(&ty, value);
+                    if !assignable(&ty, &actual) {
+                        self.error(format!(
+                            "Variable '{}' has type {} but is initialized with {}",
+                            name, ty, actual
+                        ));
+                    }
+                    self.declare(name, ty, true);
+                }
+                None => {
+                    let actual = self.expression(value);
+                    self.declare(name, actual, false);
+                }
+            },
+            Statement::Assign { name, value } => {
+                let actual = self.expression(value);
+                self.assign(name, &actual);
+            }
+            Statement::FunctionDef {
+                name,
+                is_main,
+                params,
+                param_types,
+                return_type,
+                body,
+                ..
+            } => {
+                let name = if *is_main {
+                    "main".to_string()
+                } else {
+                    name.clone()
+                };
+                self.function_body(name, params, param_types, return_type.as_ref(), body, None);
+            }
+            Statement::Impl { name, methods } => {
+                for method in methods {
+                    self.line = method.line;
+                    if let Statement::FunctionDef {
+                        name: method,
+                        params,
+                        param_types,
+                        return_type,
+                        body,
+                        ..
+                    } = &method.statement
+                    {
+                        let self_type = Type::Named(name.clone());
+                        let qualified = format!("{}.{}", name, method);
+                        self.function_body(
+                            qualified,
+                            params,
+                            param_types,
+                            return_type.as_ref(),
+                            body,
+                            Some(self_type),
+                        );
+                    }
+                }
+            }
+            Statement::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+            }
+            Statement::Return(expr) => {
+                let expected = self.return_type.clone();
+                let actual = match &expected {
+                    Some(expected) => self.expected(expected, expr),
+                    None => self.expression(expr),
+                };
+                if let Some(expected) = expected {
+                    // `return;` is parsed as `return false;`
+                    let bare_return = expected == Type::Void && *expr == Expression::Bool(false);
+                    if !bare_return && !assignable(&expected, &actual) {
+                        self.error(format!(
+                            "Return value must be {}, found {}",
+                            expected, actual
+                        ));
+                    }
+                }
+            }
+            Statement::ExprStmt(expr) | Statement::Print(expr) | Statement::Expression(expr) => {
+                self.expression(expr);
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition("if", condition);
+                self.block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch);
+                }
+            }
+            Statement::While { condition, body } => {
+                self.condition("while", condition);
+                self.block(body);
+            }
+            Statement::For {
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                self.scopes.push(HashMap::new());
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.condition("for", condition);
+                }
+                if let Some(update) = update {
+                    self.statement(update);
+                }
+                self.block(body);
+                self.scopes.pop();
+            }
+            Statement::ForIn {
+                names,
+                iterable,
+                body,
+            } => {
+                let iterable = self.expression(iterable);
+                let element = match &iterable {
+                    Type::Array(element) => (**element).clone(),
+                    Type::Map(key, _) if names.len() == 1 => (**key).clone(),
+                    Type::Map(key, value) => Type::Array(Box::new(unify(key, value))),
+                    Type::String => Type::String,
+                    Type::Range => Type::Int,
+                    Type::Int | Type::Float | Type::Bool | Type::Void | Type::Function { .. } => {
+                        self.error(format!("Cannot iterate over {}", iterable));
+                        Type::Any
+                    }
+                    Type::Named(_) | Type::Any => Type::Any,
+                };
+
+                self.scopes.push(HashMap::new());
+                match (names.as_slice(), &element, &iterable) {
+                    ([name], _, _) => self.declare(name, element.clone(), false),
+                    ([key_name, value_name], _, Type::Map(key, value)) => {
+                        self.declare(key_name, (**key).clone(), false);
+                        self.declare(value_name, (**value).clone(), false);
+                    }
+                    (names, Type::Array(pair), _) => {
+                        for name in names {
+                            self.declare(name, (**pair).clone(), false);
+                        }
+                    }
+                    (names, _, _) => {
+                        for name in names {
+                            self.declare(name, Type::Any, false);
+                        }
+                    }
+                }
+                self.statements(body);
+                self.scopes.pop();
+            }
+            Statement::Block(statements) => self.block(statements),
+            Statement::Try { body, catch } => {
+                self.block(body);
+                self.block(catch);
+            }
+            Statement::Struct { .. }
+            | Statement::Enum { .. }
+            | Statement::Import { .. }
+            | Statement::ImportFrom { .. } => {}
+        }
+    }
+
+    fn function_body(
+        &mut self,
+        name: String,
+        params: &[String],
+        param_types: &[Option<Type>],
+        return_type: Option<&Type>,
+        body: &[Located],
+        self_type: Option<Type>,
+    ) {
+        let outer_function = self.function.replace(name);
+        // Function bodies don't see the locals of the code around them
+        let outer_scopes = std::mem::replace(&mut self.scopes, vec![HashMap::new()]);
+
+        for (param, ty) in params.iter().zip(param_types) {
+            match (ty, &self_type) {
+                (Some(ty), _) => {
+                    let ty = self.check_annotation(ty);
+                    self.declare(param, ty, true);
+                }
+                (None, Some(self_type)) if param == "self" => {
+                    self.declare(param, self_type.clone(), true)
+                }
+                (None, _) => self.declare(param, Type::Any, false),
+            }
+        }
+        let return_type = return_type.map(|ty| self.check_annotation(ty));
+        let outer_return = std::mem::replace(&mut self.return_type, return_type.clone());
+
+        self.statements(body);
+
+        if let Some(return_type) = return_type.filter(|ty| !matches!(ty, Type::Void | Type::Any))
+            && !always_returns(body)
+        {
+            self.error(format!(
+                "Not all paths return a value of type {}",
+                return_type
+            ));
+        }
+
+        self.scopes = outer_scopes;
+        self.return_type = outer_return;
+        self.function = outer_function;
+    }
+
+    // Report struct and enum names in an annotation that the program doesn't
+    // declare, and treat them as `any` so they cause no further errors
+    fn check_annotation(&mut self, ty: &Type) -> Type {
+        match ty {
+            Type::Named(name) if !name.contains('.') && !self.types.contains(name) => {
+                self.error(format!("Unknown type '{}'", name));
+                Type::Any
+            }
+            Type::Array(element) => Type::Array(Box::new(self.check_annotation(element))),
+            Type::Map(key, value) => Type::Map(
+                Box::new(self.check_annotation(key)),
+                Box::new(self.check_annotation(value)),
+            ),
+            Type::Function {
+                params,
+                return_type,
+            } => Type::Function {
+                params: params
+                    .iter()
+                    .map(|param| self.check_annotation(param))
+                    .collect(),
+                return_type: Box::new(self.check_annotation(return_type)),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    fn condition(&mut self, statement: &str, condition: &Expression) {
+        let ty = self.expression(condition);
+        if !assignable(&Type::Bool, &ty) {
+            self.error(format!(
+                "Condition of '{}' must be bool, found {}",
+                statement, ty
+            ));
+        }
+    }
+
+    // The type of `value` where a value of type `expected` is wanted. Arrays
+    // may mix types, so an array literal is checked element by element and
+    // the first element that doesn't fit is reported.
+    fn expected(&mut self, expected: &Type, value: &Expression) -> Type {
+        let (Type::Array(element), Expression::Array { elements }) = (expected, value) else {
+            return self.expression(value);
+        };
+        let mut mismatch = None;
+        for (i, value) in elements.iter().enumerate() {
+            let actual = self.expected(element, value);
+            if mismatch.is_none() && !assignable(element, &actual) {
+                mismatch = Some((i, actual));
+            }
+        }
+        if let Some((i, actual)) = mismatch {
+            self.error(format!(
+                "Array element {} must be {}, found {}",
+                i + 1,
+                element,
+                actual
+            ));
+        }
+        expected.clone()
+    }
+
+    fn assign(&mut self, name: &str, actual: &Type) {
+        let error = match self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+        {
+            Some(binding) if binding.annotated => {
+                if assignable(&binding.ty, actual) {
+                    None
+                } else {
+                    Some(format!(
+                        "Cannot assign {} to variable '{}' of type {}",
+                        actual, name, binding.ty
+                    ))
+                }
+            }
+            Some(binding) => {
+                if binding.ty != *actual {
+                    binding.ty = Type::Any;
+                }
+                None
+            }
+            None => None,
+        };
+        if let Some(error) = error {
+            self.error(error);
+        }
+    }
+
+    fn arms(&mut self, arms: &[MatchArm]) {
+        for arm in arms {
+            // Pattern bindings are not typed
+            self.scopes.push(HashMap::new());
+            let mut bindings = Vec::new();
+            pattern_bindings(&arm.pattern, &mut bindings);
+            for binding in bindings {
+                self.declare(&binding, Type::Any, false);
+            }
+            if let Some(guard) = &arm.guard {
+                self.expression(guard);
+            }
+            match &arm.body {
+                ArmBody::Expression(expr) => {
+                    self.expression(expr);
+                }
+                ArmBody::Block(statements) => self.statements(statements),
+            }
+            self.scopes.pop();
+        }
+    }
+
+    // The type of `expr`, reporting the errors inside it
+    fn expression(&mut self, expr: &Expression) -> Type {
+        match expr {
+            Expression::Number(_) | Expression::BigInt(_) => Type::Int,
+            Expression::Float(_) => Type::Float,
+            Expression::Bool(_) => Type::Bool,
+            Expression::String(_) => Type::String,
+            Expression::Interpolation { parts } => {
+                for part in parts {
+                    if let InterpolationPart::Expression { expr, .. } = part {
+                        self.expression(expr);
+                    }
+                }
+                Type::String
+            }
+            Expression::Identifier(name, _) => match self.lookup(name) {
+                Some(binding) => binding.ty.clone(),
+                None => self.functions.get(name).cloned().unwrap_or(Type::Any),
+            },
+            Expression::Binary { left, op, right } => {
+                let left = self.expression(left);
+                let right = self.expression(right);
+                self.binary(&left, op, &right)
+            }
+            Expression::Unary { op, expr } => {
+                let ty = self.expression(expr);
+                match (op, &ty) {
+                    (_, Type::Any) => Type::Any,
+                    (UnaryOp::Negate, Type::Int | Type::Float) => ty,
+                    (UnaryOp::Not, Type::Bool) => Type::Bool,
+                    (UnaryOp::BitNot, Type::Int) => Type::Int,
+                    _ => {
+                        self.error(format!("Operator '{}' cannot be applied to {}", op, ty));
+                        Type::Any
+                    }
+                }
+            }
+            Expression::CompoundAssign { target, op, value } => {
+                let current = self.expression(target);
+                let value = self.expression(value);
+                let result = self.binary(&current, op, &value);
+                if let Expression::Identifier(name, _) = &**target {
+                    self.assign(name, &result);
+                }
+                result
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition("?:", condition);
+                let then_type = self.expression(then_branch);
+                let else_type = self.expression(else_branch);
+                unify(&then_type, &else_type)
+            }
+            Expression::Range { start, end } => {
+                for bound in [start, end] {
+                    let ty = self.expression(bound);
+                    if !assignable(&Type::Int, &ty) {
+                        self.error(format!("Range bounds must be int, found {}", ty));
+                    }
+                }
+                Type::Range
+            }
+            Expression::Call { callee, arguments } => self.call(callee, arguments),
+            Expression::Array { elements } => {
+                let types: Vec<Type> = elements.iter().map(|e| self.expression(e)).collect();
+                Type::Array(Box::new(unify_all(&types)))
+            }
+            Expression::Map { entries } => {
+                let (keys, values): (Vec<Type>, Vec<Type>) = entries
+                    .iter()
+                    .map(|(key, value)| (self.expression(key), self.expression(value)))
+                    .unzip();
+                Type::Map(Box::new(unify_all(&keys)), Box::new(unify_all(&values)))
+            }
+            Expression::StructLiteral { name, fields } => {
+                for (_, value) in fields {
+                    self.expression(value);
+                }
+                Type::Named(name.clone())
+            }
+            Expression::Match { subject, arms } => {
+                self.expression(subject);
+                self.arms(arms);
+                Type::Any
+            }
+            Expression::ArrayAccess { array, index } => {
+                let container = self.expression(array);
+                let index = self.expression(index);
+                match &container {
+                    Type::Array(element) => {
+                        if !assignable(&Type::Int, &index) {
+                            self.error(format!("Array index must be int, found {}", index));
+                        }
+                        (**element).clone()
+                    }
+                    Type::String => {
+                        if !assignable(&Type::Int, &index) {
+                            self.error(format!("String index must be int, found {}", index));
+                        }
+                        Type::String
+                    }
+                    Type::Map(key, value) => {
+                        if !assignable(key, &index) {
+                            self.error(format!("Map key must be {}, found {}", key, index));
+                        }
+                        (**value).clone()
+                    }
+                    Type::Int
+                    | Type::Float
+                    | Type::Bool
+                    | Type::Void
+                    | Type::Range
+                    | Type::Function { .. } => {
+                        self.error(format!("Cannot index {}", container));
+                        Type::Any
+                    }
+                    Type::Named(_) | Type::Any => Type::Any,
+                }
+            }
+            Expression::FieldAccess { object, .. } => {
+                self.expression(object);
+                Type::Any
+            }
+            Expression::Closure { params, body } => {
+                // Closures see the enclosing scopes, but their `return` is their own
+                let outer_return = self.return_type.take();
+                self.scopes.push(HashMap::new());
+                for param in params {
+                    self.declare(param, Type::Any, false);
+                }
+                self.statements(body);
+                self.scopes.pop();
+                self.return_type = outer_return;
+                Type::Function {
+                    params: vec![Type::Any; params.len()],
+                    return_type: Box::new(Type::Any),
+                }
+            }
+        }
+    }
+
+    // `target[index] = value`, checked like reading the element. The value
+    // must fit the element type when the target's variable is annotated;
+    // unannotated variables may change type.
+    fn element_assignment(&mut self, target: &Type, index: &Type, value: &Type, annotated: bool) {
+        match target {
+            Type::Array(element) => {
+                if !assignable(&Type::Int, index) {
+                    self.error(format!("Array index must be int, found {}", index));
+                }
+                if annotated && !assignable(element, value) {
+                    self.error(format!(
+                        "Cannot assign {} to an element of {}",
+                        value, target
+                    ));
+                }
+            }
+            Type::Map(key, element) => {
+                if !assignable(key, index) {
+                    self.error(format!("Map key must be {}, found {}", key, index));
+                }
+                if annotated && !assignable(element, value) {
+                    self.error(format!(
+                        "Cannot assign {} to an element of {}",
+                        value, target
+                    ));
+                }
+            }
+            Type::Named(_) | Type::Any => {}
+            _ => self.error(format!("Cannot assign to an element of {}", target)),
+        }
+    }
+
+    // Whether `target`, such as `grid[0]` or `p.items`, is part of a variable
+    // with a type annotation
+    fn annotated(&self, target: &Expression) -> bool {
+        match target {
+            Expression::Identifier(name, _) => {
+                self.lookup(name).is_some_and(|binding| binding.annotated)
+            }
+            Expression::ArrayAccess { array: object, .. }
+            | Expression::FieldAccess { object, .. } => self.annotated(object),
+            _ => false,
+        }
+    }
+
+    fn call(&mut self, callee: &Expression, arguments: &[Expression]) -> Type {
+        let args: Vec<Type> = arguments.iter().map(|a| self.expression(a)).collect();
+
+        let name = match callee {
+            Expression::Identifier(name, _) => name,
+            // Methods, module functions and enum variants are not typed
+            Expression::FieldAccess { object, .. } => {
+                self.expression(object);
+                return Type::Any;
+            }
+            _ => {
+                self.expression(callee);
+                return Type::Any;
+            }
+        };
+
+        // `__array_assign(target, index, value)` and `__field_assign` stand for assignments
+        if let ("__array_assign" | "__field_assign", [target, key, value]) =
+            (name.as_str(), args.as_slice())
+        {
+            if name == "__array_assign" {
+                let annotated = self.annotated(&arguments[0]);
+                self.element_assignment(target, key, value, annotated);
+            } else if !matches!(target, Type::Named(_) | Type::Any) {
+                let field = match &arguments[1] {
+                    Expression::String(field) => field.as_str(),
+                    _ => "?",
+                };
+                self.error(format!("Cannot assign field '{}' of {}", field, target));
+            }
+            return value.clone();
+        }
+
+        let function = match self.lookup(name) {
+            Some(binding) => binding.ty.clone(),
+            None => match self.functions.get(name) {
+                Some(signature) => signature.clone(),
+                None => return builtin_type(name, &args),
+            },
+        };
+        match function {
+            Type::Function {
+                params,
+                return_type,
+            } => {
+                // Wrong argument counts are reported at run time
+                if params.len() == args.len() {
+                    for (i, (param, arg)) in params.iter().zip(&args).enumerate() {
+                        if !assignable(param, arg) {
+                            self.error(format!(
+                                "Argument {} of '{}' must be {}, found {}",
+                                i + 1,
+                                name,
+                                param,
+                                arg
+                            ));
+                        }
+                    }
+                }
+                *return_type
+            }
+            Type::Any => Type::Any,
+            other => {
+                self.error(format!("Cannot call '{}' of type {}", name, other));
+                Type::Any
+            }
+        }
+    }
+
+    // The type of `left op right`, reporting operands it can't be applied to
+    fn binary(&mut self, left: &Type, op: &BinaryOp, right: &Type) -> Type {
+        binary_type(left, op, right).unwrap_or_else(|| {
+            self.error(format!(
+                "Operator '{}' cannot be applied to {} and {}",
+                op, left, right
+            ));
+            Type::Any
+        })
+    }
+}
+
+// Whether a value of type `actual` can be stored where `expected` is declared
+fn assignable(expected: &Type, actual: &Type) -> bool {
+    match (expected, actual) {
+        (Type::Any, _) | (_, Type::Any) => true,
+        (Type::Array(expected), Type::Array(actual)) => assignable(expected, actual),
+        (Type::Map(expected_key, expected_value), Type::Map(actual_key, actual_value)) => {
+            assignable(expected_key, actual_key) && assignable(expected_value, actual_value)
+        }
+        (
+            Type::Function {
+                params: expected_params,
+                return_type: expected_return,
+            },
+            Type::Function {
+                params: actual_params,
+                return_type: actual_return,
+            },
+        ) => {
+            expected_params.len() == actual_params.len()
+                && expected_params
+                    .iter()
+                    .zip(actual_params)
+                    .all(|(e, a)| assignable(a, e))
+                && assignable(expected_return, actual_return)
+        }
+        // `geo.Point` and `Point` are the same type inside module `geo`
+        (Type::Named(expected), Type::Named(actual)) => {
+            expected.rsplit('.').next() == actual.rsplit('.').next()
+        }
+        _ => expected == actual,
+    }
+}
+
+// The type of a value that is either `a` or `b`
+fn unify(a: &Type, b: &Type) -> Type {
+    if a == b { a.clone() } else { Type::Any }
+}
+
+fn unify_all(types: &[Type]) -> Type {
+    match types.split_first() {
+        Some((first, rest)) => rest.iter().fold(first.clone(), |ty, next| unify(&ty, next)),
+        None => Type::Any,
+    }
+}
+
+// Result types of the built-in functions
+fn builtin_type(name: &str, args: &[Type]) -> Type {
+    match (name, args) {
+        ("len", _) => Type::Int,
+        ("has", _) => Type::Bool,
+        ("args", _) => Type::Array(Box::new(Type::String)),
+        ("input" | "read_line", _) => Type::String,
+        ("keys", [Type::Map(key, _)]) => Type::Array(key.clone()),
+        ("values", [Type::Map(_, value)]) => Type::Array(value.clone()),
+        ("keys" | "values", _) => Type::Array(Box::new(Type::Any)),
+        ("remove", [map @ Type::Map(..), _]) => map.clone(),
+        _ => Type::Any,
+    }
+}
+
+/// Whether running `statements` always ends in a `return`
+pub fn always_returns(statements: &[Located]) -> bool {
+    statements.iter().any(|stmt| match &stmt.statement {
+        Statement::Return(_) => true,
+        Statement::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => always_returns(then_branch) && always_returns(else_branch),
+        Statement::Block(statements) => always_returns(statements),
+        Statement::Try { body, catch } => always_returns(body) && always_returns(catch),
+        Statement::Match { arms, .. } => {
+            !arms.is_empty()
+                && arms
+                    .iter()
+                    .all(|arm| matches!(&arm.body, ArmBody::Block(body) if always_returns(body)))
+        }
+        _ => false,
+    })
+}
+
+/// Names bound by a match pattern
+pub fn pattern_bindings(pattern: &Pattern, bindings: &mut Vec<String>) {
+    match pattern {
+        Pattern::Binding(name) => bindings.push(name.clone()),
+        Pattern::Array { elements, rest } => {
+            for element in elements {
+                pattern_bindings(element, bindings);
+            }
+            if let Some(Some(rest)) = rest {
+                bindings.push(rest.clone());
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for (_, field) in fields {
+                pattern_bindings(field, bindings);
+            }
+        }
+        Pattern::Enum { fields, .. } => {
+            for field in fields {
+                pattern_bindings(field, bindings);
+            }
+        }
+        Pattern::Wildcard | Pattern::Literal(_) => {}
+    }
+}
@@ -0,0 +1,70 @@
+use kode_compiler::{Interpreter, MemoryIo};
+
+// What `source` prints, or its error
+fn run(source: &str) -> Result<String, String> {
+    let io = MemoryIo::default();
+    let mut kode = Interpreter::new();
+    kode.set_io(io.clone());
+    kode.eval(source)?;
+    Ok(io.stdout_text())
+}
+
+#[test]
+fn overflow_promotes_to_big_integers() {
+    assert_eq!(
+        run("print 9223372036854775807 + 1;").unwrap(),
+        "9223372036854775808\n"
+    );
+    assert_eq!(
+        run("print -9223372036854775807 - 2;").unwrap(),
+        "-9223372036854775809\n"
+    );
+    assert_eq!(
+        run("print 9223372036854775807 * 9223372036854775807;").unwrap(),
+        "85070591730234615847396907784232501249\n"
+    );
+    assert_eq!(
+        run("print 99999999999999999999999;").unwrap(),
+        "99999999999999999999999\n"
+    );
+}
+
+#[test]
+fn big_integers_that_fit_are_numbers_again() {
+    assert_eq!(
+        run("let big = 9223372036854775807 + 1;\nprint big - 1;\nprint [10, 20][big - big + 1];")
+            .unwrap(),
+        "9223372036854775807\n20\n"
+    );
+    assert_eq!(
+        run("print (9223372036854775807 + 1) % 7;\nprint 99999999999999999999 > 5;").unwrap(),
+        "1\ntrue\n"
+    );
+}
+
+#[test]
+fn big_integer_errors() {
+    let error = run("print (9223372036854775807 + 1) / 0;").unwrap_err();
+    assert!(error.contains("Division by zero"), "{}", error);
+    let error = run("print 1 << 70;").unwrap_err();
+    assert!(error.contains("out of range"), "{}", error);
+}
+
+#[test]
+fn big_integer_index_is_out_of_bounds() {
+    let error = run("let a = [1, 2];\nprint a[9223372036854775807 + 1];").unwrap_err();
+    assert_eq!(
+        error,
+        "Array index out of bounds: 9223372036854775808 (array length: 2)"
+    );
+    let error = run("let a = [1, 2];\na[9223372036854775807 + 1] = 3;").unwrap_err();
+    assert_eq!(
+        error,
+        "Array index out of bounds: 9223372036854775808 (array length: 2)"
+    );
+    let error = run("print \"ab\"[-9223372036854775807 - 2];").unwrap_err();
+    assert_eq!(
+        error,
+        "String index out of bounds: -9223372036854775809 (string length: 2)"
+    );
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{kode, temp_dir, write};
+
+// Errors reported by `kode check` for `source`
+fn check(name: &str, source: &str) -> String {
+    let dir = temp_dir(name);
+    write(&dir, "main.kode", source);
+    let output = kode(&dir, &["check", "main.kode"]);
+    String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr)
+}
+
+#[test]
+fn array_literal_is_checked_against_its_annotation() {
+    let errors = check("check-array", "let a: [int] = [1, 2, \"x\"];\n");
+    assert!(
+        errors.contains("Array element 3 must be int, found string"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("Found 1 error"), "{}", errors);
+
+    let errors = check(
+        "check-array-return",
+        "fn f() -> [[int]] { return [[1], [true]]; }\n",
+    );
+    assert!(
+        errors.contains("Array element 1 must be int, found bool in function 'f'"),
+        "{}",
+        errors
+    );
+}
+
+#[test]
+fn arrays_may_mix_types_without_an_annotation() {
+    let errors = check(
+        "check-mixed",
+        "let mixed = [1, \"two\", true];\nlet names: [string] = [\"ann\", \"bob\"];\nlet none: [int] = [];\n",
+    );
+    assert!(errors.contains("No errors"), "{}", errors);
+}
+
+#[test]
+fn errors_show_the_file_and_line() {
+    let errors = check(
+        "check-lines",
+        "let w: float = 1.0;\n\nfn f() {\n    let v: float = 2;\n}\n",
+    );
+    assert!(
+        errors.contains("main.kode:4: error: Variable 'v' has type float but is initialized with int in function 'f'"),
+        "{}",
+        errors
+    );
+}
+
+#[test]
+fn element_assignments_are_checked() {
+    let errors = check(
+        "check-elements",
+        "let arr: [int] = [1];\narr[0] = \"s\";\narr[0] = 2;\nlet ages: {string: int} = {};\nages[\"ann\"] = true;\nlet n = 5;\nn[0] = 1;\nn.x = 1;\n",
+    );
+    for expected in [
+        "main.kode:2: error: Cannot assign string to an element of [int]",
+        "main.kode:5: error: Cannot assign bool to an element of {string: int}",
+        "main.kode:7: error: Cannot assign to an element of int",
+        "main.kode:8: error: Cannot assign field 'x' of int",
+        "Found 4 errors",
+    ] {
+        assert!(errors.contains(expected), "{}", errors);
+    }
+
+    // Unannotated variables may change type
+    let errors = check("check-unannotated", "let b = [1];\nb[0] = \"s\";\n");
+    assert!(errors.contains("No errors"), "{}", errors);
+}
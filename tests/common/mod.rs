@@ -0,0 +1,41 @@
+// Helpers shared by the integration tests; each test file uses some of them
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use kode_compiler::{Interpreter, MemoryIo};
+
+/// A new empty directory for one test, removed first if an earlier run left it
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kode-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+    dir
+}
+
+/// Write `contents` to `file` under `dir`, creating its parent directories
+pub fn write(dir: &Path, file: &str, contents: &str) {
+    let path = dir.join(file);
+    fs::create_dir_all(path.parent().unwrap()).expect("failed to create test directory");
+    fs::write(path, contents).expect("failed to write test file");
+}
+
+/// Run the `kode` binary with `args` in `dir`
+pub fn kode(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_kode"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run kode")
+}
+
+/// An interpreter that imports modules from `dir` and prints to the returned buffer
+pub fn interpreter(dir: &Path) -> (Interpreter, MemoryIo) {
+    let io = MemoryIo::default();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_base_path(dir);
+    interpreter.set_io(io.clone());
+    (interpreter, io)
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{Value, json};
+
+use common::{temp_dir, write};
+
+// A `kode debug` session, driven one request at a time as an editor would
+struct Session {
+    adapter: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    seq: u64,
+    events: Vec<Value>, // Events read while waiting for a response
+}
+
+impl Session {
+    fn start(dir: &Path, file: &str) -> Session {
+        let mut adapter = Command::new(env!("CARGO_BIN_EXE_kode"))
+            .args(["debug", file])
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run kode debug");
+        Session {
+            input: adapter.stdin.take().unwrap(),
+            output: BufReader::new(adapter.stdout.take().unwrap()),
+            adapter,
+            seq: 0,
+            events: Vec::new(),
+        }
+    }
+
+    fn read(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            assert!(
+                self.output.read_line(&mut line).unwrap() > 0,
+                "kode debug ended"
+            );
+            match line.trim_end().strip_prefix("Content-Length: ") {
+                Some(value) => length = value.parse().unwrap(),
+                None if line.trim_end().is_empty() => break,
+                None => {}
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    // Send a request and return the body of its response, which must succeed
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let body = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments })
+            .to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+        loop {
+            let message = self.read();
+            if message["type"] == "response" && message["request_seq"] == self.seq {
+                assert_eq!(message["success"], true, "{}", message);
+                return message["body"].clone();
+            }
+            self.events.push(message);
+        }
+    }
+
+    // Wait for event `name` and return its body
+    fn event(&mut self, name: &str) -> Value {
+        if let Some(index) = self.events.iter().position(|e| e["event"] == name) {
+            return self.events.remove(index)["body"].clone();
+        }
+        loop {
+            let message = self.read();
+            if message["event"] == name {
+                return message["body"].clone();
+            }
+            // Rather than waiting forever for a stop that never comes
+            assert_ne!(message["event"], "terminated", "ended before '{}'", name);
+            self.events.push(message);
+        }
+    }
+
+    // Where the program stopped: the line of each frame, innermost first, and
+    // the locals of the innermost one
+    fn stopped(&mut self, reason: &str) -> (Vec<u64>, Vec<(String, String)>) {
+        let stopped = self.event("stopped");
+        assert_eq!(stopped["reason"], reason, "{}", stopped);
+
+        let trace = self.request("stackTrace", json!({ "threadId": 1 }));
+        let frames = trace["stackFrames"].as_array().unwrap();
+        let lines = frames.iter().map(|f| f["line"].as_u64().unwrap()).collect();
+        let scopes = self.request("scopes", json!({ "frameId": frames[0]["id"] }));
+        assert_eq!(scopes["scopes"][0]["name"], "Locals", "{}", scopes);
+        let variables = self.request(
+            "variables",
+            json!({ "variablesReference": scopes["scopes"][0]["variablesReference"] }),
+        );
+        let locals = variables["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| {
+                (
+                    v["name"].as_str().unwrap().to_string(),
+                    v["value"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        (lines, locals)
+    }
+}
+
+fn locals(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn breakpoints_and_stepping() {
+    let dir = temp_dir("debug-session");
+    write(
+        &dir,
+        "main.kode",
+        "fn square(n) {\n    let result = n * n;\n    return result;\n}\nlet total = 0;\nfor i in 0..5 {\n    total = total + square(i);\n}\nprint total;\n",
+    );
+    let mut session = Session::start(&dir, "main.kode");
+
+    session.request("initialize", json!({ "adapterID": "kode" }));
+    session.event("initialized");
+    let breakpoints = session.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": dir.join("main.kode") },
+            "breakpoints": [{ "line": 2, "condition": "n == 3" }],
+        }),
+    );
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    session.request("launch", json!({ "program": "main.kode" }));
+    session.request("configurationDone", json!({}));
+
+    // The condition holds on the fourth call only, made from line 7
+    assert_eq!(
+        session.stopped("breakpoint"),
+        (vec![2, 7], locals(&[("n", "3")]))
+    );
+
+    session.request("next", json!({ "threadId": 1 }));
+    assert_eq!(
+        session.stopped("step"),
+        (vec![3, 7], locals(&[("n", "3"), ("result", "9")]))
+    );
+
+    // Out of `square`, at the loop body for the next `i`
+    session.request("stepOut", json!({ "threadId": 1 }));
+    let (lines, variables) = session.stopped("step");
+    assert_eq!(lines, vec![7]);
+    assert!(
+        variables.contains(&("i".to_string(), "4".to_string())),
+        "{:?}",
+        variables
+    );
+
+    session.request("continue", json!({ "threadId": 1 }));
+    let output = session.event("output");
+    assert_eq!(output["output"], "30\n");
+    assert_eq!(session.event("exited")["exitCode"], 0);
+    session.event("terminated");
+
+    session.request("disconnect", json!({}));
+    assert!(session.adapter.wait().unwrap().success());
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use kode_compiler::{Interpreter, MemoryIo, Value};
+
+#[test]
+fn eval_keeps_definitions_between_calls() {
+    let mut kode = Interpreter::new();
+    kode.eval("let total = 1;\nfn add(n) { total = total + n; return total; }")
+        .unwrap();
+
+    assert!(matches!(kode.eval("add(2);").unwrap(), Value::Number(3)));
+    assert!(matches!(
+        kode.eval("return add(4);").unwrap(),
+        Value::Number(7)
+    ));
+    assert!(matches!(kode.eval("let unused = 0;").unwrap(), Value::Void));
+    assert_eq!(kode.global::<i64>("total").unwrap(), 7);
+}
+
+#[test]
+fn eval_does_not_call_main() {
+    let io = MemoryIo::default();
+    let mut kode = Interpreter::new();
+    kode.set_io(io.clone());
+    kode.eval("fn main() { print \"main\"; }").unwrap();
+    assert_eq!(io.stdout_text(), "");
+
+    kode.call::<()>("main", ()).unwrap();
+    assert_eq!(io.stdout_text(), "main\n");
+}
+
+#[test]
+fn call_converts_arguments_and_results() {
+    let mut kode = Interpreter::new();
+    kode.eval("fn describe(name, age, tags) { return [name + \" \" + age, len(tags)]; }")
+        .unwrap();
+
+    let result: Vec<Value> = kode.call("describe", ("ann", 31, vec!["a", "b"])).unwrap();
+    assert!(matches!(&result[..], [Value::String(s), Value::Number(2)] if s == "ann 31"));
+
+    let error = kode
+        .call::<String>("describe", ("ann", 31, vec!["a"]))
+        .unwrap_err();
+    assert!(error.contains("Expected a string"), "{}", error);
+    assert!(kode.call::<i64>("missing", ()).is_err());
+}
+
+#[test]
+fn globals_set_by_the_host() {
+    let mut kode = Interpreter::new();
+    kode.set_global("limit", 3);
+    kode.set_global("names", vec!["ann", "bob"]);
+    kode.set_global("ages", HashMap::from([("ann".to_string(), 31)]));
+
+    let found: bool = kode
+        .eval("limit > len(names) && has(ages, \"ann\");")
+        .and_then(|v| match v {
+            Value::Bool(b) => Ok(b),
+            other => Err(format!("{:?}", other)),
+        })
+        .unwrap();
+    assert!(found);
+
+    kode.eval("limit = limit * 2;").unwrap();
+    assert_eq!(kode.global::<i64>("limit").unwrap(), 6);
+    assert_eq!(
+        kode.global::<HashMap<String, i64>>("ages").unwrap()["ann"],
+        31
+    );
+    assert!(
+        kode.global::<i64>("undefined")
+            .unwrap_err()
+            .contains("Undefined variable")
+    );
+}
+
+#[test]
+fn host_functions_are_callable() {
+    let mut kode = Interpreter::new();
+    kode.register_fn("double", |n: i64| n * 2);
+    kode.register_fn("join", |parts: Vec<String>, separator: String| {
+        parts.join(&separator)
+    });
+    kode.register_fn("checked", |n: i64| {
+        if n < 0 {
+            Err("negative".to_string())
+        } else {
+            Ok(n)
+        }
+    });
+
+    assert!(matches!(
+        kode.eval("double(21);").unwrap(),
+        Value::Number(42)
+    ));
+    assert!(
+        matches!(kode.eval("join([\"a\", \"b\"], \"-\");").unwrap(), Value::String(s) if s == "a-b")
+    );
+
+    let error = kode.eval("checked(-1);").unwrap_err();
+    assert!(error.contains("negative"), "{}", error);
+    let error = kode.eval("double(\"x\");").unwrap_err();
+    assert!(error.contains("In function 'double'"), "{}", error);
+    let error = kode.eval("double(1, 2);").unwrap_err();
+    assert!(error.contains("expects 1 args, got 2"), "{}", error);
+}
+
+#[test]
+fn program_functions_take_precedence_over_host_functions() {
+    let mut kode = Interpreter::new();
+    kode.register_fn("len", |_: Value| 99);
+    assert!(matches!(kode.eval("len([1]);").unwrap(), Value::Number(99)));
+
+    kode.eval("fn len(x) { return -1; }").unwrap();
+    assert!(matches!(kode.eval("len([1]);").unwrap(), Value::Number(-1)));
+}
+
+#[test]
+fn errors_are_returned_to_the_host() {
+    let mut kode = Interpreter::new();
+    let error = kode.eval("let x = ;").unwrap_err();
+    assert!(error.contains("Expected expression"), "{}", error);
+    let error = kode.eval("[1][5];").unwrap_err();
+    assert!(error.contains("out of bounds"), "{}", error);
+
+    // The interpreter is still usable after an error
+    assert!(matches!(kode.eval("1 + 1;").unwrap(), Value::Number(2)));
+}
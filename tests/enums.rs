@@ -0,0 +1,28 @@
+use kode_compiler::{Interpreter, MemoryIo};
+
+// What `source` prints, or its error
+fn run(source: &str) -> Result<String, String> {
+    let io = MemoryIo::default();
+    let mut kode = Interpreter::new();
+    kode.set_io(io.clone());
+    kode.eval(source)?;
+    Ok(io.stdout_text())
+}
+
+#[test]
+fn enum_values_compare_with_equality_operators() {
+    let source = "enum E { A, B(x) }\nenum F { A }\nlet e = E.A;\nprint e == E.A;\nprint e != E.A;\nprint E.B(1) == E.B(1);\nprint E.B(1) == E.B(2);\nprint E.B(1) != E.A;\nprint E.A == F.A;";
+    assert_eq!(
+        run(source).unwrap(),
+        "true\nfalse\ntrue\nfalse\ntrue\nfalse\n"
+    );
+
+    let error = run("enum E { A }\nprint E.A < E.A;").unwrap_err();
+    assert!(error.contains("Type mismatch"), "{}", error);
+}
+
+#[test]
+fn struct_literal_inside_a_match_subject() {
+    let source = "struct P { x, y }\nenum O { Some(v), None }\nlet sum = match O.Some(P { x: 5, y: 6 }) {\n    O.Some(P { x, y }) => x + y,\n    _ => 0,\n};\nprint sum;\nmatch [P { x: 1, y: 2 }][0] {\n    P { x } => { print x; }\n}";
+    assert_eq!(run(source).unwrap(), "11\n1\n");
+}
@@ -0,0 +1,65 @@
+use std::process::Command;
+
+use kode_compiler::Value;
+use kode_compiler::interpreter::exit_code;
+
+// Exit status of `kode run -e <source>`
+fn run(source: &str) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_kode"))
+        .args(["run", "-e", source])
+        .output()
+        .expect("failed to run kode")
+        .status
+        .code()
+}
+
+#[test]
+fn main_return_value_is_exit_status() {
+    assert_eq!(run("fn main() { return 0; }"), Some(0));
+    assert_eq!(run("fn main() { return 3; }"), Some(3));
+    assert_eq!(run("fn main() { return 255; }"), Some(255));
+}
+
+#[test]
+fn out_of_range_exit_status_fails() {
+    assert_eq!(run("fn main() { return 256; }"), Some(1));
+    assert_eq!(run("fn main() { return -1; }"), Some(1));
+    assert_eq!(
+        run("fn main() { return 9223372036854775807 + 1; }"),
+        Some(1)
+    );
+}
+
+#[test]
+fn runtime_error_exit_status() {
+    assert_eq!(run("fn main() { return [1][5]; }"), Some(1));
+}
+
+#[test]
+fn exit_code_of_values() {
+    assert_eq!(exit_code(&Value::Number(42)), 42);
+    assert_eq!(exit_code(&Value::Number(256)), 1);
+    assert_eq!(exit_code(&Value::Number(-1)), 1);
+    assert_eq!(exit_code(&Value::Number(i64::from(i32::MAX) + 1)), 1);
+    assert_eq!(exit_code(&Value::Void), 0);
+    assert_eq!(exit_code(&Value::String("done".to_string())), 0);
+}
+
+#[test]
+fn program_arguments_and_environment() {
+    let output = Command::new(env!("CARGO_BIN_EXE_kode"))
+        .args([
+            "run",
+            "-e",
+            r#"print args(); print env("KODE_TEST_VAR"); print env("KODE_TEST_UNSET");"#,
+        ])
+        .args(["--", "a", "b c"])
+        .env("KODE_TEST_VAR", "set")
+        .env_remove("KODE_TEST_UNSET")
+        .output()
+        .expect("failed to run kode");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "[a, b c]\nset\nvoid\n"
+    );
+}
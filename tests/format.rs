@@ -0,0 +1,85 @@
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::{kode, temp_dir, write};
+
+// `source` formatted by `kode fmt -`
+fn format(source: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_kode"))
+        .args(["fmt", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run kode fmt");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn formatting_twice_changes_nothing() {
+    let sources = [
+        "fn   add(a,b){return a+b;}\nprint add( 1,2 ) ;\n",
+        "enum Color {\n  Red,\n  Green, // g\n  Blue\n}\nstruct Config {\n    name,\n    // the size\n    size\n}\n",
+        "fn main() {\nlet t = {\n\"one\": 1,\n\"two\": [\n1,\n2\n]\n};\nif (t.one > 1 &&\nt.one < 3) {\nprint \"yes\";\n} // done\nelse {\nprint \"no\";\n}\nlet c = match t.one {\n1 => \"one\",\n_ => \"many\"\n};\nprint \"${c}-${- -1}\";\n}\n",
+        "let f = fn(x) {\nreturn x * 2;\n};\nfor i in 0..3 { print f(i); }\ntry { print 1 / 0; } catch { print \"error\"; }\n",
+    ];
+    for source in sources {
+        let once = format(source);
+        assert_eq!(format(&once), once, "formatting {:?}", source);
+    }
+}
+
+#[test]
+fn comments_and_blank_lines_are_kept() {
+    let source = "/* header */\n// add two numbers\nfn add(a, b) {\n  return a + b; // the sum\n}\n\n\n\nprint add(1, 2);\n";
+    assert_eq!(
+        format(source),
+        "/* header */\n// add two numbers\nfn add(a, b) {\n    return a + b; // the sum\n}\n\nprint add(1, 2);\n"
+    );
+}
+
+#[test]
+fn check_fails_on_unformatted_files_without_changing_them() {
+    let dir = temp_dir("format-check");
+    let source = "fn add(a,b){\nreturn a+b;\n}\n";
+    write(&dir, "main.kode", source);
+
+    let output = kode(&dir, &["fmt", "--check", "main.kode"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Would reformat main.kode\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 file is not formatted"));
+    assert_eq!(fs::read_to_string(dir.join("main.kode")).unwrap(), source);
+
+    let output = kode(&dir, &["fmt", "main.kode"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Formatted main.kode\n"
+    );
+
+    let output = kode(&dir, &["fmt", "--check", "main.kode"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        fs::read_to_string(dir.join("main.kode")).unwrap(),
+        "fn add(a, b) {\n    return a + b;\n}\n"
+    );
+}
@@ -0,0 +1,69 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use kode_compiler::{Interpreter, MemoryIo};
+
+fn interpreter(input: &str) -> (Interpreter, MemoryIo) {
+    let io = MemoryIo::new(input);
+    let mut kode = Interpreter::new();
+    kode.set_io(io.clone());
+    (kode, io)
+}
+
+#[test]
+fn input_prints_the_prompt_and_strips_the_newline() {
+    let (mut kode, io) = interpreter("Ada\r\nBob\n");
+    kode.eval("let a = input(\"Name: \");\nlet b = input();\nprint a + \"|\" + b;")
+        .unwrap();
+    assert_eq!(io.stdout_text(), "Name: Ada|Bob\n");
+}
+
+#[test]
+fn input_at_end_of_input_is_an_error() {
+    let (mut kode, io) = interpreter("last");
+    kode.eval("print input();").unwrap();
+    let error = kode.eval("input(\"More: \");").unwrap_err();
+    assert!(error.contains("reached the end of input"), "{}", error);
+    assert_eq!(io.stdout_text(), "last\nMore: ");
+}
+
+#[test]
+fn read_line_keeps_the_newline_and_is_empty_at_end_of_input() {
+    let (mut kode, io) = interpreter("one\ntwo");
+    kode.eval("let lines = [read_line(), read_line(), read_line()];\nprint len(lines[0]);\nprint len(lines[1]);\nprint len(lines[2]);")
+        .unwrap();
+    assert_eq!(io.stdout_text(), "4\n3\n0\n");
+}
+
+#[test]
+fn print_and_eprint_are_captured_separately() {
+    let (mut kode, io) = interpreter("");
+    kode.eval("print \"out\";\neprint(\"err\");\neprint([1, 2]);")
+        .unwrap();
+    assert_eq!(io.stdout_text(), "out\n");
+    assert_eq!(io.stderr_text(), "err\n[1, 2]\n");
+
+    let error = kode.eval("eprint(1, 2);").unwrap_err();
+    assert!(error.contains("expects 1 arg"), "{}", error);
+}
+
+#[test]
+fn kode_run_uses_the_process_streams() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_kode"))
+        .args([
+            "run",
+            "-e",
+            "let name = input(\"Name? \"); eprint(\"got \" + name); print \"Hi \" + name;",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run kode");
+    child.stdin.take().unwrap().write_all(b"Ada\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Name? Hi Ada\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "got Ada\n");
+}
@@ -0,0 +1,179 @@
+mod common;
+
+use std::path::Path;
+use std::process::Output;
+
+use common::{kode, temp_dir, write};
+
+// `kode lint main.kode` in `dir`, with what it printed
+fn lint(dir: &Path) -> (Output, String) {
+    let output = kode(dir, &["lint", "main.kode"]);
+    let text = String::from_utf8_lossy(&output.stdout).into_owned()
+        + &String::from_utf8_lossy(&output.stderr);
+    (output, text)
+}
+
+// Lint `source`, which must warn about `message` for `rule` at `line`, then
+// again with `// kode:allow(rule)` on a line of its own before that line,
+// which must report nothing
+fn reported_and_allowed(rule: &str, source: &str, line: usize, message: &str) {
+    let dir = temp_dir(&format!("lint-{}", rule));
+    write(&dir, "main.kode", source);
+    let (output, text) = lint(&dir);
+    assert!(output.status.success(), "{}", text);
+    assert_eq!(
+        text,
+        format!(
+            "main.kode:{}: warning: {} [{}]\nFound 1 warning\n",
+            line, message, rule
+        )
+    );
+
+    let mut lines: Vec<String> = source.lines().map(String::from).collect();
+    lines.insert(line - 1, format!("// kode:allow({})", rule));
+    write(&dir, "main.kode", &lines.join("\n"));
+    let (_, text) = lint(&dir);
+    assert_eq!(text, "No problems in main.kode\n");
+}
+
+#[test]
+fn unused_variable() {
+    reported_and_allowed(
+        "unused-variable",
+        "fn main() {\n    let total = 0;\n}\n",
+        2,
+        "Variable 'total' is never used",
+    );
+}
+
+#[test]
+fn unused_parameter() {
+    reported_and_allowed(
+        "unused-parameter",
+        "fn handler(event) {\n    print \"ready\";\n}\n",
+        1,
+        "Parameter 'event' of function 'handler' is never used",
+    );
+}
+
+#[test]
+fn unreachable_code() {
+    reported_and_allowed(
+        "unreachable-code",
+        "fn f() {\n    return 1;\n    print \"never\";\n}\n",
+        3,
+        "Unreachable code after 'return'",
+    );
+}
+
+#[test]
+fn constant_condition() {
+    reported_and_allowed(
+        "constant-condition",
+        "if (1 < 2) {\n    print \"always\";\n}\n",
+        1,
+        "Condition of 'if' is always true",
+    );
+}
+
+#[test]
+fn empty_catch() {
+    reported_and_allowed(
+        "empty-catch",
+        "try {\n    print 1 / 0;\n} catch {\n}\n",
+        1,
+        "Empty 'catch' block ignores the error",
+    );
+}
+
+#[test]
+fn self_assignment() {
+    reported_and_allowed(
+        "self-assignment",
+        "let x = 1;\nx = x;\nprint x;\n",
+        2,
+        "Variable 'x' is assigned to itself",
+    );
+}
+
+#[test]
+fn bool_comparison() {
+    reported_and_allowed(
+        "bool-comparison",
+        "let done = false;\nif (done == true) {\n    print done;\n}\n",
+        2,
+        "Comparing with 'true' using '==' is redundant; use the value itself",
+    );
+}
+
+#[test]
+fn trailing_allow_comment_covers_its_statement_only() {
+    let dir = temp_dir("lint-trailing");
+    write(
+        &dir,
+        "main.kode",
+        "fn main() {\n    let a = 0; // kode:allow(unused-variable)\n    let b = 0;\n}\n",
+    );
+    let (_, text) = lint(&dir);
+    assert_eq!(
+        text,
+        "main.kode:3: warning: Variable 'b' is never used [unused-variable]\nFound 1 warning\n"
+    );
+}
+
+#[test]
+fn levels_are_set_in_the_manifest() {
+    let dir = temp_dir("lint-levels");
+    write(
+        &dir,
+        "main.kode",
+        "fn handler(event) {\n    let unused = 0;\n    try {\n        print 1;\n    } catch {\n    }\n}\n",
+    );
+    write(
+        &dir,
+        "kode.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[lint]\nunused-parameter = \"allow\"\nempty-catch = \"deny\"\n",
+    );
+    let (output, text) = lint(&dir);
+    assert_eq!(output.status.code(), Some(1), "{}", text);
+    assert!(!text.contains("[unused-parameter]"), "{}", text);
+    assert!(
+        text.contains("warning: Variable 'unused' is never used [unused-variable]"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains(": error: Empty 'catch' block ignores the error [empty-catch]"),
+        "{}",
+        text
+    );
+    assert!(text.contains("Found 1 error and 1 warning"), "{}", text);
+
+    // Every rule allowed: nothing is reported
+    write(
+        &dir,
+        "kode.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[lint]\nunused-parameter = \"allow\"\nunused-variable = \"allow\"\nempty-catch = \"allow\"\n",
+    );
+    let (output, text) = lint(&dir);
+    assert!(output.status.success(), "{}", text);
+    assert_eq!(text, "No problems in main.kode\n");
+}
+
+#[test]
+fn unknown_rules_in_the_manifest_are_errors() {
+    let dir = temp_dir("lint-unknown");
+    write(&dir, "main.kode", "print 1;\n");
+    write(
+        &dir,
+        "kode.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[lint]\nunused-things = \"allow\"\n",
+    );
+    let (output, text) = lint(&dir);
+    assert_eq!(output.status.code(), Some(1), "{}", text);
+    assert!(
+        text.contains("Unknown lint rule 'unused-things'"),
+        "{}",
+        text
+    );
+}
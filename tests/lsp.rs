@@ -0,0 +1,105 @@
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde_json::{Value, json};
+
+use common::{temp_dir, write};
+
+// Send `requests` to `kode lsp` in `dir`, then shut it down, and return the
+// responses by id
+fn session(dir: &Path, requests: &[Value]) -> Vec<Value> {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_kode"))
+        .arg("lsp")
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run kode lsp");
+
+    let mut input = server.stdin.take().unwrap();
+    let shutdown = [
+        json!({ "jsonrpc": "2.0", "id": 0, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ];
+    for message in requests.iter().chain(&shutdown) {
+        let body = message.to_string();
+        write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+    drop(input);
+
+    let output = server.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let mut output = String::from_utf8(output.stdout).unwrap();
+    let mut responses = Vec::new();
+    while let Some((headers, rest)) = output.split_once("\r\n\r\n") {
+        let length: usize = headers
+            .trim_start_matches("Content-Length: ")
+            .parse()
+            .unwrap();
+        let response: Value = serde_json::from_str(&rest[..length]).unwrap();
+        if response.get("id").is_some() {
+            responses.push(response);
+        }
+        output = rest[length..].to_string();
+    }
+    responses
+}
+
+fn uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+// Requests opening `file` and asking for the definition at each of `positions`
+fn definitions(file: &Path, text: &str, positions: &[(u32, u32)]) -> Vec<Value> {
+    let mut requests = vec![
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": uri(file), "languageId": "kode", "version": 1, "text": text }
+        } }),
+    ];
+    for (i, (line, character)) in positions.iter().enumerate() {
+        requests.push(
+            json!({ "jsonrpc": "2.0", "id": i + 2, "method": "textDocument/definition", "params": {
+            "textDocument": { "uri": uri(file) },
+            "position": { "line": line, "character": character }
+        } }),
+        );
+    }
+    requests
+}
+
+#[test]
+fn definition_in_nested_module() {
+    let dir = temp_dir("lsp-nested");
+    write(
+        &dir,
+        "util/helper.kode",
+        "pub fn up(x) {\n    return x + 1;\n}\n",
+    );
+    write(
+        &dir,
+        "util/other.kode",
+        "pub fn up(x) {\n    return x + 2;\n}\n",
+    );
+    let text = "import util.other;\nimport util.helper;\nimport util.helper as h;\nprint util.helper.up(1);\nprint h.up(2);\nprint util.other.up(3);\n";
+    write(&dir, "main.kode", text);
+
+    let responses = session(
+        &dir,
+        &definitions(&dir.join("main.kode"), text, &[(3, 18), (4, 8), (5, 17)]),
+    );
+    let helper = uri(&dir.join("util/helper.kode"));
+    let other = uri(&dir.join("util/other.kode"));
+    for (id, expected) in [(2, &helper), (3, &helper), (4, &other)] {
+        let response = responses.iter().find(|r| r["id"] == id).unwrap();
+        assert_eq!(response["result"]["uri"], *expected, "{}", response);
+        assert_eq!(
+            response["result"]["range"]["start"]["line"], 0,
+            "{}",
+            response
+        );
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{interpreter, temp_dir, write};
+
+#[test]
+fn module_without_pub_exports_everything() {
+    let dir = temp_dir("modules-no-pub");
+    write(
+        &dir,
+        "util.kode",
+        "let base = 10;\nfn helper(x) { return x + base; }\n",
+    );
+    let (mut kode, io) = interpreter(&dir);
+
+    kode.eval("import util;\nfrom util import helper;\nprint util.base;\nprint helper(1);")
+        .unwrap();
+    assert_eq!(io.stdout_text(), "10\n11\n");
+}
+
+#[test]
+fn module_with_pub_keeps_other_names_private() {
+    let dir = temp_dir("modules-pub");
+    write(
+        &dir,
+        "util.kode",
+        "let base = 10;\nfn helper(x) { return x + base; }\npub fn add(x) { return helper(x); }\n",
+    );
+    let (mut kode, io) = interpreter(&dir);
+
+    kode.eval("import util;\nprint util.add(1);").unwrap();
+    assert_eq!(io.stdout_text(), "11\n");
+
+    let error = kode.eval("util.helper(1);").unwrap_err();
+    assert!(error.contains("private"), "{}", error);
+    let error = kode.eval("print util.base;").unwrap_err();
+    assert!(error.contains("private"), "{}", error);
+    let error = kode.eval("from util import helper;").unwrap_err();
+    assert!(error.contains("private"), "{}", error);
+}
+
+#[test]
+fn nested_imports_and_module_globals() {
+    let dir = temp_dir("modules-nested");
+    write(
+        &dir,
+        "util/counter.kode",
+        "let count = 0;\npub fn bump() { count = count + 1; return count; }\n",
+    );
+    write(
+        &dir,
+        "app.kode",
+        "import util.counter as c;\npub fn twice() { c.bump(); return c.bump(); }\n",
+    );
+    let (mut kode, io) = interpreter(&dir);
+
+    kode.eval("import app;\nimport util.counter;\nprint app.twice();\nprint util.counter.bump();")
+        .unwrap();
+    assert_eq!(io.stdout_text(), "2\n3\n");
+}
+
+#[test]
+fn import_cycle_is_an_error() {
+    let dir = temp_dir("modules-cycle");
+    write(&dir, "a.kode", "import b;\nfn f() { return 1; }\n");
+    write(&dir, "b.kode", "import a;\nfn g() { return 2; }\n");
+    let (mut kode, _) = interpreter(&dir);
+
+    let error = kode.eval("import a;").unwrap_err();
+    assert!(error.contains("Cyclic import: a -> b -> a"), "{}", error);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{kode, temp_dir, write};
+
+// What `kode run` prints for `source` on stdout and stderr, and whether it succeeded
+fn run(name: &str, source: &str) -> (bool, String, String) {
+    let dir = temp_dir(name);
+    write(&dir, "main.kode", source);
+    let output = kode(&dir, &["run", "main.kode"]);
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn undefined_names_are_reported_with_their_line() {
+    let (success, output, errors) = run(
+        "names-undefined",
+        "print 1;\nprint missing;\nprint nope(1);\n",
+    );
+    assert!(!success);
+    assert!(
+        errors.contains("main.kode:2: error: Undefined variable 'missing'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("main.kode:3: error: Undefined function 'nope'"),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("Found 2 errors"), "{}", errors);
+    // Nothing runs when the names do not resolve
+    assert_eq!(output, "");
+}
+
+#[test]
+fn variables_used_before_their_let_or_outside_their_scope() {
+    let (_, _, errors) = run(
+        "names-scopes",
+        "print later;\nlet later = 1;\nfn g() {\n    for i in [1] {\n        let inner = i;\n    }\n    return inner;\n}\n",
+    );
+    assert!(
+        errors.contains("main.kode:1: error: Variable 'later' is used before its 'let'"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains(
+            "main.kode:7: error: Variable 'inner' is used outside the scope that declares it in function 'g'"
+        ),
+        "{}",
+        errors
+    );
+}
+
+#[test]
+fn duplicate_functions_and_wrong_argument_counts() {
+    let (_, _, errors) = run(
+        "names-functions",
+        "fn f(a) { return a; }\nfn f(b) { return b; }\nprint f(1, 2);\n",
+    );
+    assert!(
+        errors.contains("main.kode:2: error: Function 'f' is defined more than once"),
+        "{}",
+        errors
+    );
+    assert!(
+        errors.contains("main.kode:3: error: Function 'f' expects 1 args, got 2"),
+        "{}",
+        errors
+    );
+}
+
+#[test]
+fn shadowing_is_a_warning() {
+    let (success, output, warnings) = run(
+        "names-shadowing",
+        "let x = 1;\nfor i in [2] {\n    let x = i;\n    print x;\n}\nprint x;\n",
+    );
+    assert!(success, "{}", warnings);
+    assert_eq!(output, "2\n1\n");
+    assert_eq!(
+        warnings,
+        "main.kode:3: warning: Variable 'x' shadows a variable of an enclosing scope\n"
+    );
+}
+
+#[test]
+fn recursive_calls_see_their_own_locals() {
+    let (success, output, errors) = run(
+        "names-recursion",
+        "fn fact(n) {\n    if (n <= 1) {\n        return 1;\n    }\n    let rest = fact(n - 1);\n    {\n        let product = n * rest;\n        return product;\n    }\n}\nprint fact(5);\nfn depth(n) {\n    let here = n;\n    for i in [0] {\n        if (n > 0) {\n            depth(n - 1);\n        }\n    }\n    return here;\n}\nprint depth(3);\n",
+    );
+    assert!(success, "{}", errors);
+    assert_eq!(output, "120\n3\n");
+}
+
+#[test]
+fn closures_called_from_deeper_scopes_see_their_own_variables() {
+    let (success, output, errors) = run(
+        "names-closures",
+        "let base = 10;\nlet add = fn(x) { return x + base; };\n{\n    let base = 100;\n    print add(1);\n    for i in [2] {\n        print add(i);\n    }\n}\nfn twice(f) {\n    let total = 0;\n    for i in [1, 2] {\n        for j in [3] {\n            total = total + f(i * j);\n        }\n    }\n    return total;\n}\nprint twice(add);\n",
+    );
+    assert!(success, "{}", errors);
+    assert_eq!(output, "11\n12\n29\n");
+}
@@ -0,0 +1,49 @@
+use kode_compiler::{Interpreter, MemoryIo};
+
+// What `source` prints, or its error
+fn run(source: &str) -> Result<String, String> {
+    let io = MemoryIo::default();
+    let mut kode = Interpreter::new();
+    kode.set_io(io.clone());
+    kode.eval(source)?;
+    Ok(io.stdout_text())
+}
+
+#[test]
+fn integer_literals_in_each_base() {
+    assert_eq!(
+        run("print 0xff;\nprint 0O17;\nprint 0b1010;\nprint 1_000_000;\nprint 0xff_ff;\nprint 1.5_5;").unwrap(),
+        "255\n15\n10\n1000000\n65535\n1.55\n"
+    );
+}
+
+#[test]
+fn digit_separators_must_be_between_digits() {
+    for source in [
+        "print 1_;",
+        "print 1__0;",
+        "print 0x_ff;",
+        "print 0b1_;",
+        "print 1_.5;",
+        "print 1e1_;",
+    ] {
+        let error = run(source).unwrap_err();
+        assert!(
+            error.contains("Digit separator '_' must be between digits"),
+            "{}: {}",
+            source,
+            error
+        );
+    }
+    let error = run("print 0x;").unwrap_err();
+    assert!(error.contains("Expected digits after '0x'"), "{}", error);
+}
+
+#[test]
+fn bitwise_operators() {
+    assert_eq!(
+        run("print 6 & 3;\nprint 6 | 1;\nprint 6 ^ 3;\nprint ~5;\nprint 1 << 4;\nprint -16 >> 2;")
+            .unwrap(),
+        "2\n7\n5\n-6\n16\n-4\n"
+    );
+}
@@ -0,0 +1,274 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{kode, temp_dir, write};
+
+// A package whose entry defines `greet()`, returning `greeting`
+fn write_library(dir: &Path, name: &str, version: &str, greeting: &str) {
+    let manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"{}\"\nentry = \"lib.kode\"\n",
+        name, version
+    );
+    write(dir, "kode.toml", &manifest);
+    write(
+        dir,
+        "lib.kode",
+        &format!("pub fn greet() {{ return \"{}\"; }}\n", greeting),
+    );
+}
+
+// A package that prints the greeting of `library`, declared with `spec` if given
+fn write_app(dir: &Path, library: &str, spec: Option<&str>) {
+    let dependency = spec
+        .map(|spec| format!("{} = {}\n", library, spec))
+        .unwrap_or_default();
+    write(
+        dir,
+        "kode.toml",
+        &format!(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+            dependency
+        ),
+    );
+    write(
+        dir,
+        "src/main.kode",
+        &format!("import {0};\nprint {0}.greet();\n", library),
+    );
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    assert!(
+        !output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=Kode", "-c", "user.email=kode@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run git")
+        .status;
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn init_creates_a_runnable_package() {
+    let dir = temp_dir("packages-init");
+    stdout(&kode(&dir, &["init", "hello"]));
+    assert_eq!(
+        stdout(&kode(&dir.join("hello"), &["run"])),
+        "Hello, World!\n"
+    );
+    assert!(!dir.join("hello/kode.lock").exists());
+}
+
+#[test]
+fn path_dependency_lock_round_trip() {
+    let dir = temp_dir("packages-path");
+    write_library(&dir.join("strings"), "strings", "1.0.0", "hi");
+    write_app(&dir.join("app"), "strings", None);
+    let app = dir.join("app");
+
+    stdout(&kode(&app, &["add", "strings", "--path", "../strings"]));
+    let lock = fs::read_to_string(app.join("kode.lock")).unwrap();
+    assert!(lock.contains("source = \"path+../strings\""), "{}", lock);
+    assert_eq!(stdout(&kode(&app, &["run"])), "hi\n");
+
+    // Running only reads the lock
+    let modified = fs::metadata(app.join("kode.lock"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    stdout(&kode(&app, &["run"]));
+    assert_eq!(
+        fs::metadata(app.join("kode.lock"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        modified
+    );
+    assert_eq!(fs::read_to_string(app.join("kode.lock")).unwrap(), lock);
+
+    // The lock is the same wherever the packages are
+    let moved = dir.with_extension("moved");
+    let _ = fs::remove_dir_all(&moved);
+    fs::rename(&dir, &moved).unwrap();
+    assert_eq!(stdout(&kode(&moved.join("app"), &["run"])), "hi\n");
+    stdout(&kode(&moved.join("app"), &["install"]));
+    assert_eq!(
+        fs::read_to_string(moved.join("app/kode.lock")).unwrap(),
+        lock
+    );
+    fs::remove_dir_all(moved).unwrap();
+}
+
+#[test]
+fn run_requires_an_up_to_date_lock() {
+    let dir = temp_dir("packages-stale");
+    write_library(&dir.join("strings"), "strings", "1.0.0", "hi");
+    write_library(&dir.join("other"), "other", "1.0.0", "hey");
+    let app = dir.join("app");
+    write_app(&app, "strings", Some("{ path = \"../strings\" }"));
+
+    assert!(stderr(&kode(&app, &["run"])).contains("kode.lock is missing; run `kode install`"));
+    assert!(!app.join("kode.lock").exists());
+
+    stdout(&kode(&app, &["install"]));
+    assert_eq!(stdout(&kode(&app, &["run"])), "hi\n");
+
+    let manifest = fs::read_to_string(app.join("kode.toml")).unwrap();
+    fs::write(
+        app.join("kode.toml"),
+        manifest + "other = { path = \"../other\" }\n",
+    )
+    .unwrap();
+    assert!(stderr(&kode(&app, &["run"])).contains("kode.lock is out of date; run `kode install`"));
+    stdout(&kode(&app, &["install"]));
+    assert_eq!(stdout(&kode(&app, &["run"])), "hi\n");
+}
+
+#[test]
+fn registry_dependency_is_locked_by_version() {
+    let dir = temp_dir("packages-registry");
+    write_library(
+        &dir.join("registry/mathx/1.2.0"),
+        "mathx",
+        "1.2.0",
+        "one two",
+    );
+    write_library(
+        &dir.join("registry/mathx/1.4.1"),
+        "mathx",
+        "1.4.1",
+        "one four",
+    );
+    write_library(&dir.join("registry/mathx/2.0.0"), "mathx", "2.0.0", "two");
+    let app = dir.join("app");
+    write_app(&app, "mathx", Some("\"1.2\""));
+    let manifest = fs::read_to_string(app.join("kode.toml")).unwrap();
+    fs::write(
+        app.join("kode.toml"),
+        manifest + "\n[registry]\npath = \"../registry\"\n",
+    )
+    .unwrap();
+
+    stdout(&kode(&app, &["install"]));
+    let lock = fs::read_to_string(app.join("kode.lock")).unwrap();
+    assert!(
+        lock.contains("version = \"1.4.1\"\nsource = \"registry\""),
+        "{}",
+        lock
+    );
+    assert!(!lock.contains(dir.to_string_lossy().as_ref()), "{}", lock);
+    assert_eq!(stdout(&kode(&app, &["run"])), "one four\n");
+}
+
+#[test]
+fn git_dependency_is_locked_to_a_commit() {
+    let dir = temp_dir("packages-git");
+    let repo = dir.join("greeter");
+    write_library(&repo, "greeter", "1.0.0", "first");
+    git(&repo, &["init", "--quiet"]);
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "--quiet", "-m", "First"]);
+    git(&repo, &["tag", "v1"]);
+    write(&repo, "lib.kode", "pub fn greet() { return \"second\"; }\n");
+    git(&repo, &["commit", "--quiet", "-am", "Second"]);
+
+    let app = dir.join("app");
+    write_app(&app, "greeter", None);
+    stdout(&kode(
+        &app,
+        &["add", "greeter", "--git", "../greeter", "--rev", "v1"],
+    ));
+    let lock = fs::read_to_string(app.join("kode.lock")).unwrap();
+    assert!(lock.contains("source = \"git+../greeter#"), "{}", lock);
+    assert_eq!(stdout(&kode(&app, &["run"])), "first\n");
+
+    // A missing checkout is fetched again by install, not by run
+    fs::remove_dir_all(app.join(".kode")).unwrap();
+    assert!(stderr(&kode(&app, &["run"])).contains("run `kode install`"));
+    stdout(&kode(&app, &["install"]));
+    assert_eq!(fs::read_to_string(app.join("kode.lock")).unwrap(), lock);
+    assert_eq!(stdout(&kode(&app, &["run"])), "first\n");
+}
+
+#[test]
+fn git_options_in_dependencies_are_rejected() {
+    let dir = temp_dir("packages-git-options");
+    let app = dir.join("app");
+    write_app(
+        &app,
+        "evil",
+        Some("{ git = \"../repo\", rev = \"--upload-pack=touch pwned\" }"),
+    );
+    assert!(stderr(&kode(&app, &["install"])).contains("must not start with '-'"));
+
+    write_app(
+        &app,
+        "evil",
+        Some("{ git = \"--upload-pack=touch pwned\" }"),
+    );
+    assert!(stderr(&kode(&app, &["install"])).contains("must not start with '-'"));
+
+    write_app(&app, "evil", None);
+    let error = stderr(&kode(
+        &app,
+        &["add", "evil", "--git", "../repo", "--rev=--output=pwned"],
+    ));
+    assert!(error.contains("must not start with '-'"), "{}", error);
+    assert!(!app.join("pwned").exists());
+}
+
+#[test]
+fn dependency_names_must_be_valid() {
+    let dir = temp_dir("packages-names");
+    let app = dir.join("app");
+    write_app(&app, "strings", None);
+    let manifest = fs::read_to_string(app.join("kode.toml")).unwrap();
+    fs::write(
+        app.join("kode.toml"),
+        manifest + "\"../../victim\" = { git = \"../repo\" }\n",
+    )
+    .unwrap();
+    let error = stderr(&kode(&app, &["install"]));
+    assert!(
+        error.contains("Invalid dependency name '../../victim'"),
+        "{}",
+        error
+    );
+    assert!(stderr(&kode(&app, &["run"])).contains("Invalid dependency name"));
+    assert!(fs::read_dir(&dir).unwrap().count() == 1);
+
+    write_app(&app, "strings", None);
+    let error = stderr(&kode(
+        &app,
+        &["add", "x = 1\n[package]\ny", "--path", "../strings"],
+    ));
+    assert!(error.contains("Invalid dependency name"), "{}", error);
+    let error = stderr(&kode(&app, &["add", "../strings", "--path", "../strings"]));
+    assert!(error.contains("Invalid dependency name"), "{}", error);
+    assert!(
+        !fs::read_to_string(app.join("kode.toml"))
+            .unwrap()
+            .contains("x = 1")
+    );
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{interpreter, temp_dir, write};
+
+// Two modules that each declare `struct Point` with a different method
+fn write_points(dir: &std::path::Path) {
+    write(
+        dir,
+        "flat.kode",
+        "struct Point { x, y }\nimpl Point {\n    fn new(x, y) { return Point { x: x, y: y }; }\n    fn norm(self) { return self.x + self.y; }\n}\n",
+    );
+    write(
+        dir,
+        "space.kode",
+        "struct Point { x, y, z }\nimpl Point {\n    fn new(x, y, z) { return Point { x: x, y: y, z: z }; }\n    fn norm(self) { return self.x * self.y * self.z; }\n}\n",
+    );
+}
+
+#[test]
+fn structs_of_the_same_name_are_kept_per_module() {
+    let dir = temp_dir("structs-modules");
+    write_points(&dir);
+    let (mut kode, io) = interpreter(&dir);
+
+    kode.eval("import flat;\nimport space as s;\nprint flat.Point.new(1, 2).norm();\nprint s.Point.new(2, 3, 4).norm();\nprint flat.Point.new(5, 6).norm();").unwrap();
+    assert_eq!(io.stdout_text(), "3\n24\n11\n");
+}
+
+#[test]
+fn imported_struct_is_used_by_name() {
+    let dir = temp_dir("structs-from-import");
+    write_points(&dir);
+    let (mut kode, io) = interpreter(&dir);
+
+    kode.eval("import flat;\nfrom space import Point;\nlet p = Point { x: 1, y: 2, z: 3 };\nprint p.norm();\nprint Point.new(1, 1, 1).norm();").unwrap();
+    assert_eq!(io.stdout_text(), "6\n1\n");
+}
+
+#[test]
+fn struct_declared_by_several_modules_is_ambiguous() {
+    let dir = temp_dir("structs-ambiguous");
+    write_points(&dir);
+    let (mut kode, _) = interpreter(&dir);
+
+    let error = kode
+        .eval("import flat;\nimport space;\nlet p = Point { x: 1, y: 2 };")
+        .unwrap_err();
+    assert!(error.contains("declared in several modules"), "{}", error);
+}
+
+#[test]
+fn enums_of_the_same_name_do_not_match_each_other() {
+    let dir = temp_dir("structs-enums");
+    write(
+        &dir,
+        "a.kode",
+        "enum Shape { Dot, Line(len) }\nfn dot() { return Shape.Dot; }\n",
+    );
+    write(&dir, "b.kode", "enum Shape { Dot, Square(side) }\n");
+    let (mut kode, io) = interpreter(&dir);
+
+    kode.eval("import a;\nfrom b import Shape;\nlet kind = match a.dot() {\n    Shape.Dot => \"b\",\n    _ => \"a\",\n};\nprint kind;\nprint Shape.Square(2);").unwrap();
+    assert_eq!(io.stdout_text(), "a\nShape.Square(2)\n");
+}